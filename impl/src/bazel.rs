@@ -14,22 +14,41 @@
 
 use anyhow::{anyhow, Result};
 
+use rayon::prelude::*;
+
 use tera::{self, Context, Tera};
 
 use crate::{
   context::{CrateContext, WorkspaceContext},
   planning::PlannedBuild,
-  rendering::{BuildRenderer, FileOutputs, RenderDetails},
-  util::RazeError,
+  rendering::{
+    add_legacy_buildfile_stubs, ignore_crates_rendered, normalize_line_endings,
+    sort_by_repository_name, BuildRenderer, CrateRenderedHook, FileOutputs, RenderDetails,
+  },
+  settings::{AliasNameStyle, FetchMode},
+  starlark::{escape_starlark, escape_starlark_docstring},
+  util::{self, RazeError},
 };
 
-use std::{env, error::Error, iter::Iterator, path::PathBuf};
+use semver::Version;
+
+use std::{
+  collections::{BTreeMap, HashMap},
+  env,
+  error::Error,
+  io::Write,
+  iter::Iterator,
+  path::{Path, PathBuf},
+  process::{Command, Stdio},
+};
 
 use cfg_expr::{
-  targets::get_builtin_target_by_triple,
-  Expression, Predicate,
+  expr::TargetPredicate,
+  targets::{get_builtin_target_by_triple, TargetInfo},
+  Expression, ParseError, Predicate,
 };
 
+/** The platform triples used when `supported_platform_triples` is not set in `RazeSettings` */
 static SUPPORTED_PLATFORM_TRIPLES: &'static [&'static str] = &[
   // SUPPORTED_T1_PLATFORM_TRIPLES
   "i686-apple-darwin",
@@ -53,9 +72,205 @@ static SUPPORTED_PLATFORM_TRIPLES: &'static [&'static str] = &[
   "x86_64-unknown-freebsd",
 ];
 
+lazy_static! {
+  /** The `TargetInfo`s of the default `SUPPORTED_PLATFORM_TRIPLES`, resolved once. */
+  static ref SUPPORTED_PLATFORM_TARGET_INFOS: Vec<&'static TargetInfo<'static>> =
+    SUPPORTED_PLATFORM_TRIPLES
+      .iter()
+      .map(|triple| get_builtin_target_by_triple(triple).unwrap())
+      .collect();
+}
+
+/**
+ * Yields the targets for the provided platform triples, or the built-in defaults if none were
+ * provided.
+ *
+ * Triples unknown to rustc are skipped. These are rejected when loading settings.
+ */
+fn supported_target_infos(
+  supported_platform_triples: &[String],
+) -> Vec<&'static TargetInfo<'static>> {
+  if supported_platform_triples.is_empty() {
+    SUPPORTED_PLATFORM_TARGET_INFOS.clone()
+  } else {
+    supported_platform_triples
+      .iter()
+      .filter_map(|triple| get_builtin_target_by_triple(triple))
+      .collect()
+  }
+}
+
+/**
+ * The key `target_family` predicates are rewritten to when their family is unknown to cfg-expr.
+ *
+ * cfg-expr only knows of the `unix` and `windows` families and fails to parse any other, such as
+ * `wasm`. Renaming the key leaves the predicate as a `Predicate::KeyValue` to be matched by raze.
+ */
+const RAZE_TARGET_FAMILY_KEY: &str = "raze_target_family";
+
+/** The target families cfg-expr does not know of. */
+static UNPARSED_TARGET_FAMILIES: &[&str] = &["wasm"];
+
+/**
+ * The prefix of the cfg options known to rustc that cfg-expr cannot parse.
+ *
+ * cfg-expr fails to parse any `target_` option besides the ones it matches, such as
+ * `target_has_atomic`. Prefixing them turns them into custom cfg options, which match no platform.
+ */
+const RAZE_UNPARSED_OPTION_PREFIX: &str = "raze_";
+
+/** The cfg options known to rustc that cfg-expr does not know of. */
+static UNPARSED_CFG_OPTIONS: &[&str] = &[
+  "target_abi",
+  "target_has_atomic",
+  "target_has_atomic_equal_alignment",
+  "target_has_atomic_load_store",
+  "target_thread_local",
+];
+
+/** Evaluates a single cfg predicate against the given target.
+ *
+ * N.B. cfg-expr parses the `target_arch`, `target_os`, `target_env`, `target_vendor`,
+ * `target_family` and `target_pointer_width` keys (as well as bare `unix` and `windows`) into
+ * `Predicate::Target`, matched against the corresponding part of the triple (`target_vendor =
+ * "apple"` matches the `*-apple-*` triples, `target_env = "musl"` the `*-musl` ones). The bare
+ * `target` key, used to express a plain target triple, is left as a `Predicate::KeyValue`, as are
+ * the `target_family` keys rewritten by `parse_target_expression`. The other options it rewrites,
+ * such as `target_has_atomic`, match no target.
+ */
+fn matches_target_predicate(pred: &Predicate, target_info: &TargetInfo) -> bool {
+  match pred {
+    // cfg-expr leaves the OS of bare-metal targets unset, where rustc reports "none"
+    Predicate::Target(TargetPredicate::Os(os)) if os.0 == "none" => target_info.os.is_none(),
+    Predicate::Target(tp) => tp.matches(target_info),
+    Predicate::KeyValue {
+      key,
+      val,
+    } => match *key {
+      "target" => *val == target_info.triple,
+      RAZE_TARGET_FAMILY_KEY => target_families(target_info).contains(&val.to_string()),
+      _ => false,
+    },
+    // For now there is no other kind of matching
+    _ => false,
+  }
+}
+
+/** Yields the names of the target families the given target belongs to. */
+fn target_families(target_info: &TargetInfo) -> Vec<String> {
+  let mut families: Vec<String> = target_info
+    .family
+    .iter()
+    .map(|family| format!("{:?}", family))
+    .collect();
+
+  // cfg-expr does not know of the `wasm` family, so it is derived from the architecture
+  if target_info.triple.starts_with("wasm32-") {
+    families.push("wasm".to_owned());
+  }
+
+  families
+}
+
+/**
+ * Renames the key of `target_family` predicates whose family cfg-expr cannot parse.
+ *
+ * For example, `cfg(target_family = "wasm")` becomes `cfg(raze_target_family = "wasm")`.
+ */
+fn rewrite_unparsed_target_families(target_exp: &str) -> String {
+  let mut rewritten = String::with_capacity(target_exp.len());
+  let mut rest = target_exp;
+
+  while let Some(index) = rest.find("target_family") {
+    let (before, after) = rest.split_at(index);
+    let after_key = &after["target_family".len()..];
+    let is_key_start = !matches!(before.chars().last(), Some(c) if c.is_alphanumeric() || c == '_');
+    let is_unparsed_family = match after_key.trim_start().strip_prefix('=') {
+      Some(value) => UNPARSED_TARGET_FAMILIES
+        .iter()
+        .any(|family| value.trim_start().starts_with(&format!("\"{}\"", family))),
+      None => false,
+    };
+
+    rewritten.push_str(before);
+    if is_key_start && is_unparsed_family {
+      rewritten.push_str(RAZE_TARGET_FAMILY_KEY);
+    } else {
+      rewritten.push_str("target_family");
+    }
+    rest = after_key;
+  }
+
+  rewritten.push_str(rest);
+  rewritten
+}
+
+/**
+ * Prefixes the cfg options cfg-expr cannot parse, leaving the quoted values untouched.
+ *
+ * For example, `cfg(target_has_atomic = "64")` becomes `cfg(raze_target_has_atomic = "64")`.
+ */
+fn rewrite_unparsed_cfg_options(target_exp: &str) -> String {
+  let mut rewritten = String::with_capacity(target_exp.len());
+  let mut option = String::new();
+  let mut is_quoted = false;
+
+  for c in target_exp.chars().chain(std::iter::once(' ')) {
+    if !is_quoted && (c.is_alphanumeric() || c == '_') {
+      option.push(c);
+      continue;
+    }
+
+    if UNPARSED_CFG_OPTIONS.contains(&option.as_str()) {
+      rewritten.push_str(RAZE_UNPARSED_OPTION_PREFIX);
+    }
+    rewritten.push_str(&option);
+    option.clear();
+
+    if c == '"' {
+      is_quoted = !is_quoted;
+    }
+    rewritten.push(c);
+  }
+
+  // Drop the trailing space terminating the last option
+  rewritten.pop();
+  rewritten
+}
+
+/**
+ * Parses the target of a platform-specific dependency, which is either a cfg expression or a plain
+ * target triple.
+ */
+pub fn parse_target_expression(target: &str) -> Result<Expression, ParseError> {
+  // Ensure the target is represented as an expression
+  let target_exp = match target.starts_with("cfg(") {
+    true => rewrite_unparsed_cfg_options(&rewrite_unparsed_target_families(target)),
+    false => format!("cfg(target = \"{}\")", target),
+  };
+
+  Expression::parse(&target_exp)
+}
+
+/** Describes how a platform target maps onto the platforms supported by rules_rust. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlatformSupport {
+  // Whether the target matches at least one supported platform
+  pub is_supported: bool,
+  // Whether the target matches every supported platform
+  pub matches_all: bool,
+}
+
+impl From<PlatformSupport> for (bool, bool) {
+  /** Yields the legacy `(is_supported, matches_all)` representation. */
+  fn from(support: PlatformSupport) -> Self {
+    (support.is_supported, support.matches_all)
+  }
+}
+
 /** Determines if the target matches those supported by and defined in rules_rust
  *
- * Examples can be seen below:
+ * Examples can be seen below, where "returns" is `(is_supported, matches_all)`:
  *
  * | target                                | returns          | reason                                           |
  * | ------------------------------------- | ---------------- | ------------------------------------------------ |
@@ -76,19 +291,27 @@ static SUPPORTED_PLATFORM_TRIPLES: &'static [&'static str] = &[
  * |                                       |                  |                                                  |
  * | `cfg(foo)`                            | `(false, false)` | `foo` is not a strongly defined cfg value.       |
  * | `cfg(target_os = "redox")`            | `(false, false)` | `redox` is not a supported platform.             |
+ * |                                       |                  |                                                  |
+ * | `cfg(target_os = "none")`             | `(false, false)` | No default platform is bare-metal. Bare-metal    |
+ * |                                       |                  | triples, when supported, match it.               |
+ * |                                       |                  |                                                  |
+ * | `cfg(not(target_os = "none"))`        | `(true, true)`   | Every default platform has an operating system.  |
+ *
+ * The `supported_platform_triples` are the triples to match against. If empty, the built-in
+ * defaults are used instead.
  */
-pub fn is_bazel_supported_platform(target: &String) -> (bool, bool) {
-  // Ensure the target is represented as an expression
-  let target_exp = match target.starts_with("cfg(") {
-    true => target.clone(),
-    false => format!("cfg(target = \"{}\")", target),
-  };
-
-  let expression = match Expression::parse(&target_exp) {
+pub fn is_bazel_supported_platform(
+  target: &str,
+  supported_platform_triples: &[String],
+) -> PlatformSupport {
+  let expression = match parse_target_expression(target) {
     Ok(exp) => exp,
     // If the target expression cannot be parsed it is not considered a Bazel platform
     Err(_) => {
-      return (false, false);
+      return PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      };
     },
   };
 
@@ -96,53 +319,36 @@ pub fn is_bazel_supported_platform(target: &String) -> (bool, bool) {
   let mut matches_all = true;
 
   // Attempt to match the expression
-  for target_info in SUPPORTED_PLATFORM_TRIPLES
-    .iter()
-    .map(|x| get_builtin_target_by_triple(x).unwrap())
-  {
-    if expression.eval(|pred| {
-      match pred {
-        Predicate::Target(tp) => tp.matches(target_info),
-        Predicate::KeyValue {
-          key,
-          val,
-        } => (*key == "target") && (*val == target_info.triple),
-        // For now there is no other kind of matching
-        _ => false,
-      }
-    }) {
+  for target_info in supported_target_infos(supported_platform_triples) {
+    if expression.eval(|pred| matches_target_predicate(pred, target_info)) {
       is_supported = true;
     } else {
       matches_all = false;
     }
   }
 
-  (is_supported, matches_all)
+  PlatformSupport {
+    is_supported,
+    matches_all,
+  }
 }
 
 /** Maps a Rust cfg target to a Bazel supported triples.
  *
  * Note, the Bazel triples must be defined in:
  * https://github.com/bazelbuild/rules_rust/blob/master/rust/platform/platform.bzl
+ *
+ * If `supported_platform_triples` is empty, the built-in defaults are used instead.
  */
-pub fn get_matching_bazel_triples(target: &String) -> Result<Vec<String>> {
-  let target_exp = match target.starts_with("cfg(") {
-    true => target.clone(),
-    false => format!("cfg(target=\"{}\")", target),
-  };
-
-  let expression = Expression::parse(&target_exp)?;
-  let triples: Vec<String> = SUPPORTED_PLATFORM_TRIPLES
+pub fn get_matching_bazel_triples(
+  target: &str,
+  supported_platform_triples: &[String],
+) -> Result<Vec<String>> {
+  let expression = parse_target_expression(target)?;
+  let triples: Vec<String> = supported_target_infos(supported_platform_triples)
     .iter()
-    .filter_map(|triple| {
-      let target_info = get_builtin_target_by_triple(triple).unwrap();
-      match expression.eval(|pred| {
-        match pred {
-          Predicate::Target(tp) => tp.matches(target_info),
-          // For now there is no other kind of matching
-          _ => false,
-        }
-      }) {
+    .filter_map(|target_info| {
+      match expression.eval(|pred| matches_target_predicate(pred, target_info)) {
         true => Some(String::from((*target_info).triple)),
         false => None,
       }
@@ -152,23 +358,111 @@ pub fn get_matching_bazel_triples(target: &String) -> Result<Vec<String>> {
   Ok(triples)
 }
 
-/** Produces a list of triples based on a provided whitelist */
-pub fn filter_bazel_triples(triples: &mut Vec<String>, triples_whitelist: &Vec<String>) {
+/**
+ * Maps a Rust cfg target onto the given supported platform triples.
+ *
+ * This is the same evaluation used when planning platform-specific dependencies, exposed for
+ * tools which want to reuse it outside of the rendering pipeline. The resulting triples are
+ * deduplicated and sorted. If `supported` is empty, the built-in defaults are used instead.
+ *
+ * ```
+ * use cargo_raze::bazel::bazel_triples_for_cfg;
+ *
+ * let supported = ["x86_64-unknown-linux-gnu", "x86_64-apple-darwin", "x86_64-pc-windows-gnu"];
+ *
+ * assert_eq!(
+ *   bazel_triples_for_cfg("cfg(unix)", &supported).unwrap(),
+ *   vec!["x86_64-apple-darwin", "x86_64-unknown-linux-gnu"]
+ * );
+ * assert_eq!(
+ *   bazel_triples_for_cfg("cfg(not(target_os = \"macos\"))", &supported).unwrap(),
+ *   vec!["x86_64-pc-windows-gnu", "x86_64-unknown-linux-gnu"]
+ * );
+ * assert_eq!(
+ *   bazel_triples_for_cfg("x86_64-pc-windows-gnu", &supported).unwrap(),
+ *   vec!["x86_64-pc-windows-gnu"]
+ * );
+ * assert!(bazel_triples_for_cfg("cfg(target_os = \"redox\")", &supported)
+ *   .unwrap()
+ *   .is_empty());
+ *
+ * // Triples unknown to rustc are rejected
+ * assert!(bazel_triples_for_cfg("cfg(unix)", &["not-a-triple"]).is_err());
+ * ```
+ */
+pub fn bazel_triples_for_cfg(cfg: &str, supported: &[&str]) -> Result<Vec<String>> {
+  let supported_platform_triples = supported
+    .iter()
+    .map(|triple| {
+      get_builtin_target_by_triple(triple.trim())
+        .map(|target_info| String::from(target_info.triple))
+        .ok_or_else(|| RazeError::Generic(format!("Unknown platform triple: {}", triple)))
+    })
+    .collect::<Result<Vec<String>, RazeError>>()?;
+
+  let mut triples = get_matching_bazel_triples(cfg.trim(), &supported_platform_triples)?;
+  triples.sort();
+  triples.dedup();
+  Ok(triples)
+}
+
+/** Returns the triples of every supported platform. */
+pub fn get_supported_bazel_triples(supported_platform_triples: &[String]) -> Vec<String> {
+  supported_target_infos(supported_platform_triples)
+    .iter()
+    .map(|target_info| String::from(target_info.triple))
+    .collect()
+}
+
+/** Produces a sorted, deduplicated list of triples based on a provided whitelist
+ *
+ * Every entry of the whitelist must be a platform triple known to rustc, otherwise a typo would
+ * silently filter out every triple.
+ */
+pub fn filter_bazel_triples(
+  triples: &mut Vec<String>,
+  triples_whitelist: &[String],
+) -> Result<(), RazeError> {
   // Early-out if the filter list is empty
   if triples_whitelist.len() == 0 {
-    return;
+    return Ok(());
+  }
+
+  if let Some(unknown_triple) = triples_whitelist
+    .iter()
+    .find(|triple| get_builtin_target_by_triple(triple).is_none())
+  {
+    return Err(RazeError::Config {
+      field_path_opt: Some("raze.targets".to_owned()),
+      message: format!(
+        "\"{}\" is not a platform triple known to rustc. Run `rustc --print target-list` for the \
+         list of known triples.",
+        unknown_triple
+      ),
+    });
   }
 
   // Prune everything that's not found in the whitelist
   triples.retain(|triple| triples_whitelist.iter().any(|i| i == triple));
 
   triples.sort();
+  triples.dedup();
+  Ok(())
 }
 
 /** Returns a list of Bazel targets for use in `select` statements based on a
  * given list of triples.
+ *
+ * The targets are labels within the `rules_rust_repository_name` repository. Each triple must be
+ * one of `supported_platform_triples` (or of the built-in defaults, if empty).
  */
-pub fn generate_bazel_conditions(triples: &Vec<String>) -> Result<Vec<String>> {
+pub fn generate_bazel_conditions(
+  rules_rust_repository_name: &str,
+  triples: &[String],
+  supported_platform_triples: &[String],
+) -> Result<Vec<String>> {
+  let supported_target_infos = supported_target_infos(supported_platform_triples);
+
   // Sanity check ensuring all strings represent real triples
   for triple in triples.iter() {
     match get_builtin_target_by_triple(triple) {
@@ -177,11 +471,18 @@ pub fn generate_bazel_conditions(triples: &Vec<String>) -> Result<Vec<String>> {
       },
       _ => {},
     }
+
+    if !supported_target_infos
+      .iter()
+      .any(|target_info| target_info.triple == triple)
+    {
+      return Err(anyhow!("Not a supported platform triple: '{}'", triple));
+    }
   }
 
   let mut bazel_triples: Vec<String> = triples
     .iter()
-    .map(|triple| format!("@io_bazel_rules_rust//rust/platform:{}", triple))
+    .map(|triple| format!("@{}//rust/platform:{}", rules_rust_repository_name, triple))
     .collect();
 
   bazel_triples.sort();
@@ -189,9 +490,16 @@ pub fn generate_bazel_conditions(triples: &Vec<String>) -> Result<Vec<String>> {
   Ok(bazel_triples)
 }
 
-/** Returns whether or not the given path is a Bazel workspace root */
+/** Returns whether or not the given path is a Bazel workspace root
+ *
+ * Workspaces using bzlmod may only have a `MODULE.bazel` file at their root.
+ */
 pub fn is_workspace_root(dir: &PathBuf) -> bool {
-  let workspace_files = [dir.join("WORKSPACE.bazel"), dir.join("WORKSPACE")];
+  let workspace_files = [
+    dir.join("WORKSPACE.bazel"),
+    dir.join("WORKSPACE"),
+    dir.join("MODULE.bazel"),
+  ];
 
   for workspace in workspace_files.iter() {
     if workspace.exists() {
@@ -204,12 +512,14 @@ pub fn is_workspace_root(dir: &PathBuf) -> bool {
 
 /** Returns a path to a Bazel workspace root based on the current working
  * directory, otherwise None if not workspace is detected.
+ *
+ * Symlinks in the working directory are resolved first, so the returned path is the real location
+ * of the workspace root.
  */
 pub fn find_workspace_root() -> Option<PathBuf> {
-  let mut dir = match env::current_dir() {
-    Ok(result) => Some(result),
-    Err(_) => None,
-  };
+  let mut dir = env::current_dir()
+    .and_then(|current_dir| current_dir.canonicalize())
+    .ok();
 
   while let Some(current_dir) = dir {
     if is_workspace_root(&current_dir) {
@@ -225,15 +535,136 @@ pub fn find_workspace_root() -> Option<PathBuf> {
   return None;
 }
 
+const DEFAULT_BUILDIFIER_BIN_PATH: &str = "buildifier";
+
+/** Pipes the contents of a single rendered file through buildifier. */
+fn format_with_buildifier(buildifier_path: &str, path: &str, contents: &str) -> Result<String> {
+  let file_type = if path.ends_with(".bzl") {
+    "bzl"
+  } else {
+    "build"
+  };
+
+  let mut child = Command::new(buildifier_path)
+    .arg(format!("--type={}", file_type))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  // UNWRAP: stdin is piped above. It is dropped (and closed) at the end of this statement.
+  child.stdin.take().unwrap().write_all(contents.as_bytes())?;
+
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    return Err(anyhow!(
+      "buildifier failed with status: '{}'\nstderr: {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr)
+    ));
+  }
+
+  Ok(String::from_utf8(output.stdout)?)
+}
+
+/**
+ * Formats rendered files with buildifier.
+ *
+ * Files which buildifier fails to format (including when buildifier is not installed) are
+ * returned unchanged, and a warning is printed.
+ */
+pub fn format_file_outputs(
+  file_outputs: Vec<FileOutputs>,
+  buildifier_path_opt: Option<&str>,
+) -> Vec<FileOutputs> {
+  let buildifier_path = buildifier_path_opt.unwrap_or(DEFAULT_BUILDIFIER_BIN_PATH);
+
+  file_outputs
+    .into_iter()
+    .map(|output| {
+      match format_with_buildifier(buildifier_path, &output.path, &output.contents) {
+        Ok(contents) => FileOutputs {
+          path: output.path,
+          contents,
+        },
+        Err(err) => {
+          eprintln!(
+            "Unable to format {} with {}, leaving it unformatted: {}",
+            output.path, buildifier_path, err
+          );
+          output
+        },
+      }
+    })
+    .collect()
+}
+
 #[derive(Default)]
 pub struct BazelRenderer {
   internal_renderer: Tera,
 }
 
+/**
+ * Names the repository of a remote crate in templates, as
+ * `repository_name(prefix=..., name=..., version=...)`.
+ */
+fn repository_name_function(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+  let arg = |key: &str| {
+    args
+      .get(key)
+      .and_then(|value| value.as_str())
+      .ok_or_else(|| tera::Error::msg(format!("repository_name requires a `{}` string", key)))
+  };
+
+  Ok(tera::Value::String(util::sanitize_repository_name(
+    arg("prefix")?,
+    arg("name")?,
+    arg("version")?,
+  )))
+}
+
+/**
+ * The `starlark_escape` template filter, escaping user-provided strings interpolated into Starlark
+ * string literals.
+ */
+fn starlark_escape_filter(
+  value: &tera::Value,
+  _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+  Ok(escape_template_value(value, escape_starlark))
+}
+
+/**
+ * The `starlark_docstring_escape` template filter, escaping user-provided strings interpolated
+ * into the docstring heading generated files.
+ */
+fn starlark_docstring_escape_filter(
+  value: &tera::Value,
+  _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+  Ok(escape_template_value(value, escape_starlark_docstring))
+}
+
+fn escape_template_value(value: &tera::Value, escape: fn(&str) -> String) -> tera::Value {
+  let escaped = match value {
+    // Missing values are rendered as empty strings, as when not filtered
+    tera::Value::Null => String::new(),
+    tera::Value::String(value) => escape(value),
+    value => escape(&value.to_string()),
+  };
+  tera::Value::String(escaped)
+}
+
 impl BazelRenderer {
   pub fn new() -> Self {
     // Configure tera with a bogus template dir: We don't want any runtime template support
     let mut internal_renderer = Tera::new("src/not/a/dir/*").unwrap();
+    internal_renderer.register_function("repository_name", repository_name_function);
+    internal_renderer.register_filter("starlark_escape", starlark_escape_filter);
+    internal_renderer.register_filter(
+      "starlark_docstring_escape",
+      starlark_docstring_escape_filter,
+    );
     internal_renderer
       .add_raw_templates(vec![
         (
@@ -248,6 +679,18 @@ impl BazelRenderer {
           "templates/partials/rust_library.template",
           include_str!("templates/partials/rust_library.template"),
         ),
+        (
+          "templates/partials/rust_test.template",
+          include_str!("templates/partials/rust_test.template"),
+        ),
+        (
+          "templates/partials/rust_example.template",
+          include_str!("templates/partials/rust_example.template"),
+        ),
+        (
+          "templates/partials/rust_doc.template",
+          include_str!("templates/partials/rust_doc.template"),
+        ),
         (
           "templates/partials/common_attrs.template",
           include_str!("templates/partials/common_attrs.template"),
@@ -260,10 +703,26 @@ impl BazelRenderer {
           "templates/crate.BUILD.template",
           include_str!("templates/crate.BUILD.template"),
         ),
+        (
+          "templates/remote_crate.BUILD.template",
+          include_str!("templates/remote_crate.BUILD.template"),
+        ),
         (
           "templates/remote_crates.bzl.template",
           include_str!("templates/remote_crates.bzl.template"),
         ),
+        (
+          "templates/remote_crates_extension.bzl.template",
+          include_str!("templates/remote_crates_extension.bzl.template"),
+        ),
+        (
+          "templates/remote_crates_module.bazel.template",
+          include_str!("templates/remote_crates_module.bazel.template"),
+        ),
+        (
+          "templates/partials/remote_crate_repository_attrs.template",
+          include_str!("templates/partials/remote_crate_repository_attrs.template"),
+        ),
         (
           "templates/partials/remote_crates_patch.template",
           include_str!("templates/partials/remote_crates_patch.template"),
@@ -272,6 +731,30 @@ impl BazelRenderer {
           "templates/partials/targeted_dependencies.template",
           include_str!("templates/partials/targeted_dependencies.template"),
         ),
+        (
+          "templates/partials/targeted_proc_macro_dependencies.template",
+          include_str!("templates/partials/targeted_proc_macro_dependencies.template"),
+        ),
+        (
+          "templates/partials/targeted_build_dependencies.template",
+          include_str!("templates/partials/targeted_build_dependencies.template"),
+        ),
+        (
+          "templates/partials/targeted_features.template",
+          include_str!("templates/partials/targeted_features.template"),
+        ),
+        (
+          "templates/partials/selectable_features.template",
+          include_str!("templates/partials/selectable_features.template"),
+        ),
+        (
+          "templates/partials/feature_flags.template",
+          include_str!("templates/partials/feature_flags.template"),
+        ),
+        (
+          "templates/partials/crate_build_file.template",
+          include_str!("templates/partials/crate_build_file.template"),
+        ),
       ])
       .unwrap();
 
@@ -285,6 +768,9 @@ impl BazelRenderer {
     workspace_context: &WorkspaceContext,
     package: &CrateContext,
   ) -> Result<String, tera::Error> {
+    let mut package = package.clone();
+    package.sort_dependencies_by_label();
+
     let mut context = Context::new();
     context.insert("workspace", &workspace_context);
     context.insert("crate", &package);
@@ -301,6 +787,10 @@ impl BazelRenderer {
     let mut context = Context::new();
     context.insert("workspace", &workspace_context);
     context.insert("crates", &all_packages);
+    context.insert(
+      "root_aliases",
+      &root_dependency_aliases(workspace_context.alias_name_style, all_packages),
+    );
     self
       .internal_renderer
       .render("templates/workspace.BUILD.template", &context)
@@ -311,12 +801,26 @@ impl BazelRenderer {
     workspace_context: &WorkspaceContext,
     package: &CrateContext,
   ) -> Result<String, tera::Error> {
+    let mut package = package.clone();
+    package.sort_dependencies_by_label();
+
     let mut context = Context::new();
     context.insert("workspace", &workspace_context);
     context.insert("crate", &package);
+    // Path dependencies are built from their own directory, rather than an external repository
+    if package.source_details.path_data.is_none() {
+      context.insert(
+        "repository_name",
+        &util::sanitize_repository_name(
+          &workspace_context.gen_workspace_prefix,
+          &package.pkg_name,
+          &package.pkg_version,
+        ),
+      );
+    }
     self
       .internal_renderer
-      .render("templates/crate.BUILD.template", &context)
+      .render("templates/remote_crate.BUILD.template", &context)
   }
 
   pub fn render_remote_aliases(
@@ -327,6 +831,10 @@ impl BazelRenderer {
     let mut context = Context::new();
     context.insert("workspace", &workspace_context);
     context.insert("crates", &all_packages);
+    context.insert(
+      "root_aliases",
+      &root_dependency_aliases(workspace_context.alias_name_style, all_packages),
+    );
     self
       .internal_renderer
       .render("templates/workspace.BUILD.template", &context)
@@ -337,86 +845,310 @@ impl BazelRenderer {
     workspace_context: &WorkspaceContext,
     all_packages: &[CrateContext],
   ) -> Result<String, tera::Error> {
+    self.render_remote_crates_template(
+      "templates/remote_crates.bzl.template",
+      workspace_context,
+      all_packages,
+    )
+  }
+
+  /** Renders the module extension fetching the remote crates with bzlmod. */
+  pub fn render_bzl_module_extension(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> Result<String, tera::Error> {
+    self.render_remote_crates_template(
+      "templates/remote_crates_extension.bzl.template",
+      workspace_context,
+      all_packages,
+    )
+  }
+
+  /** Renders the MODULE.bazel statements using the module extension of the remote crates. */
+  pub fn render_module_snippet(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> Result<String, tera::Error> {
+    self.render_remote_crates_template(
+      "templates/remote_crates_module.bazel.template",
+      workspace_context,
+      all_packages,
+    )
+  }
+
+  fn render_remote_crates_template(
+    &self,
+    template_name: &str,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> Result<String, tera::Error> {
+    // Path dependencies are built from their own directory, so there is nothing to fetch
+    let remote_packages = all_packages
+      .iter()
+      .filter(|package| package.source_details.path_data.is_none())
+      .collect::<Vec<&CrateContext>>();
+
     let mut context = Context::new();
     context.insert("workspace", &workspace_context);
-    context.insert("crates", &all_packages);
-    self
-      .internal_renderer
-      .render("templates/remote_crates.bzl.template", &context)
+    context.insert("crates", &remote_packages);
+    self.internal_renderer.render(template_name, &context)
   }
 }
 
-fn include_additional_build_file(
-  package: &CrateContext,
-  existing_contents: String,
-) -> Result<String> {
-  match &package.raze_settings.additional_build_file {
-    Some(file_path) => {
-      let additional_content =
-        std::fs::read_to_string(file_path).map_err(|e| RazeError::Rendering {
-          crate_name_opt: Some(package.pkg_name.to_owned()),
-          message: format!("failed to read additional_build_file: {}", e),
-        })?;
-
-      Ok(format!(
-        "{}\n# Additional content from {}\n{}",
-        existing_contents, file_path, additional_content
-      ))
-    },
+/**
+ * Yields the key of a root dependency in `root_dependency_aliases`.
+ *
+ * The dots of the version are replaced, since Tera would treat them as a path into the map.
+ */
+pub fn root_alias_key(pkg_name: &str, pkg_version: &str) -> String {
+  format!("{}-{}", pkg_name, pkg_version.replace(".", "_"))
+}
+
+/**
+ * Yields the names of the root aliases of each root dependency, keyed by `root_alias_key`.
+ *
+ * A package is aliased by its name, sanitized or not depending on `alias_name_style`, unless
+ * several of its versions are root dependencies. Each version is then aliased by its full version
+ * (eg: "rand-0_7_3"), as well as by its semver compatible version (eg: "rand-0_7") if no other
+ * version shares it.
+ *
+ * Distinct packages may still sanitize to the same alias, such as "foo-bar" and "foo_bar". Such an
+ * alias is dropped from every package claiming it, which are instead aliased by their
+ * `root_alias_key`, so that each root dependency stays uniquely addressable.
+ */
+pub fn root_dependency_aliases(
+  alias_name_style: AliasNameStyle,
+  all_packages: &[CrateContext],
+) -> BTreeMap<String, Vec<String>> {
+  let mut packages_by_name: BTreeMap<&str, Vec<&CrateContext>> = BTreeMap::new();
+  for package in all_packages
+    .iter()
+    .filter(|package| package.is_root_dependency && package.lib_target_name.is_some())
+  {
+    packages_by_name
+      .entry(&package.pkg_name)
+      .or_default()
+      .push(package);
+  }
+
+  let mut aliases = BTreeMap::new();
+  for (name, packages) in packages_by_name {
+    let alias_name = match alias_name_style {
+      AliasNameStyle::Sanitize => name.replace("-", "_"),
+      AliasNameStyle::Preserve => name.to_owned(),
+    };
+    let package_key = |package: &CrateContext| root_alias_key(name, &package.pkg_version);
+
+    if packages.len() == 1 {
+      aliases.insert(package_key(packages[0]), vec![alias_name]);
+      continue;
+    }
+
+    let compatible_versions = packages
+      .iter()
+      .map(|package| semver_compatible_version(&package.pkg_version))
+      .collect::<Vec<String>>();
+    for (package, compatible_version) in packages.iter().zip(compatible_versions.iter()) {
+      let mut package_aliases = Vec::new();
+      let is_unique = compatible_versions
+        .iter()
+        .filter(|version| *version == compatible_version)
+        .count()
+        == 1;
+      if is_unique {
+        package_aliases.push(format!("{}-{}", alias_name, compatible_version));
+      }
+      package_aliases.push(format!(
+        "{}-{}",
+        alias_name,
+        util::sanitize_ident(&package.pkg_version)
+      ));
+      aliases.insert(package_key(package), package_aliases);
+    }
+  }
+
+  let mut claims_by_alias: BTreeMap<String, usize> = BTreeMap::new();
+  for alias in aliases.values().flatten() {
+    *claims_by_alias.entry(alias.clone()).or_default() += 1;
+  }
+  for (key, package_aliases) in aliases.iter_mut() {
+    let alias_count = package_aliases.len();
+    package_aliases.retain(|alias| claims_by_alias[alias] == 1);
+    if package_aliases.len() < alias_count && !package_aliases.contains(key) {
+      package_aliases.push(key.clone());
+    }
+  }
+
+  aliases
+}
 
-    None => Ok(existing_contents),
+/** Yields the leftmost non-zero part of a version, and the parts before it (eg: "0_7" for 0.7.3). */
+fn semver_compatible_version(version: &str) -> String {
+  match Version::parse(version) {
+    Ok(version) if version.major > 0 => version.major.to_string(),
+    Ok(version) if version.minor > 0 => format!("0_{}", version.minor),
+    Ok(version) => format!("0_0_{}", version.patch),
+    Err(_) => util::sanitize_ident(version),
   }
 }
 
-macro_rules! unwind_tera_error {
-  ($err:ident) => {{
-    let mut messages = vec![$err.to_string()];
-    let mut cause = $err.source();
-    while let Some(e) = cause {
-      messages.push(e.to_string());
-      cause = e.source();
+/**
+ * Resolves the path of an additional build file from the root of the Bazel workspace, so that it
+ * does not depend on the directory raze is invoked from.
+ */
+fn resolve_additional_build_file(file_path: &str) -> PathBuf {
+  let path = Path::new(file_path);
+  if path.is_relative() {
+    if let Some(workspace_root) = find_workspace_root() {
+      return workspace_root.join(path);
     }
-    messages.join("\n└─")
-  }};
+  }
+  path.to_path_buf()
 }
 
-impl BuildRenderer for BazelRenderer {
-  fn render_planned_build(
+pub(crate) fn include_additional_build_file(
+  package: &CrateContext,
+  existing_contents: String,
+) -> Result<String> {
+  let file_paths = package
+    .raze_settings
+    .additional_build_file
+    .iter()
+    .chain(package.raze_settings.additional_build_files.iter());
+
+  let mut contents = existing_contents;
+  for file_path in file_paths {
+    let resolved_file_path = resolve_additional_build_file(file_path);
+    let additional_content =
+      std::fs::read_to_string(&resolved_file_path).map_err(|e| RazeError::Rendering {
+        crate_name_opt: Some(package.pkg_name.to_owned()),
+        message: format!(
+          "failed to read additional build file {}: {}",
+          resolved_file_path.display(),
+          e
+        ),
+      })?;
+
+    // The path is written as configured, as the resolved one depends on the machine
+    contents = format!(
+      "{}\n# Additional content from {}\n{}",
+      contents, file_path, additional_content
+    );
+  }
+
+  if let Some(additional_content) = &package.raze_settings.additional_build_content {
+    contents = format!(
+      "{}\n# Additional inline content\n{}",
+      contents, additional_content
+    );
+  }
+
+  Ok(contents)
+}
+
+/** Renders the build file template of a crate, from any of the threads rendering crates. */
+type CrateTemplateRenderer<'a> = dyn Fn(&CrateContext) -> Result<String, tera::Error> + Sync + 'a;
+
+macro_rules! unwind_tera_error {
+  ($err:ident) => {{
+    let mut messages = vec![$err.to_string()];
+    let mut cause = $err.source();
+    while let Some(e) = cause {
+      messages.push(e.to_string());
+      cause = e.source();
+    }
+    messages.join("\n└─")
+  }};
+}
+
+/** Renders the build file of a crate with the given template rendering function. */
+fn render_crate_file(
+  package: &CrateContext,
+  path_prefix: &str,
+  render_crate: &CrateTemplateRenderer,
+  on_crate_rendered: &CrateRenderedHook,
+) -> Result<FileOutputs> {
+  let rendered_crate_build_file = render_crate(package).map_err(|e| RazeError::Rendering {
+    crate_name_opt: Some(package.pkg_name.to_owned()),
+    message: unwind_tera_error!(e),
+  })?;
+
+  let final_crate_build_file = include_additional_build_file(package, rendered_crate_build_file)?;
+  on_crate_rendered(&package.pkg_name);
+
+  Ok(FileOutputs {
+    path: format!("{}/{}", path_prefix, package.expected_build_path),
+    contents: final_crate_build_file,
+  })
+}
+
+/**
+ * Renders the build files of the crates in parallel, yielding the same files as rendering them
+ * one at a time.
+ *
+ * The files are collected in the order of the crates. Every crate is rendered even if another one
+ * fails, so that the error returned is always that of the first crate failing to render.
+ */
+fn render_crate_files(
+  crate_contexts: &[CrateContext],
+  path_prefix: &str,
+  render_crate: &CrateTemplateRenderer,
+  on_crate_rendered: &CrateRenderedHook,
+) -> Result<Vec<FileOutputs>> {
+  crate_contexts
+    .par_iter()
+    .map(|package| render_crate_file(package, path_prefix, render_crate, on_crate_rendered))
+    .collect::<Vec<Result<FileOutputs>>>()
+    .into_iter()
+    .collect()
+}
+
+impl BuildRenderer for BazelRenderer {
+  fn render_planned_build(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+  ) -> Result<Vec<FileOutputs>> {
+    self.render_planned_build_with_progress(render_details, planned_build, &ignore_crates_rendered)
+  }
+
+  fn render_remote_planned_build(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+  ) -> Result<Vec<FileOutputs>> {
+    self.render_remote_planned_build_with_progress(
+      render_details,
+      planned_build,
+      &ignore_crates_rendered,
+    )
+  }
+
+  fn render_planned_build_with_progress(
     &mut self,
     render_details: &RenderDetails,
     planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
   ) -> Result<Vec<FileOutputs>> {
+    let render_details = &render_details.normalized()?;
     let &RenderDetails {
-      ref path_prefix,
-      ref buildfile_suffix,
-      ..
+      ref path_prefix, ..
     } = render_details;
     let &PlannedBuild {
       ref workspace_context,
       ref crate_contexts,
       ..
     } = planned_build;
-    let mut file_outputs = Vec::new();
-
-    for package in crate_contexts {
-      let rendered_crate_build_file =
-        self
-          .render_crate(&workspace_context, &package)
-          .map_err(|e| RazeError::Rendering {
-            crate_name_opt: None,
-            message: unwind_tera_error!(e),
-          })?;
-
-      let final_crate_build_file =
-        include_additional_build_file(package, rendered_crate_build_file)?;
 
-      file_outputs.push(FileOutputs {
-        path: format!("{}/{}", path_prefix, package.expected_build_path),
-        contents: final_crate_build_file,
-      })
-    }
+    let mut file_outputs = render_crate_files(
+      crate_contexts,
+      path_prefix,
+      &|package| self.render_crate(workspace_context, package),
+      on_crate_rendered,
+    )?;
 
-    let build_file_path = format!("{}/{}", &path_prefix, buildfile_suffix);
     let rendered_alias_build_file = self
       .render_aliases(&workspace_context, &crate_contexts)
       .map_err(|e| RazeError::Rendering {
@@ -425,17 +1157,22 @@ impl BuildRenderer for BazelRenderer {
       })?;
 
     file_outputs.push(FileOutputs {
-      path: build_file_path,
+      path: render_details.aliases_build_file_path(),
       contents: rendered_alias_build_file,
     });
+
+    normalize_line_endings(render_details, &mut file_outputs);
+    add_legacy_buildfile_stubs(render_details, &mut file_outputs);
     Ok(file_outputs)
   }
 
-  fn render_remote_planned_build(
+  fn render_remote_planned_build_with_progress(
     &mut self,
     render_details: &RenderDetails,
     planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
   ) -> Result<Vec<FileOutputs>> {
+    let render_details = &render_details.normalized()?;
     let &RenderDetails {
       ref path_prefix,
       ref buildfile_suffix,
@@ -454,24 +1191,13 @@ impl BuildRenderer for BazelRenderer {
       contents: String::new(),
     });
 
-    for package in crate_contexts {
-      let rendered_crate_build_file = self
-        .render_remote_crate(&workspace_context, &package)
-        .map_err(|e| RazeError::Rendering {
-          crate_name_opt: Some(package.pkg_name.to_owned()),
-          message: unwind_tera_error!(e),
-        })?;
-
-      let final_crate_build_file =
-        include_additional_build_file(package, rendered_crate_build_file)?;
-
-      file_outputs.push(FileOutputs {
-        path: format!("{}/{}", path_prefix, package.expected_build_path),
-        contents: final_crate_build_file,
-      })
-    }
+    file_outputs.extend(render_crate_files(
+      crate_contexts,
+      path_prefix,
+      &|package| self.render_remote_crate(workspace_context, package),
+      on_crate_rendered,
+    )?);
 
-    let alias_file_path = format!("{}/{}", &path_prefix, buildfile_suffix);
     let rendered_alias_build_file = self
       .render_remote_aliases(&workspace_context, &crate_contexts)
       .map_err(|e| RazeError::Rendering {
@@ -480,23 +1206,39 @@ impl BuildRenderer for BazelRenderer {
       })?;
 
     file_outputs.push(FileOutputs {
-      path: alias_file_path,
+      path: render_details.aliases_build_file_path(),
       contents: rendered_alias_build_file,
     });
 
-    let bzl_fetch_file_path = format!("{}/crates.bzl", &path_prefix);
-    let rendered_bzl_fetch_file = self
-      .render_bzl_fetch(&workspace_context, &crate_contexts)
-      .map_err(|e| RazeError::Rendering {
-        crate_name_opt: None,
-        message: unwind_tera_error!(e),
-      })?;
-
-    file_outputs.push(FileOutputs {
-      path: bzl_fetch_file_path,
-      contents: rendered_bzl_fetch_file,
-    });
+    let fetched_crates = sort_by_repository_name(workspace_context, crate_contexts);
+    let rendered_fetch_files = match render_details.fetch_mode {
+      FetchMode::Workspace => vec![(
+        "crates.bzl",
+        self.render_bzl_fetch(workspace_context, &fetched_crates),
+      )],
+      FetchMode::Bzlmod => vec![
+        (
+          "extensions.bzl",
+          self.render_bzl_module_extension(workspace_context, &fetched_crates),
+        ),
+        (
+          "MODULE.bazel.snippet",
+          self.render_module_snippet(workspace_context, &fetched_crates),
+        ),
+      ],
+    };
+    for (file_name, rendered_fetch_file) in rendered_fetch_files {
+      file_outputs.push(FileOutputs {
+        path: format!("{}/{}", &path_prefix, file_name),
+        contents: rendered_fetch_file.map_err(|e| RazeError::Rendering {
+          crate_name_opt: None,
+          message: unwind_tera_error!(e),
+        })?,
+      });
+    }
 
+    normalize_line_endings(render_details, &mut file_outputs);
+    add_legacy_buildfile_stubs(render_details, &mut file_outputs);
     Ok(file_outputs)
   }
 }
@@ -507,21 +1249,35 @@ mod tests {
 
   use crate::{
     context::*,
+    license::get_license_from_str,
     planning::PlannedBuild,
-    rendering::{FileOutputs, RenderDetails},
-    settings::CrateSettings,
+    rendering::{check_path_dependency_outputs, render_subset, FileOutputs, RenderDetails},
+    settings::{testing::dummy_raze_settings, CrateSettings, GenMode, RazeSettings},
+    util,
   };
 
   use super::*;
 
-  use std::fs::File;
+  use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    sync::{Mutex, PoisonError},
+    time::Instant,
+  };
 
   use tempfile::TempDir;
 
+  // Serializes the tests changing the current working directory, which is process wide
+  static CWD_LOCK: Mutex<()> = Mutex::new(());
+
   fn dummy_render_details(buildfile_suffix: &str) -> RenderDetails {
     RenderDetails {
       path_prefix: "./some_render_prefix".to_owned(),
       buildfile_suffix: buildfile_suffix.to_owned(),
+      legacy_buildfile_stubs: false,
+      fetch_mode: FetchMode::Workspace,
+      aliases_package: None,
+      normalize_line_endings: true,
     }
   }
 
@@ -531,8 +1287,13 @@ mod tests {
         workspace_path: "//workspace/prefix".to_owned(),
         gen_workspace_prefix: "".to_owned(),
         output_buildfile_suffix: "BUILD".to_owned(),
+        rules_rust_repository_name: "io_bazel_rules_rust".to_owned(),
+        default_tags: vec!["cargo-raze".to_owned(), "manual".to_owned()],
+        generated_file_header: "DO NOT EDIT! Replaced on runs of cargo-raze".to_owned(),
+        alias_name_style: AliasNameStyle::Sanitize,
       },
       crate_contexts,
+      warnings: Vec::new(),
     }
   }
 
@@ -542,6 +1303,8 @@ mod tests {
       pkg_version: "1.1.1".to_owned(),
       edition: "2015".to_owned(),
       features: vec!["feature1".to_owned(), "feature2".to_owned()].to_owned(),
+      targeted_features: Vec::new(),
+      selectable_features: Vec::new(),
       expected_build_path: format!("vendor/test-binary-1.1.1/{}", buildfile_suffix),
       license: LicenseData::default(),
       raze_settings: CrateSettings::default(),
@@ -554,6 +1317,7 @@ mod tests {
         aliased_dependencies: Vec::new(),
       },
       targeted_deps: Vec::new(),
+      target_dependencies: BTreeMap::new(),
       is_root_dependency: true,
       workspace_path_to_crate: "@raze__test_binary__1_1_1//".to_owned(),
       targets: vec![BuildableTarget {
@@ -565,6 +1329,7 @@ mod tests {
       build_script_target: None,
       source_details: SourceDetails {
         git_data: None,
+        path_data: None,
       },
       sha256: None,
       registry_url: "https://crates.io/api/v1/crates/test-binary/1.1.1/download".to_string(),
@@ -584,6 +1349,8 @@ mod tests {
       license: LicenseData::default(),
       raze_settings: CrateSettings::default(),
       features: vec!["feature1".to_owned(), "feature2".to_owned()].to_owned(),
+      targeted_features: Vec::new(),
+      selectable_features: Vec::new(),
       expected_build_path: format!("vendor/test-library-1.1.1/{}", buildfile_suffix),
       default_deps: CrateDependencyContext {
         dependencies: Vec::new(),
@@ -594,6 +1361,7 @@ mod tests {
         aliased_dependencies: Vec::new(),
       },
       targeted_deps: Vec::new(),
+      target_dependencies: BTreeMap::new(),
       is_root_dependency: true,
       workspace_path_to_crate: "@raze__test_library__1_1_1//".to_owned(),
       targets: vec![BuildableTarget {
@@ -605,6 +1373,7 @@ mod tests {
       build_script_target: None,
       source_details: SourceDetails {
         git_data: None,
+        path_data: None,
       },
       sha256: None,
       registry_url: "https://crates.io/api/v1/crates/test-binary/1.1.1/download".to_string(),
@@ -644,6 +1413,193 @@ mod tests {
     return render_crates_for_test_with_name("BUILD", crate_contexts);
   }
 
+  fn renderer_with_broken_crate_template() -> BazelRenderer {
+    let mut renderer = BazelRenderer::new();
+    for template_name in &[
+      "templates/crate.BUILD.template",
+      "templates/remote_crate.BUILD.template",
+    ] {
+      renderer
+        .internal_renderer
+        .add_raw_template(template_name, "{{ undefined_variable }}")
+        .unwrap();
+    }
+    renderer
+  }
+
+  #[test]
+  fn crlf_line_endings_are_normalized_to_lf() {
+    let mut renderer = BazelRenderer::new();
+    renderer
+      .internal_renderer
+      .add_raw_template(
+        "templates/crate.BUILD.template",
+        "# {{ crate.pkg_name }}\r\nfilegroup(name = \"srcs\")\r\n",
+      )
+      .unwrap();
+    let planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+    let mut render_crate_build_file = |normalize_line_endings: bool| {
+      let file_outputs = renderer
+        .render_planned_build(
+          &RenderDetails {
+            normalize_line_endings,
+            ..dummy_render_details("BUILD")
+          },
+          &planned_build,
+        )
+        .unwrap();
+      extract_contents_matching_path(
+        &file_outputs,
+        "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+      )
+    };
+
+    assert_eq!(
+      render_crate_build_file(true),
+      "# test-library\nfilegroup(name = \"srcs\")\n"
+    );
+    assert_eq!(
+      render_crate_build_file(false),
+      "# test-library\r\nfilegroup(name = \"srcs\")\r\n"
+    );
+  }
+
+  #[test]
+  fn crate_render_errors_name_the_crate() {
+    for remote in [false, true] {
+      let mut renderer = renderer_with_broken_crate_template();
+      let render_details = dummy_render_details("BUILD");
+      let planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+
+      let result = if remote {
+        renderer.render_remote_planned_build(&render_details, &planned_build)
+      } else {
+        renderer.render_planned_build(&render_details, &planned_build)
+      };
+
+      let message = result.unwrap_err().to_string();
+      assert!(
+        message.contains("Raze failed to render crate \"test-library\""),
+        "expected the error to name the crate, but it was [{}]",
+        message
+      );
+    }
+  }
+
+  fn many_crates(count: usize) -> Vec<CrateContext> {
+    (0..count)
+      .map(|index| {
+        let package = match index % 2 {
+          0 => dummy_library_crate(),
+          _ => dummy_binary_crate(),
+        };
+        let pkg_name = format!("{}-{}", package.pkg_name, index);
+        CrateContext {
+          expected_build_path: format!("vendor/{}-1.1.1/BUILD", pkg_name),
+          pkg_name,
+          ..package
+        }
+      })
+      .collect()
+  }
+
+  fn render_crates_sequentially(
+    renderer: &BazelRenderer,
+    remote: bool,
+    planned_build: &PlannedBuild,
+  ) -> Vec<FileOutputs> {
+    let workspace_context = &planned_build.workspace_context;
+    let render_crate = |package: &CrateContext| match remote {
+      true => renderer.render_remote_crate(workspace_context, package),
+      false => renderer.render_crate(workspace_context, package),
+    };
+    planned_build
+      .crate_contexts
+      .iter()
+      .map(|package| {
+        render_crate_file(
+          package,
+          "./some_render_prefix",
+          &render_crate,
+          &ignore_crates_rendered,
+        )
+      })
+      .collect::<Result<Vec<FileOutputs>>>()
+      .unwrap()
+  }
+
+  #[test]
+  fn crates_rendered_in_parallel_match_those_rendered_sequentially() {
+    let planned_build = dummy_planned_build(many_crates(64));
+    for remote in [false, true] {
+      let mut renderer = BazelRenderer::new();
+      let render_details = dummy_render_details("BUILD");
+      let file_outputs = if remote {
+        renderer.render_remote_planned_build(&render_details, &planned_build)
+      } else {
+        renderer.render_planned_build(&render_details, &planned_build)
+      }
+      .unwrap();
+
+      // Remote crates come after the build file of the `remote` package
+      let crate_file_outputs = &file_outputs[remote as usize..][..64];
+      assert_eq!(
+        crate_file_outputs,
+        &render_crates_sequentially(&renderer, remote, &planned_build)[..]
+      );
+    }
+  }
+
+  #[test]
+  fn crate_render_errors_are_those_of_the_first_failing_crate() {
+    let planned_build = dummy_planned_build(many_crates(64));
+    for _ in 0..8 {
+      let message = renderer_with_broken_crate_template()
+        .render_planned_build(&dummy_render_details("BUILD"), &planned_build)
+        .unwrap_err()
+        .to_string();
+      assert!(
+        message.contains("Raze failed to render crate \"test-library-0\""),
+        "expected the error of the first crate, but it was [{}]",
+        message
+      );
+    }
+  }
+
+  /**
+   * Compares rendering the crates of a large build in parallel and one at a time.
+   *
+   * Run with `cargo test --release -- --ignored --nocapture parallel_rendering_benchmark`.
+   */
+  #[test]
+  #[ignore]
+  fn parallel_rendering_benchmark() {
+    let planned_build = dummy_planned_build(many_crates(2000));
+    let renderer = BazelRenderer::new();
+
+    let start = Instant::now();
+    let sequential_file_outputs = render_crates_sequentially(&renderer, false, &planned_build);
+    let sequential_duration = start.elapsed();
+
+    let start = Instant::now();
+    let file_outputs = render_crate_files(
+      &planned_build.crate_contexts,
+      "./some_render_prefix",
+      &|package| renderer.render_crate(&planned_build.workspace_context, package),
+      &ignore_crates_rendered,
+    )
+    .unwrap();
+    let parallel_duration = start.elapsed();
+
+    println!(
+      "Rendered {} crates sequentially in {:?}, and in parallel in {:?}",
+      planned_build.crate_contexts.len(),
+      sequential_duration,
+      parallel_duration
+    );
+    assert_eq!(file_outputs, sequential_file_outputs);
+  }
+
   #[test]
   fn all_plans_contain_root_build_file() {
     let file_outputs = render_crates_for_test(Vec::new());
@@ -714,6 +1670,138 @@ mod tests {
     .unwrap();
   }
 
+  #[test]
+  fn extra_aliased_targets_get_build_aliases() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings: CrateSettings {
+        extra_aliased_targets: vec!["cargo_bin_protoc_gen_rust".to_owned()],
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let root_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+
+    assert_eq!(root_build_contents.matches("alias(").count(), 2);
+    assert!(
+      root_build_contents.contains("    actual = \"@raze__test_library__1_1_1//:test_library\",\n")
+    );
+    assert!(root_build_contents.contains(
+      "    name = \"cargo_bin_protoc_gen_rust\",\n    \
+       actual = \"@raze__test_library__1_1_1//:cargo_bin_protoc_gen_rust\",\n"
+    ));
+  }
+
+  #[test]
+  fn multiple_versions_of_a_root_crate_get_versioned_aliases() {
+    let older_library_crate = CrateContext {
+      pkg_version: "0.7.3".to_owned(),
+      workspace_path_to_crate: "@raze__test_library__0_7_3//".to_owned(),
+      ..dummy_library_crate()
+    };
+    let file_outputs =
+      render_crates_for_test(vec![dummy_library_crate(), older_library_crate.clone()]);
+    let root_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+
+    assert_eq!(root_build_contents.matches("alias(").count(), 4);
+    assert!(!root_build_contents.contains("name = \"test_library\","));
+    for (name, version) in &[
+      ("test_library-0_7", "0_7_3"),
+      ("test_library-0_7_3", "0_7_3"),
+      ("test_library-1", "1_1_1"),
+      ("test_library-1_1_1", "1_1_1"),
+    ] {
+      assert!(root_build_contents.contains(&format!(
+        "    name = \"{}\",\n    actual = \"@raze__test_library__{}//:test_library\",\n",
+        name, version
+      )));
+    }
+
+    // Versions sharing their semver compatible version only get their full version alias
+    let newer_library_crate = CrateContext {
+      pkg_version: "1.2.0".to_owned(),
+      ..dummy_library_crate()
+    };
+    let aliases = root_dependency_aliases(
+      AliasNameStyle::Sanitize,
+      &[
+        dummy_library_crate(),
+        newer_library_crate,
+        older_library_crate,
+      ],
+    );
+    assert_eq!(
+      aliases[&root_alias_key("test-library", "1.1.1")],
+      vec!["test_library-1_1_1"]
+    );
+    assert_eq!(
+      aliases[&root_alias_key("test-library", "1.2.0")],
+      vec!["test_library-1_2_0"]
+    );
+    assert_eq!(
+      aliases[&root_alias_key("test-library", "0.7.3")],
+      vec!["test_library-0_7", "test_library-0_7_3"]
+    );
+  }
+
+  #[test]
+  fn root_crate_aliases_can_preserve_the_package_name() {
+    let serde_json_crate = CrateContext {
+      pkg_name: "serde-json".to_owned(),
+      workspace_path_to_crate: "@raze__serde_json__1_1_1//".to_owned(),
+      expected_build_path: "vendor/serde-json-1.1.1/BUILD".to_owned(),
+      ..dummy_library_crate()
+    };
+    let mut planned_build = dummy_planned_build(vec![serde_json_crate]);
+    planned_build.workspace_context.alias_name_style = AliasNameStyle::Preserve;
+
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      .unwrap();
+    let root_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+
+    assert!(root_build_contents.contains(
+      "    name = \"serde-json\",\n    actual = \"@raze__serde_json__1_1_1//:serde_json\",\n"
+    ));
+    assert!(!root_build_contents.contains("name = \"serde_json\","));
+  }
+
+  #[test]
+  fn clashing_root_crate_aliases_are_disambiguated() {
+    let clashing_library_crate = CrateContext {
+      pkg_name: "test_library".to_owned(),
+      workspace_path_to_crate: "@raze__test_library_git__1_1_1//".to_owned(),
+      ..dummy_library_crate()
+    };
+    let file_outputs =
+      render_crates_for_test(vec![dummy_library_crate(), clashing_library_crate.clone()]);
+    let root_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+
+    assert_eq!(root_build_contents.matches("alias(").count(), 2);
+    assert!(!root_build_contents.contains("name = \"test_library\","));
+    for (name, repository) in &[
+      ("test-library-1_1_1", "test_library"),
+      ("test_library-1_1_1", "test_library_git"),
+    ] {
+      assert!(root_build_contents.contains(&format!(
+        "    name = \"{}\",\n    actual = \"@raze__{}__1_1_1//:test_library\",\n",
+        name, repository
+      )));
+    }
+
+    let aliases = root_dependency_aliases(
+      AliasNameStyle::Sanitize,
+      &[dummy_library_crate(), clashing_library_crate],
+    );
+    assert_eq!(
+      aliases[&root_alias_key("test-library", "1.1.1")],
+      vec!["test-library-1_1_1"]
+    );
+  }
+
   #[test]
   fn non_root_crates_dont_get_build_aliases() {
     let mut non_root_crate = dummy_library_crate();
@@ -735,84 +1823,1776 @@ mod tests {
   }
 
   #[test]
-  fn binaries_get_rust_binary_rules() {
-    let file_outputs = render_crates_for_test(vec![dummy_binary_crate()]);
-    let crate_build_contents = extract_contents_matching_path(
-      &file_outputs,
-      "./some_render_prefix/vendor/test-binary-1.1.1/BUILD",
-    );
+  fn every_rendered_file_starts_with_the_generated_file_header() {
+    let mut planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+    planned_build.workspace_context.generated_file_header =
+      "Generated by cargo-raze, edit Cargo.toml instead".to_owned();
 
-    expect(
-      crate_build_contents.contains("rust_binary("),
-      format!(
-        "expected crate build contents to contain rust_binary, but it just contained [{}]",
-        crate_build_contents
-      ),
-    )
-    .unwrap();
+    for remote in [false, true] {
+      let mut renderer = BazelRenderer::new();
+      let file_outputs = if remote {
+        renderer.render_remote_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      } else {
+        renderer.render_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      }
+      .unwrap();
+
+      // Empty outputs only create packages, and have no contents to mistake for hand-written ones
+      for output in file_outputs
+        .iter()
+        .filter(|output| !output.contents.is_empty())
+      {
+        let header = output.contents.lines().take(6).collect::<Vec<_>>();
+        assert_eq!(header[1], "@generated", "in {}", output.path);
+        assert_eq!(
+          header[4], "Generated by cargo-raze, edit Cargo.toml instead",
+          "in {}",
+          output.path
+        );
+      }
+      let crate_build_contents = extract_contents_matching_path(
+        &file_outputs,
+        "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+      );
+      assert!(crate_build_contents.contains("cargo-raze crate build file for test-library 1.1.1."));
+    }
   }
 
   #[test]
-  fn libraries_get_rust_library_rules() {
-    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
-    let crate_build_contents = extract_contents_matching_path(
-      &file_outputs,
-      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
-    );
+  fn remote_crates_use_gen_workspace_prefix() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.workspace_path_to_crate = "@my_prefix__test_library__1_1_1//".to_owned();
+    let mut planned_build = dummy_planned_build(vec![library_crate]);
+    planned_build.workspace_context.gen_workspace_prefix = "my_prefix".to_owned();
 
-    expect(
-      crate_build_contents.contains("rust_library("),
-      format!(
-        "expected crate build contents to contain rust_library, but it just contained [{}]",
-        crate_build_contents
-      ),
-    )
-    .unwrap();
-  }
+    let file_outputs = BazelRenderer::new()
+      .render_remote_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      .unwrap();
+    let root_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+    let crates_bzl_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/crates.bzl");
 
-  #[test]
-  fn additional_build_file_missing_file_failure() {
-    let render_result = BazelRenderer::new().render_planned_build(
-      &dummy_render_details("BUILD"),
-      &dummy_planned_build(vec![CrateContext {
-        raze_settings: CrateSettings {
-          additional_build_file: Some("non-existent-file".into()),
-          ..Default::default()
-        },
-        ..dummy_library_crate()
-      }]),
+    assert!(
+      root_build_contents.contains("actual = \"@my_prefix__test_library__1_1_1//:test_library\"")
     );
-
-    assert_that!(render_result, err());
+    assert!(crates_bzl_contents.contains("def my_prefix_fetch_remote_crates():"));
+    assert!(crates_bzl_contents.contains("name = \"my_prefix__test_library__1_1_1\""));
+  }
+
+  #[test]
+  fn a_single_macro_fetches_every_remote_crate() {
+    let other_crate = CrateContext {
+      pkg_name: "other-library".to_owned(),
+      ..dummy_library_crate()
+    };
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![
+      dummy_library_crate(),
+      other_crate,
+      dummy_git_library_crate(),
+    ]);
+
+    let (preamble, macro_body) = crates_bzl_contents
+      .split_once("def _fetch_remote_crates():\n")
+      .unwrap();
+    assert!(!preamble.contains("maybe("));
+    assert!(!macro_body.contains("\ndef "));
+    // Every statement of the body is indented in the macro
+    assert!(macro_body
+      .lines()
+      .all(|line| line.is_empty() || line.starts_with("    ")));
+    assert_eq!(macro_body.matches("    maybe(\n").count(), 3);
+    for repository_name in &["__test_library__1_1_1", "__other_library__1_1_1"] {
+      assert!(macro_body.contains(&format!("        name = \"{}\",\n", repository_name)));
+    }
+  }
+
+  #[test]
+  fn remote_crates_reference_the_sources_of_their_repository() {
+    let mut planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+    planned_build.workspace_context.gen_workspace_prefix = "my_prefix".to_owned();
+
+    let file_outputs = BazelRenderer::new()
+      .render_remote_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      .unwrap();
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+    assert!(crate_build_contents
+      .contains("filegroup(\n    name = \"srcs\",\n    srcs = glob([\"**/*.rs\"]),\n)"));
+    assert!(
+      crate_build_contents.contains("    srcs = [\"@my_prefix__test_library__1_1_1//:srcs\"],")
+    );
+    assert!(!crate_build_contents.contains("    srcs = glob([\"**/*.rs\"]),\n    crate_root"));
+
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      .unwrap();
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+    assert!(!crate_build_contents.contains("filegroup("));
+    assert!(!crate_build_contents.contains("//:srcs"));
+    assert!(crate_build_contents.contains("    srcs = glob([\"**/*.rs\"]),\n    crate_root"));
+  }
+
+  #[test]
+  fn bzlmod_fetch_mode_renders_a_module_extension() {
+    let mut planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+    planned_build.workspace_context.gen_workspace_prefix = "my_prefix".to_owned();
+
+    let file_outputs = BazelRenderer::new()
+      .render_remote_planned_build(
+        &RenderDetails {
+          fetch_mode: FetchMode::Bzlmod,
+          ..dummy_render_details("BUILD")
+        },
+        &planned_build,
+      )
+      .unwrap();
+    assert!(!file_outputs
+      .iter()
+      .any(|output| output.path.ends_with("crates.bzl")));
+
+    let extension_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/extensions.bzl");
+    assert!(extension_contents.contains("def _my_prefix_crates_impl(module_ctx):"));
+    assert!(extension_contents
+      .contains("    http_archive(\n        name = \"my_prefix__test_library__1_1_1\",\n"));
+    assert!(extension_contents.contains(
+      "my_prefix_crates = module_extension(\n    implementation = _my_prefix_crates_impl,\n)"
+    ));
+    assert!(!extension_contents.contains("maybe"));
+
+    let module_snippet_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/MODULE.bazel.snippet");
+    assert!(module_snippet_contents.contains(
+      "my_prefix_crates = use_extension(\"//workspace/prefix:extensions.bzl\", \"my_prefix_crates\")"
+    ));
+    assert!(module_snippet_contents
+      .contains("use_repo(\n    my_prefix_crates,\n    \"my_prefix__test_library__1_1_1\",\n)"));
+  }
+
+  #[test]
+  fn repository_names_are_scoped_by_the_workspace_prefix() {
+    let mut prerelease_crate = dummy_library_crate();
+    prerelease_crate.pkg_version = "0.2.0-Beta.1+build.5".to_owned();
+
+    let repository_names = |prefix: &str| {
+      let mut planned_build =
+        dummy_planned_build(vec![dummy_library_crate(), prerelease_crate.clone()]);
+      planned_build.workspace_context.gen_workspace_prefix = prefix.to_owned();
+      let file_outputs = BazelRenderer::new()
+        .render_remote_planned_build(&dummy_render_details("BUILD"), &planned_build)
+        .unwrap();
+      let crates_bzl_contents =
+        extract_contents_matching_path(&file_outputs, "./some_render_prefix/crates.bzl");
+
+      let names = crates_bzl_contents
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("name = \""))
+        .map(|name| name.trim_end_matches("\",").to_owned())
+        .collect::<BTreeSet<String>>();
+      // The aliases and dependency labels point at repositories named by the same helper
+      let expected = planned_build
+        .crate_contexts
+        .iter()
+        .map(|krate| util::sanitize_repository_name(prefix, &krate.pkg_name, &krate.pkg_version))
+        .collect::<BTreeSet<String>>();
+      assert_eq!(names, expected);
+      names
+    };
+
+    let first_names = repository_names("first");
+    let second_names = repository_names("second");
+    assert!(first_names.contains("first__test_library__0_2_0_beta_1_build_5"));
+    assert!(first_names.is_disjoint(&second_names));
+  }
+
+  fn render_bzl_fetch_for_test(crate_contexts: Vec<CrateContext>) -> String {
+    let file_outputs = BazelRenderer::new()
+      .render_remote_planned_build(
+        &dummy_render_details("BUILD"),
+        &dummy_planned_build(crate_contexts),
+      )
+      .unwrap();
+    extract_contents_matching_path(&file_outputs, "./some_render_prefix/crates.bzl")
+  }
+
+  fn dummy_git_library_crate() -> CrateContext {
+    let mut git_crate = dummy_library_crate();
+    git_crate.source_details.git_data = Some(GitRepo {
+      remote: "https://github.com/some/test-library.git".to_owned(),
+      commit: "4c30d1c3ddd0f3a3d1ba0e9a5ef7c7f0ba8e2d8e".to_owned(),
+    });
+    git_crate
+  }
+
+  #[test]
+  fn remote_crates_are_fetched_in_repository_name_order() {
+    let crate_contexts = [
+      ("zlib-sys", "1.0.0"),
+      ("test-library", "1.10.0"),
+      ("bar-baz", "0.1.0"),
+      ("test-library", "1.9.0"),
+    ]
+    .iter()
+    .map(|(name, version)| CrateContext {
+      pkg_name: name.to_string(),
+      pkg_version: version.to_string(),
+      ..dummy_library_crate()
+    })
+    .collect::<Vec<CrateContext>>();
+
+    let crates_bzl_contents = render_bzl_fetch_for_test(crate_contexts);
+    let repository_names = crates_bzl_contents
+      .lines()
+      .filter_map(|line| line.trim().strip_prefix("name = "))
+      .collect::<Vec<&str>>();
+    assert_eq!(
+      repository_names,
+      vec![
+        "\"__bar_baz__0_1_0\",",
+        "\"__test_library__1_10_0\",",
+        "\"__test_library__1_9_0\",",
+        "\"__zlib_sys__1_0_0\",",
+      ]
+    );
+  }
+
+  #[test]
+  fn remote_crates_only_declare_known_checksums() {
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![dummy_library_crate()]);
+    assert!(!crates_bzl_contents.contains("sha256"));
+    assert!(crates_bzl_contents.contains(
+      "        type = \"tar.gz\",
+        strip_prefix = \"test-library-1.1.1\","
+    ));
+
+    let checksum = "0".repeat(64);
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![CrateContext {
+      sha256: Some(checksum.clone()),
+      ..dummy_library_crate()
+    }]);
+    assert!(crates_bzl_contents.contains(&format!("        sha256 = \"{}\",\n", checksum)));
+  }
+
+  #[test]
+  fn remote_crates_are_patched() {
+    let patched_settings = |mut crate_context: CrateContext| {
+      crate_context.raze_settings.patches = vec![
+        "@//patches:fix_build.patch".to_owned(),
+        "@//patches:fix_tests.patch".to_owned(),
+      ];
+      crate_context.raze_settings.patch_args = vec!["-p1".to_owned()];
+      crate_context.raze_settings.patch_cmds = vec!["rm -rf benches".to_owned()];
+      crate_context
+    };
+
+    for crate_context in [dummy_library_crate(), dummy_git_library_crate()] {
+      let crates_bzl_contents = render_bzl_fetch_for_test(vec![patched_settings(crate_context)]);
+
+      assert!(crates_bzl_contents.contains(
+        r#"
+        patches = [
+            "@//patches:fix_build.patch",
+            "@//patches:fix_tests.patch",
+        ],
+        patch_args = [
+            "-p1",
+        ],
+        patch_cmds = [
+            "rm -rf benches",
+        ],"#
+      ));
+    }
+  }
+
+  #[test]
+  fn git_dependencies_are_fetched_by_commit() {
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![dummy_git_library_crate()]);
+
+    assert!(crates_bzl_contents.contains("new_git_repository,"));
+    assert!(crates_bzl_contents.contains("remote = \"https://github.com/some/test-library.git\","));
+    assert!(crates_bzl_contents.contains("commit = \"4c30d1c3ddd0f3a3d1ba0e9a5ef7c7f0ba8e2d8e\","));
+    assert!(crates_bzl_contents.contains("init_submodules = True,"));
+    assert!(!crates_bzl_contents.contains("http_archive,\n"));
+  }
+
+  #[test]
+  fn remote_build_file_labels_use_buildfile_suffix_overrides() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.raze_settings.buildfile_suffix_override = Some("BUILD.bazel".to_owned());
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![library_crate]);
+
+    assert!(crates_bzl_contents.contains(
+      "build_file = Label(\"//workspace/prefix/remote:test-library-1.1.1.BUILD.bazel\"),"
+    ));
+  }
+
+  #[test]
+  fn git_dependencies_can_skip_submodules() {
+    let mut git_crate = dummy_git_library_crate();
+    git_crate.raze_settings.init_submodules = false;
+
+    let crates_bzl_contents = render_bzl_fetch_for_test(vec![git_crate]);
+
+    assert!(crates_bzl_contents.contains("init_submodules = False,"));
+  }
+
+  #[test]
+  fn path_dependencies_are_not_fetched() {
+    let mut local_crate = dummy_library_crate();
+    local_crate.source_details.path_data = Some(PathSource {
+      path: "../test-library".to_owned(),
+    });
+    local_crate.expected_build_path = "../test-library/BUILD".to_owned();
+    local_crate.workspace_path_to_crate = "//test-library".to_owned();
+
+    let file_outputs = BazelRenderer::new()
+      .render_remote_planned_build(
+        &dummy_render_details("BUILD"),
+        &dummy_planned_build(vec![local_crate]),
+      )
+      .unwrap();
+    let crate_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/../test-library/BUILD");
+    let crates_bzl_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/crates.bzl");
+
+    assert!(crate_build_contents.contains("rust_library("));
+    assert!(crate_build_contents.contains("    srcs = glob([\"**/*.rs\"]),\n"));
+    assert!(!crate_build_contents.contains("//:srcs"));
+    assert!(!crates_bzl_contents.contains("test_library"));
+    assert!(crates_bzl_contents.contains("This is a no-op"));
+  }
+
+  #[test]
+  fn path_dependencies_do_not_overwrite_user_build_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let manifest_dir = temp_dir.path().join("cargo");
+    fs::create_dir_all(&manifest_dir).unwrap();
+    fs::create_dir_all(temp_dir.path().join("test-library")).unwrap();
+    let render_details = RenderDetails {
+      path_prefix: manifest_dir.display().to_string(),
+      ..dummy_render_details("BUILD")
+    };
+
+    let mut local_crate = dummy_library_crate();
+    local_crate.source_details.path_data = Some(PathSource {
+      path: "../test-library".to_owned(),
+    });
+    local_crate.expected_build_path = "../test-library/BUILD".to_owned();
+    let planned_build = dummy_planned_build(vec![local_crate]);
+    let build_path = temp_dir.path().join("test-library/BUILD");
+
+    // Missing and generated build files are written
+    check_path_dependency_outputs(&render_details, &planned_build, &manifest_dir).unwrap();
+    fs::write(&build_path, "# @generated\n").unwrap();
+    check_path_dependency_outputs(&render_details, &planned_build, &manifest_dir).unwrap();
+
+    fs::write(&build_path, "# My own targets\n").unwrap();
+    let err =
+      check_path_dependency_outputs(&render_details, &planned_build, &manifest_dir).unwrap_err();
+    assert!(err.to_string().contains("Refusing to overwrite"));
+
+    // The build files are placed relative to the manifest directory, not the output one
+    let err =
+      check_path_dependency_outputs(&render_details, &planned_build, temp_dir.path()).unwrap_err();
+    assert!(err.to_string().contains("output directory"));
+  }
+
+  #[test]
+  fn binaries_get_rust_binary_rules() {
+    let file_outputs = render_crates_for_test(vec![dummy_binary_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-binary-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("rust_binary("),
+      format!(
+        "expected crate build contents to contain rust_binary, but it just contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn libraries_get_rust_library_rules() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("rust_library("),
+      format!(
+        "expected crate build contents to contain rust_library, but it just contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn proc_macros_get_rust_proc_macro_rules() {
+    let mut proc_macro_crate = dummy_library_crate();
+    proc_macro_crate.targets[0].kind = "proc-macro".to_owned();
+
+    let file_outputs = render_crates_for_test(vec![proc_macro_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("\"rust_proc_macro\",")
+        && crate_build_contents.contains("rust_proc_macro(")
+        && !crate_build_contents.contains("rust_library("),
+      format!(
+        "expected crate build contents to contain rust_proc_macro, but it just contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn libraries_dont_load_rust_proc_macro() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      !crate_build_contents.contains("rust_proc_macro"),
+      format!(
+        "expected crate build contents not to contain rust_proc_macro, but it contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn examples_get_rust_binary_rules_when_enabled() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.push(BuildableTarget {
+      name: "some-example".to_owned(),
+      kind: "example".to_owned(),
+      path: "examples/some-example.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library_crate
+      .default_deps
+      .dev_dependencies
+      .push(BuildableDependency {
+        name: "test-dev-dep".to_owned(),
+        version: "0.1.0".to_owned(),
+        buildable_target: "@raze__test_dev_dep__0_1_0//:test_dev_dep".to_owned(),
+        is_proc_macro: false,
+      });
+
+    let render_build_file = |library_crate: CrateContext| {
+      let file_outputs = render_crates_for_test(vec![library_crate]);
+      extract_contents_matching_path(
+        &file_outputs,
+        "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+      )
+    };
+
+    let crate_build_contents = render_build_file(library_crate.clone());
+    assert!(!crate_build_contents.contains("cargo_example_some_example"));
+    assert!(crate_build_contents.contains("# Unsupported target \"some-example\""));
+
+    library_crate.raze_settings.gen_examples = true;
+    let crate_build_contents = render_build_file(library_crate);
+    assert!(crate_build_contents.contains("rust_binary(\n"));
+    assert!(crate_build_contents.contains("name = \"cargo_example_some_example\","));
+    assert!(crate_build_contents.contains("\":test_library\","));
+    assert!(crate_build_contents.contains("\"@raze__test_dev_dep__0_1_0//:test_dev_dep\""));
+    assert!(!crate_build_contents.contains("Unsupported target"));
+  }
+
+  #[test]
+  fn crates_declare_their_licenses() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.license = get_license_from_str("MIT");
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents
+      .contains("licenses([\n    \"notice\",  # MIT from expression \"MIT\"\n])"));
+  }
+
+  #[test]
+  fn user_provided_strings_are_escaped_in_starlark_literals() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.license = LicenseData {
+      name: "Custom".to_owned(),
+      rating: "see \"LICENSE\"".to_owned(),
+      is_unknown: true,
+    };
+    library_crate.features = vec!["path\\to".to_owned()];
+    library_crate.raze_settings.tags = vec!["multi\nline".to_owned()];
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains("licenses([\n    \"see \\\"LICENSE\\\"\",  # Custom\n])"));
+    assert!(crate_build_contents.contains("        \"path\\\\to\",\n"));
+    assert!(crate_build_contents.contains("        \"multi\\nline\",\n"));
+  }
+
+  #[test]
+  fn user_provided_strings_are_escaped_in_docstrings() {
+    let mut planned_build = dummy_planned_build(vec![CrateContext {
+      pkg_name: "test\\library".to_owned(),
+      ..dummy_library_crate()
+    }]);
+    planned_build.workspace_context.generated_file_header = "DO \"\"\"NOT\"\"\" EDIT".to_owned();
+
+    let render_details = dummy_render_details("BUILD");
+    let mut file_outputs = BazelRenderer::new()
+      .render_planned_build(&render_details, &planned_build)
+      .unwrap();
+    file_outputs.extend(
+      BazelRenderer::new()
+        .render_remote_planned_build(&render_details, &planned_build)
+        .unwrap(),
+    );
+    let documented_file_outputs = file_outputs
+      .iter()
+      .filter(|file_output| file_output.contents.starts_with("\"\"\""))
+      .collect::<Vec<_>>();
+    assert_eq!(documented_file_outputs.len(), 5);
+    for file_output in documented_file_outputs {
+      assert!(
+        file_output
+          .contents
+          .contains("\nDO \\\"\\\"\\\"NOT\\\"\\\"\\\" EDIT\n\"\"\"\n"),
+        "{}",
+        file_output.contents
+      );
+    }
+    assert!(file_outputs.iter().any(|file_output| file_output
+      .contents
+      .contains("build file for test\\\\library 1.1.1.")));
+  }
+
+  #[test]
+  fn targets_declare_their_crate_root() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains("    crate_root = \"path/lib.rs\",\n"));
+  }
+
+  #[test]
+  fn targets_are_tagged_with_default_and_crate_tags() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.raze_settings.tags = vec!["no-clippy".to_owned(), "raze-generated".to_owned()];
+    let mut planned_build = dummy_planned_build(vec![library_crate]);
+    planned_build.workspace_context.default_tags = vec!["raze-generated".to_owned()];
+
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(&dummy_render_details("BUILD"), &planned_build)
+      .unwrap();
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+    let workspace_build_contents =
+      extract_contents_matching_path(&file_outputs, "./some_render_prefix/BUILD");
+
+    assert!(crate_build_contents
+      .contains("    tags = [\n        \"raze-generated\",\n        \"no-clippy\",\n    ],\n"));
+    assert!(
+      workspace_build_contents.contains("    tags = [\n        \"raze-generated\",\n    ],\n")
+    );
+    assert!(!crate_build_contents.contains("\"manual\""));
+    assert!(!workspace_build_contents.contains("\"no-clippy\""));
+  }
+
+  #[test]
+  fn libraries_are_also_built_as_their_alternate_crate_types() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets[0].name = "test_library".to_owned();
+    library_crate.raze_settings.crate_types = vec!["cdylib".to_owned()];
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+    assert!(crate_build_contents.contains(
+      "rust_library(\n    name = \"test_library\",\n    crate_type = \"lib\",\n    deps"
+    ));
+    assert!(crate_build_contents.contains(
+      ")\n\n# buildifier: leave-alone\nrust_library(\n    name = \"test_library_cdylib\",\n    \
+       crate_type = \"cdylib\",\n    crate_name = \"test_library\",\n    deps"
+    ));
+  }
+
+  #[test]
+  fn crates_with_a_library_and_a_proc_macro_render_both() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets[0].name = "test_library".to_owned();
+    library_crate.targets.push(BuildableTarget {
+      name: "test-library-macros".to_owned(),
+      kind: "proc-macro".to_owned(),
+      path: "path/macros.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+    assert!(crate_build_contents.contains("    \"rust_proc_macro\",\n"));
+    assert!(crate_build_contents.contains(
+      "rust_library(\n    name = \"test_library\",\n    crate_type = \"lib\",\n    deps"
+    ));
+    assert!(crate_build_contents
+      .contains("rust_proc_macro(\n    name = \"test_library_macros\",\n    deps"));
+    assert!(!crate_build_contents.contains("alias("));
+    assert!(!crate_build_contents.contains("crate_name"));
+  }
+
+  #[test]
+  fn libraries_declare_a_crate_name_distinct_from_their_target_name() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.pkg_name = "foo-bar".to_owned();
+    library_crate.lib_target_name = Some("foo_bar".to_owned());
+    library_crate.targets[0].name = "foo_bar".to_owned();
+    let crate_build_contents_of = |library_crate: CrateContext| {
+      let file_outputs = render_crates_for_test(vec![library_crate]);
+      extract_contents_matching_path(
+        &file_outputs,
+        "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+      )
+    };
+
+    let crate_build_contents = crate_build_contents_of(library_crate.clone());
+    assert!(crate_build_contents
+      .contains("rust_library(\n    name = \"foo_bar\",\n    crate_type = \"lib\",\n    deps"));
+    assert!(!crate_build_contents.contains("crate_name"));
+
+    library_crate.targets[0].name = "readable-name".to_owned();
+    let crate_build_contents = crate_build_contents_of(library_crate);
+    assert!(crate_build_contents.contains(
+      "rust_library(\n    name = \"readable_name\",\n    crate_type = \"lib\",\n    crate_name = \
+       \"foo_bar\",\n"
+    ));
+  }
+
+  #[test]
+  fn tests_get_rust_test_rules() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.push(BuildableTarget {
+      name: "some_test".to_owned(),
+      kind: "test".to_owned(),
+      path: "tests/some_test.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library_crate
+      .default_deps
+      .dev_dependencies
+      .push(BuildableDependency {
+        name: "test-dev-dep".to_owned(),
+        version: "0.1.0".to_owned(),
+        buildable_target: "@raze__test_dev_dep__0_1_0//:test_dev_dep".to_owned(),
+        is_proc_macro: false,
+      });
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("rust_test(")
+        && crate_build_contents.contains("\"@raze__test_dev_dep__0_1_0//:test_dev_dep\""),
+      format!(
+        "expected crate build contents to contain rust_test with dev dependencies, but it just \
+         contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn data_attrs_are_included_when_set() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings: CrateSettings {
+        data_attr: vec!["glob([\"**/*.proto\"])".into(), "[\"//some:label\"]".into()],
+        compile_data_attr: vec!["glob([\"templates/**\"])".into()],
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("data = glob([\"**/*.proto\"]) + [\"//some:label\"],")
+        && crate_build_contents.contains("compile_data = glob([\"templates/**\"]),"),
+      format!(
+        "expected crate build contents to include data attributes, but it just contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn data_attrs_are_omitted_when_empty() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      !crate_build_contents.contains("data ="),
+      format!(
+        "expected crate build contents not to include data attributes, but it contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn docs_are_included_when_enabled() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings: CrateSettings {
+        gen_docs: true,
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains("    \"rust_doc\",\n    \"rust_doc_test\",\n"));
+    assert!(crate_build_contents
+      .contains("rust_doc(\n    name = \"some_library_doc\",\n    dep = \":some_library\",\n)"));
+    assert!(crate_build_contents.contains("rust_doc_test(\n"));
+    assert!(crate_build_contents.contains("    name = \"some_library_doc_test\",\n"));
+  }
+
+  #[test]
+  fn docs_are_omitted_by_default() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate(), dummy_binary_crate()]);
+
+    for output in file_outputs {
+      assert!(
+        !output.contents.contains("rust_doc"),
+        "expected {} not to include doc rules, but it contained [{}]",
+        output.path,
+        output.contents
+      );
+    }
+  }
+
+  #[test]
+  fn rustc_env_is_rendered_sorted_by_key() {
+    let mut raze_settings = CrateSettings::default();
+    raze_settings
+      .additional_env
+      .insert("ZED".to_owned(), "last".to_owned());
+    raze_settings
+      .additional_env
+      .insert("ALPHA".to_owned(), "first".to_owned());
+
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings,
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains(
+      "    rustc_env = {\n        \"ALPHA\": \"first\",\n        \"ZED\": \"last\",\n    },\n"
+    ));
+  }
+
+  #[test]
+  fn build_proc_macro_dependencies_are_proc_macro_deps_of_the_build_script() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.build_script_target = Some(BuildableTarget {
+      name: "build_script_build".to_owned(),
+      kind: "custom-build".to_owned(),
+      path: "build.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library_crate.default_deps.build_dependencies = vec![BuildableDependency {
+      name: "cc".to_owned(),
+      version: "1.0.0".to_owned(),
+      buildable_target: "@raze__cc__1_0_0//:cc".to_owned(),
+      is_proc_macro: false,
+    }];
+    library_crate.default_deps.build_proc_macro_dependencies = vec![BuildableDependency {
+      name: "build_macros".to_owned(),
+      version: "1.0.0".to_owned(),
+      buildable_target: "@raze__build_macros__1_0_0//:build_macros".to_owned(),
+      is_proc_macro: true,
+    }];
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains(
+      "    deps = [\n        \"@raze__cc__1_0_0//:cc\",\n    ],\n    proc_macro_deps = [\n        \
+       \"@raze__build_macros__1_0_0//:build_macros\",\n    ],\n    rustc_flags"
+    ));
+    // Build dependencies are not dependencies of the crate itself
+    assert_eq!(
+      crate_build_contents
+        .matches("@raze__build_macros__1_0_0//:build_macros")
+        .count(),
+      1
+    );
+  }
+
+  #[test]
+  fn build_script_env_is_rendered_sorted_by_key() {
+    let mut raze_settings = CrateSettings::default();
+    raze_settings
+      .buildrs_additional_environment_variables
+      .insert("ZED".to_owned(), "last".to_owned());
+    raze_settings
+      .buildrs_additional_environment_variables
+      .insert("ALPHA".to_owned(), "first".to_owned());
+
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings,
+      build_script_target: Some(BuildableTarget {
+        name: "build_script_build".to_owned(),
+        kind: "custom-build".to_owned(),
+        path: "build.rs".to_owned(),
+        edition: "2015".to_owned(),
+      }),
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains("cargo_build_script(\n"));
+    assert!(crate_build_contents.contains(
+      "    build_script_env = {\n        \"ALPHA\": \"first\",\n        \"ZED\": \"last\",\n    },\n"
+    ));
+  }
+
+  #[test]
+  fn rust_rules_are_loaded_from_the_rules_rust_repository() {
+    let crate_context = CrateContext {
+      build_script_target: Some(BuildableTarget {
+        name: "build_script_build".to_owned(),
+        kind: "custom-build".to_owned(),
+        path: "build.rs".to_owned(),
+        edition: "2015".to_owned(),
+      }),
+      ..dummy_library_crate()
+    };
+    let mut planned_build = dummy_planned_build(vec![crate_context]);
+    planned_build.workspace_context.rules_rust_repository_name = "rules_rust".to_owned();
+
+    let mut renderer = BazelRenderer::new();
+    let render_details = dummy_render_details("BUILD");
+    let vendored_outputs = renderer
+      .render_planned_build(&render_details, &planned_build)
+      .unwrap();
+    let remote_outputs = renderer
+      .render_remote_planned_build(&render_details, &planned_build)
+      .unwrap();
+
+    for file_outputs in [&vendored_outputs, &remote_outputs] {
+      let crate_build_contents = extract_contents_matching_path(
+        file_outputs,
+        "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+      );
+      assert!(crate_build_contents.contains("\"@rules_rust//rust:rust.bzl\","));
+      assert!(crate_build_contents.contains("\"@rules_rust//cargo:cargo_build_script.bzl\","));
+      assert!(!crate_build_contents.contains("io_bazel_rules_rust"));
+
+      // The workspace BUILD file declares aliases only, so it loads no rust rules
+      let workspace_build_contents =
+        extract_contents_matching_path(file_outputs, "./some_render_prefix/BUILD");
+      assert!(!workspace_build_contents.contains("io_bazel_rules_rust"));
+    }
+  }
+
+  fn dummy_dependency(name: &str) -> BuildableDependency {
+    BuildableDependency {
+      name: name.to_owned(),
+      version: "1.0.0".to_owned(),
+      buildable_target: format!("@raze__{}__1_0_0//:{}", name, name),
+      is_proc_macro: false,
+    }
+  }
+
+  fn assert_in_order(contents: &str, needles: &[&str]) {
+    let positions = needles
+      .iter()
+      .map(|needle| {
+        contents
+          .find(needle)
+          .unwrap_or_else(|| panic!("expected [{}] to contain [{}]", contents, needle))
+      })
+      .collect::<Vec<usize>>();
+
+    let mut sorted_positions = positions.clone();
+    sorted_positions.sort();
+    assert_eq!(
+      positions, sorted_positions,
+      "expected {:?} to appear in order in [{}]",
+      needles, contents
+    );
+  }
+
+  #[test]
+  fn dependencies_are_rendered_sorted_by_label() {
+    let scrambled = |names: &[&str]| {
+      names
+        .iter()
+        .map(|name| dummy_dependency(name))
+        .collect::<Vec<BuildableDependency>>()
+    };
+
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      default_deps: CrateDependencyContext {
+        dependencies: scrambled(&["zeta", "alpha", "mid"]),
+        proc_macro_dependencies: scrambled(&["pm_zeta", "pm_alpha"]),
+        build_dependencies: Vec::new(),
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: scrambled(&["dev_zeta", "dev_alpha"]),
+        aliased_dependencies: vec![
+          DependencyAlias {
+            target: "@raze__zeta__1_0_0//:zeta".to_owned(),
+            alias: "zeta_alias".to_owned(),
+          },
+          DependencyAlias {
+            target: "@raze__alpha__1_0_0//:alpha".to_owned(),
+            alias: "alpha_alias".to_owned(),
+          },
+        ],
+      },
+      targets: vec![
+        BuildableTarget {
+          name: "some_library".to_owned(),
+          kind: "lib".to_owned(),
+          path: "path/lib.rs".to_owned(),
+          edition: "2015".to_owned(),
+        },
+        BuildableTarget {
+          name: "some_test".to_owned(),
+          kind: "test".to_owned(),
+          path: "tests/some_test.rs".to_owned(),
+          edition: "2015".to_owned(),
+        },
+      ],
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert_in_order(
+      &crate_build_contents,
+      &[
+        "\"@raze__alpha__1_0_0//:alpha\",",
+        "\"@raze__mid__1_0_0//:mid\",",
+        "\"@raze__zeta__1_0_0//:zeta\",",
+      ],
+    );
+    assert_in_order(
+      &crate_build_contents,
+      &[
+        "\"@raze__pm_alpha__1_0_0//:pm_alpha\",",
+        "\"@raze__pm_zeta__1_0_0//:pm_zeta\",",
+      ],
+    );
+    assert_in_order(
+      &crate_build_contents,
+      &[
+        "\"@raze__dev_alpha__1_0_0//:dev_alpha\",",
+        "\"@raze__dev_zeta__1_0_0//:dev_zeta\",",
+      ],
+    );
+    assert_in_order(
+      &crate_build_contents,
+      &[
+        "\"@raze__alpha__1_0_0//:alpha\": \"alpha_alias\",",
+        "\"@raze__zeta__1_0_0//:zeta\": \"zeta_alias\",",
+      ],
+    );
+  }
+
+  #[test]
+  fn aliased_dependencies_are_rendered_with_their_alias() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      default_deps: CrateDependencyContext {
+        dependencies: vec![
+          BuildableDependency {
+            name: "shared".to_owned(),
+            version: "0.1.0".to_owned(),
+            buildable_target: "@raze__shared__0_1_0//:shared".to_owned(),
+            is_proc_macro: false,
+          },
+          BuildableDependency {
+            name: "shared".to_owned(),
+            version: "0.2.0".to_owned(),
+            buildable_target: "@raze__shared__0_2_0//:shared".to_owned(),
+            is_proc_macro: false,
+          },
+        ],
+        proc_macro_dependencies: Vec::new(),
+        build_dependencies: Vec::new(),
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: Vec::new(),
+        aliased_dependencies: vec![DependencyAlias {
+          target: "@raze__shared__0_1_0//:shared".to_owned(),
+          alias: "old_shared".to_owned(),
+        }],
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains(
+      "    deps = [\n        \"@raze__shared__0_1_0//:shared\",\n        \
+       \"@raze__shared__0_2_0//:shared\",\n    ],\n"
+    ));
+    assert!(crate_build_contents.contains(
+      "    aliases = {\n        \"@raze__shared__0_1_0//:shared\": \"old_shared\",\n    },\n"
+    ));
+  }
+
+  #[test]
+  fn legacy_buildfile_stubs_sit_next_to_bazel_buildfiles() {
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(
+        &RenderDetails {
+          legacy_buildfile_stubs: true,
+          ..dummy_render_details("BUILD.bazel")
+        },
+        &dummy_planned_build(vec![dummy_library_crate_with_name("BUILD.bazel")]),
+      )
+      .unwrap();
+    let contents_of = |path: &str| {
+      file_outputs
+        .iter()
+        .find(|output| output.path == path)
+        .map(|output| output.contents.clone())
+        .unwrap_or_else(|| panic!("expected {} in {:?}", path, file_outputs))
+    };
+
+    assert_eq!(file_outputs.len(), 4);
+    for package in &[
+      "./some_render_prefix",
+      "./some_render_prefix/vendor/test-library-1.1.1",
+    ] {
+      let buildfile = contents_of(&format!("{}/BUILD.bazel", package));
+      let stub = contents_of(&format!("{}/BUILD", package));
+
+      assert!(buildfile.contains("name = \"test_library\""));
+      assert!(stub.contains("defined in BUILD.bazel"));
+      assert!(stub.lines().all(|line| line.starts_with('#')));
+    }
+  }
+
+  #[test]
+  fn aliases_can_be_written_to_their_own_package() {
+    let render_details = RenderDetails {
+      aliases_package: Some("aliases".to_owned()),
+      ..dummy_render_details("BUILD")
+    };
+    let planned_build = dummy_planned_build(vec![dummy_library_crate()]);
+
+    for remote in [false, true] {
+      let mut renderer = BazelRenderer::new();
+      let file_outputs = if remote {
+        renderer.render_remote_planned_build(&render_details, &planned_build)
+      } else {
+        renderer.render_planned_build(&render_details, &planned_build)
+      }
+      .unwrap();
+
+      let aliases_contents =
+        extract_contents_matching_path(&file_outputs, "./some_render_prefix/aliases/BUILD");
+      assert!(aliases_contents.contains("alias(\n    name = \"test_library\","));
+      assert!(!file_outputs
+        .iter()
+        .any(|output| output.path == "./some_render_prefix/BUILD"));
+    }
+  }
+
+  #[test]
+  fn rendered_crates_are_reported_once_each() {
+    let other_crate = CrateContext {
+      pkg_name: "other-library".to_owned(),
+      expected_build_path: "vendor/other-library-1.1.1/BUILD".to_owned(),
+      ..dummy_library_crate()
+    };
+    let planned_build = dummy_planned_build(vec![dummy_library_crate(), other_crate]);
+
+    for remote in [false, true] {
+      let rendered_crates = Mutex::new(Vec::new());
+      let on_crate_rendered = |crate_name: &str| {
+        rendered_crates.lock().unwrap().push(crate_name.to_owned());
+      };
+
+      let mut renderer = BazelRenderer::new();
+      if remote {
+        renderer.render_remote_planned_build_with_progress(
+          &dummy_render_details("BUILD"),
+          &planned_build,
+          &on_crate_rendered,
+        )
+      } else {
+        renderer.render_planned_build_with_progress(
+          &dummy_render_details("BUILD"),
+          &planned_build,
+          &on_crate_rendered,
+        )
+      }
+      .unwrap();
+
+      let mut rendered_crates = rendered_crates.into_inner().unwrap();
+      rendered_crates.sort();
+      assert_eq!(rendered_crates, vec!["other-library", "test-library"]);
+    }
+  }
+
+  #[test]
+  fn a_subset_of_crates_can_be_rendered() {
+    let mut other_crate = dummy_library_crate_with_name("BUILD.bazel");
+    other_crate.pkg_name = "other-library".to_owned();
+    other_crate.expected_build_path = "vendor/other-library-1.1.1/BUILD.bazel".to_owned();
+    let planned_build = dummy_planned_build(vec![
+      dummy_library_crate_with_name("BUILD.bazel"),
+      other_crate,
+    ]);
+    let render_details = RenderDetails {
+      legacy_buildfile_stubs: true,
+      ..dummy_render_details("BUILD.bazel")
+    };
+
+    let mut vendored_paths = render_subset(
+      &mut BazelRenderer::new(),
+      &RazeSettings {
+        genmode: GenMode::Vendored,
+        ..dummy_raze_settings()
+      },
+      &render_details,
+      &planned_build,
+      &["other-library".to_owned()],
+    )
+    .unwrap()
+    .into_iter()
+    .map(|output| output.path)
+    .collect::<Vec<String>>();
+    vendored_paths.sort();
+    assert_eq!(
+      vendored_paths,
+      vec![
+        "./some_render_prefix/BUILD",
+        "./some_render_prefix/BUILD.bazel",
+        "./some_render_prefix/vendor/other-library-1.1.1/BUILD",
+        "./some_render_prefix/vendor/other-library-1.1.1/BUILD.bazel",
+      ]
+    );
+
+    let remote_outputs = render_subset(
+      &mut BazelRenderer::new(),
+      &dummy_raze_settings(),
+      &render_details,
+      &planned_build,
+      &["other-library".to_owned()],
+    )
+    .unwrap();
+    let remote_paths = remote_outputs
+      .iter()
+      .map(|output| output.path.as_str())
+      .collect::<Vec<&str>>();
+    assert!(remote_paths.contains(&"./some_render_prefix/vendor/other-library-1.1.1/BUILD.bazel"));
+    assert!(!remote_paths.contains(&"./some_render_prefix/vendor/test-library-1.1.1/BUILD.bazel"));
+    // The aggregate files still cover every crate
+    let crates_bzl_contents =
+      extract_contents_matching_path(&remote_outputs, "./some_render_prefix/crates.bzl");
+    assert!(crates_bzl_contents.contains("test-library-1.1.1"));
+    assert!(crates_bzl_contents.contains("other-library-1.1.1"));
+
+    assert!(render_subset(
+      &mut BazelRenderer::new(),
+      &dummy_raze_settings(),
+      &render_details,
+      &planned_build,
+      &["unknown-library".to_owned()],
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn legacy_buildfile_stubs_are_not_emitted_for_build_files() {
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(
+        &RenderDetails {
+          legacy_buildfile_stubs: true,
+          ..dummy_render_details("BUILD")
+        },
+        &dummy_planned_build(vec![dummy_library_crate()]),
+      )
+      .unwrap();
+
+    assert_eq!(file_outputs.len(), 2);
+  }
+
+  #[test]
+  fn targeted_dependency_selects_have_a_default_arm() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.extend(vec![
+      BuildableTarget {
+        name: "some_binary".to_owned(),
+        kind: "bin".to_owned(),
+        path: "bin/main.rs".to_owned(),
+        edition: "2015".to_owned(),
+      },
+      BuildableTarget {
+        name: "some_test".to_owned(),
+        kind: "test".to_owned(),
+        path: "tests/some_test.rs".to_owned(),
+        edition: "2015".to_owned(),
+      },
+    ]);
+    library_crate.targeted_deps = vec![CrateTargetedDepContext {
+      target: "cfg(unix)".to_owned(),
+      deps: CrateDependencyContext {
+        dependencies: vec![dummy_dependency("unix_dep")],
+        proc_macro_dependencies: Vec::new(),
+        build_dependencies: Vec::new(),
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: Vec::new(),
+        aliased_dependencies: Vec::new(),
+      },
+      conditions: vec!["@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu".to_owned()],
+    }];
+
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    // One select of the targeted dependencies for each of the library, binary and test
+    let select_count = crate_build_contents.matches("selects.with_or(").count();
+    assert_eq!(select_count, 3);
+    assert_eq!(
+      crate_build_contents
+        .matches("        \"//conditions:default\": [],\n    })")
+        .count(),
+      select_count
+    );
+  }
+
+  #[test]
+  fn targeted_proc_macro_dependencies_are_selected_in_proc_macro_deps() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      targeted_deps: vec![CrateTargetedDepContext {
+        target: "cfg(unix)".to_owned(),
+        deps: CrateDependencyContext {
+          dependencies: vec![dummy_dependency("unix_dep")],
+          proc_macro_dependencies: vec![BuildableDependency {
+            is_proc_macro: true,
+            ..dummy_dependency("unix_macro")
+          }],
+          build_dependencies: Vec::new(),
+          build_proc_macro_dependencies: Vec::new(),
+          dev_dependencies: Vec::new(),
+          aliased_dependencies: Vec::new(),
+        },
+        conditions: vec!["@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu".to_owned()],
+      }],
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    let select_of = |label: &str| {
+      format!(
+        " + selects.with_or({{\n        # cfg(unix)\n        (\n            \
+         \"@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu\",\n        ): [\n            \
+         \"{}\",\n        ],\n        \"//conditions:default\": [],\n    }}),\n",
+        label
+      )
+    };
+    assert!(crate_build_contents.contains(&format!(
+      "    deps = [\n    ]{}",
+      select_of("@raze__unix_dep__1_0_0//:unix_dep")
+    )));
+    assert!(crate_build_contents.contains(&format!(
+      "    proc_macro_deps = [\n    ]{}",
+      select_of("@raze__unix_macro__1_0_0//:unix_macro")
+    )));
+  }
+
+  #[test]
+  fn targeted_build_dependencies_are_selected_in_the_build_script_deps() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      build_script_target: Some(BuildableTarget {
+        name: "build_script_build".to_owned(),
+        kind: "custom-build".to_owned(),
+        path: "build.rs".to_owned(),
+        edition: "2015".to_owned(),
+      }),
+      targeted_deps: vec![CrateTargetedDepContext {
+        target: "cfg(windows)".to_owned(),
+        deps: CrateDependencyContext {
+          dependencies: Vec::new(),
+          proc_macro_dependencies: Vec::new(),
+          build_dependencies: vec![dummy_dependency("winres")],
+          build_proc_macro_dependencies: Vec::new(),
+          dev_dependencies: Vec::new(),
+          aliased_dependencies: Vec::new(),
+        },
+        conditions: vec!["@io_bazel_rules_rust//rust/platform:x86_64-pc-windows-msvc".to_owned()],
+      }],
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains(
+      "    edition = \"2015\",\n    deps = [\n    ] + selects.with_or({\n        # cfg(windows)\n        \
+       (\n            \"@io_bazel_rules_rust//rust/platform:x86_64-pc-windows-msvc\",\n        ): \
+       [\n            \"@raze__winres__1_0_0//:winres\",\n        ],\n        \
+       \"//conditions:default\": [],\n    }),\n    rustc_flags"
+    ));
+    // Build dependencies are not dependencies of the crate itself
+    assert_eq!(
+      crate_build_contents
+        .matches("@raze__winres__1_0_0//:winres")
+        .count(),
+      1
+    );
+  }
+
+  #[test]
+  fn every_target_is_built_with_all_of_the_crate_features() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.push(BuildableTarget {
+      name: "some_binary".to_owned(),
+      kind: "bin".to_owned(),
+      path: "path/main.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert_eq!(
+      crate_build_contents.matches("crate_features = [").count(),
+      2
+    );
+    assert_eq!(
+      crate_build_contents
+        .matches("    crate_features = [\n        \"feature1\",\n        \"feature2\",\n    ],\n")
+        .count(),
+      2
+    );
+  }
+
+  #[test]
+  fn additional_target_deps_only_apply_to_their_target() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.push(BuildableTarget {
+      name: "some_binary".to_owned(),
+      kind: "bin".to_owned(),
+      path: "path/main.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library_crate.default_deps.dependencies = vec![dummy_dependency("log")];
+    library_crate.target_dependencies.insert(
+      "bin:some_binary".to_owned(),
+      vec![
+        BuildableDependency {
+          name: "//cli:clap".to_owned(),
+          version: String::new(),
+          buildable_target: "//cli:clap".to_owned(),
+          is_proc_macro: false,
+        },
+        dummy_dependency("log"),
+      ],
+    );
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert_eq!(crate_build_contents.matches("\"//cli:clap\"").count(), 1);
+    let binary_rule = crate_build_contents.split("rust_binary(\n").nth(1).unwrap();
+    assert!(
+      binary_rule.contains("        \"//cli:clap\",\n        \"@raze__log__1_0_0//:log\",\n    ]")
+    );
+  }
+
+  #[test]
+  fn additional_target_deps_tell_apart_a_library_and_binary_of_the_same_name() {
+    let mut library_crate = dummy_library_crate();
+    library_crate.targets.push(BuildableTarget {
+      name: "some_library".to_owned(),
+      kind: "bin".to_owned(),
+      path: "path/main.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library_crate.default_deps.dependencies = vec![dummy_dependency("log")];
+    library_crate.target_dependencies.insert(
+      "bin:some_library".to_owned(),
+      vec![
+        BuildableDependency {
+          name: "//cli:clap".to_owned(),
+          version: String::new(),
+          buildable_target: "//cli:clap".to_owned(),
+          is_proc_macro: false,
+        },
+        dummy_dependency("log"),
+      ],
+    );
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert_eq!(crate_build_contents.matches("\"//cli:clap\"").count(), 1);
+    let (library_rule, binary_rule) = crate_build_contents.split_once("rust_binary(\n").unwrap();
+    assert!(!library_rule.contains("//cli:clap"));
+    assert!(
+      binary_rule.contains("        \"//cli:clap\",\n        \"@raze__log__1_0_0//:log\",\n    ]")
+    );
+  }
+
+  #[test]
+  fn every_target_is_built_with_the_crate_version() {
+    let mut library_crate = CrateContext {
+      pkg_version: "2.3.4-beta.1".to_owned(),
+      expected_build_path: "vendor/test-library-2.3.4-beta.1/BUILD".to_owned(),
+      build_script_target: Some(BuildableTarget {
+        name: "build_script_build".to_owned(),
+        kind: "custom-build".to_owned(),
+        path: "build.rs".to_owned(),
+        edition: "2015".to_owned(),
+      }),
+      ..dummy_library_crate()
+    };
+    library_crate.targets.push(BuildableTarget {
+      name: "some_binary".to_owned(),
+      kind: "bin".to_owned(),
+      path: "path/main.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    let file_outputs = render_crates_for_test(vec![library_crate]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-2.3.4-beta.1/BUILD",
+    );
+
+    // The build script, the library and the binary
+    assert_eq!(crate_build_contents.matches("    version = ").count(), 3);
+    assert_eq!(
+      crate_build_contents
+        .matches("    version = \"2.3.4-beta.1\",\n")
+        .count(),
+      3
+    );
+  }
+
+  #[test]
+  fn targeted_features_are_selected_in_crate_features() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      features: vec!["std".to_owned()],
+      targeted_features: vec![CrateTargetedFeatureContext {
+        features: vec!["simd".to_owned()],
+        conditions: vec!["@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu".to_owned()],
+      }],
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    assert!(crate_build_contents.contains(
+      "    crate_features = [\n        \"std\",\n    ] + selects.with_or({\n        (\n            \
+       \"@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu\",\n        ): [\n            \
+       \"simd\",\n        ],\n        \"//conditions:default\": [],\n    }),\n"
+    ));
+  }
+
+  #[test]
+  fn visibility_defaults_to_public() {
+    let file_outputs = render_crates_for_test(vec![dummy_library_crate()]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("\"//visibility:public\","),
+      format!(
+        "expected crate build contents to be publicly visible, but it just contained [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn visibility_is_included_when_set() {
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings: CrateSettings {
+        visibility: vec!["//cargo:__pkg__".into(), "//some/package:__subpackages__".into()],
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("\"//cargo:__pkg__\",")
+        && crate_build_contents.contains("\"//some/package:__subpackages__\",")
+        && !crate_build_contents.contains("//visibility:public"),
+      format!(
+        "expected crate build contents to include configured visibility, but it just contained \
+         [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn additional_build_file_missing_file_failure() {
+    let render_result = BazelRenderer::new().render_planned_build(
+      &dummy_render_details("BUILD"),
+      &dummy_planned_build(vec![CrateContext {
+        raze_settings: CrateSettings {
+          additional_build_file: Some("non-existent-file".into()),
+          ..Default::default()
+        },
+        ..dummy_library_crate()
+      }]),
+    );
+
+    assert_that!(render_result, err());
   }
 
   #[test]
   fn additional_build_file_included() {
+    // N.B. The README is found relative to the working directory
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let file_outputs = render_crates_for_test(vec![CrateContext {
+      raze_settings: CrateSettings {
+        additional_build_file: Some("README.md".into()),
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    expect(
+      crate_build_contents.contains("# Additional content from README.md"),
+      format!(
+        "expected crate build contents to include additional_build_file, but it just contained \
+         [{}]",
+        crate_build_contents
+      ),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn additional_build_content_is_included_after_files() {
+    // N.B. The README is found relative to the working directory
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
     let file_outputs = render_crates_for_test(vec![CrateContext {
       raze_settings: CrateSettings {
         additional_build_file: Some("README.md".into()),
+        additional_build_content: Some("exports_files([\"LICENSE\"])\n".into()),
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    }]);
+    let crate_build_contents = extract_contents_matching_path(
+      &file_outputs,
+      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
+    );
+
+    let file_marker = crate_build_contents
+      .find("# Additional content from README.md")
+      .unwrap();
+    let inline_marker = crate_build_contents
+      .find("# Additional inline content\n")
+      .unwrap();
+    assert!(file_marker < inline_marker);
+    assert!(
+      crate_build_contents.ends_with("# Additional inline content\nexports_files([\"LICENSE\"])\n")
+    );
+  }
+
+  #[test]
+  fn additional_build_files_are_included_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let prelude_path = temp_dir.path().join("prelude.BUILD");
+    let snippet_path = temp_dir.path().join("snippet.BUILD");
+    fs::write(&prelude_path, "# prelude contents\n").unwrap();
+    fs::write(&snippet_path, "# snippet contents\n").unwrap();
+    let prelude_path = prelude_path.display().to_string();
+    let snippet_path = snippet_path.display().to_string();
+
+    let crate_context = CrateContext {
+      raze_settings: CrateSettings {
+        additional_build_files: vec![prelude_path.clone(), snippet_path.clone()],
+        ..Default::default()
+      },
+      ..dummy_library_crate()
+    };
+    let contents =
+      include_additional_build_file(&crate_context, "# generated\n".to_owned()).unwrap();
+
+    assert_eq!(
+      contents,
+      format!(
+        "# generated\n\n# Additional content from {}\n# prelude contents\n\n# Additional content \
+         from {}\n# snippet contents\n",
+        prelude_path, snippet_path
+      )
+    );
+
+    let missing_path = temp_dir.path().join("missing.BUILD").display().to_string();
+    let crate_context = CrateContext {
+      raze_settings: CrateSettings {
+        additional_build_files: vec![prelude_path, missing_path.clone()],
         ..Default::default()
       },
       ..dummy_library_crate()
-    }]);
-    let crate_build_contents = extract_contents_matching_path(
-      &file_outputs,
-      "./some_render_prefix/vendor/test-library-1.1.1/BUILD",
-    );
+    };
+    let message = include_additional_build_file(&crate_context, String::new())
+      .unwrap_err()
+      .to_string();
+    assert!(message.contains(&missing_path));
+  }
 
-    expect(
-      crate_build_contents.contains("# Additional content from README.md"),
-      format!(
-        "expected crate build contents to include additional_build_file, but it just contained \
-         [{}]",
-        crate_build_contents
-      ),
-    )
-    .unwrap();
+  #[test]
+  fn formatting_falls_back_to_raw_contents() {
+    let file_outputs = vec![FileOutputs {
+      path: "./some_render_prefix/BUILD".to_owned(),
+      contents: "package(default_visibility = [\"//visibility:public\"])\n".to_owned(),
+    }];
+
+    let formatted_outputs =
+      format_file_outputs(file_outputs.clone(), Some("/not/a/path/to/buildifier"));
+
+    assert_eq!(formatted_outputs.len(), 1);
+    assert_eq!(formatted_outputs[0].path, file_outputs[0].path);
+    assert_eq!(formatted_outputs[0].contents, file_outputs[0].contents);
+  }
+
+  #[test]
+  fn relative_additional_build_files_are_found_from_the_workspace_root() {
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // Cache the cwd
+    let cwd = env::current_dir().unwrap();
+
+    // Run test
+    let result = std::panic::catch_unwind(|| {
+      let bazel_root = TempDir::new().unwrap();
+      File::create(bazel_root.path().join("WORKSPACE.bazel")).unwrap();
+      fs::create_dir_all(bazel_root.path().join("cargo/some/package")).unwrap();
+      fs::write(
+        bazel_root.path().join("cargo/extra.BUILD"),
+        "# extra contents\n",
+      )
+      .unwrap();
+      assert!(env::set_current_dir(bazel_root.path().join("cargo/some/package")).is_ok());
+
+      let crate_context = CrateContext {
+        raze_settings: CrateSettings {
+          additional_build_file: Some("cargo/extra.BUILD".to_owned()),
+          ..Default::default()
+        },
+        ..dummy_library_crate()
+      };
+      let contents = include_additional_build_file(&crate_context, String::new()).unwrap();
+
+      assert_eq!(
+        contents,
+        "\n# Additional content from cargo/extra.BUILD\n# extra contents\n"
+      );
+    });
+
+    // Restore cwd
+    assert!(env::set_current_dir(&cwd).is_ok());
+
+    // Ensure test results were successful
+    assert!(result.is_ok());
   }
 
   #[test]
   fn detecting_workspace_root() {
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
     // Cache the cwd
     let cwd = env::current_dir().unwrap();
 
@@ -842,46 +3622,436 @@ mod tests {
     assert!(result.is_ok());
   }
 
+  #[test]
+  fn detecting_bzlmod_workspace_root() {
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // Cache the cwd
+    let cwd = env::current_dir().unwrap();
+
+    // Run test
+    let result = std::panic::catch_unwind(|| {
+      let bazel_root = TempDir::new().unwrap();
+      File::create(bazel_root.path().join("MODULE.bazel")).unwrap();
+      assert!(env::set_current_dir(&bazel_root).is_ok());
+
+      assert_eq!(
+        find_workspace_root(),
+        Some(bazel_root.path().canonicalize().unwrap())
+      );
+    });
+
+    // Restore cwd
+    assert!(env::set_current_dir(&cwd).is_ok());
+
+    // Ensure test results were successful
+    assert!(result.is_ok());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn detecting_workspace_root_through_symlink() {
+    let _cwd_guard = CWD_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // Cache the cwd
+    let cwd = env::current_dir().unwrap();
+
+    // Run test
+    let result = std::panic::catch_unwind(|| {
+      let bazel_root = TempDir::new().unwrap();
+      File::create(bazel_root.path().join("WORKSPACE.bazel")).unwrap();
+      fs::create_dir(bazel_root.path().join("package")).unwrap();
+
+      // Enter a package of the workspace through a symlink living outside of it
+      let links = TempDir::new().unwrap();
+      let linked_package = links.path().join("package");
+      std::os::unix::fs::symlink(bazel_root.path().join("package"), &linked_package).unwrap();
+      assert!(env::set_current_dir(&linked_package).is_ok());
+
+      assert_eq!(
+        find_workspace_root(),
+        Some(bazel_root.path().canonicalize().unwrap())
+      );
+    });
+
+    // Restore cwd
+    assert!(env::set_current_dir(&cwd).is_ok());
+
+    // Ensure test results were successful
+    assert!(result.is_ok());
+  }
+
+  fn is_bazel_supported_platform_for_test(target: &str) -> PlatformSupport {
+    is_bazel_supported_platform(target, &[])
+  }
+
+  fn generate_bazel_conditions_for_test(triples: &Vec<String>) -> Result<Vec<String>> {
+    generate_bazel_conditions("io_bazel_rules_rust", triples, &[])
+  }
+
   #[test]
   fn detect_bazel_platforms() {
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(not(fuchsia))".to_string()),
-      (true, true)
+      is_bazel_supported_platform_for_test("cfg(not(fuchsia))"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: true,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(not(target_os = \"redox\"))"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: true,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(unix)"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(not(windows))"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(target = \"x86_64-apple-darwin\")"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("x86_64-apple-darwin"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("unknown-unknown-unknown"),
+      PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(foo)"),
+      PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(target_os = \"redox\")"),
+      PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      }
+    );
+  }
+
+  #[test]
+  fn detect_wasm_target_family() {
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(target_family = \"wasm\")"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(not(target_family = \"wasm\"))"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      get_matching_bazel_triples("cfg(target_family=\"wasm\")", &[]).unwrap(),
+      vec!["wasm32-unknown-unknown"]
+    );
+    assert_eq!(
+      get_matching_bazel_triples("cfg(any(unix, target_family = \"wasm\"))", &[])
+        .unwrap()
+        .len(),
+      get_matching_bazel_triples("cfg(unix)", &[]).unwrap().len() + 1
+    );
+  }
+
+  #[test]
+  fn cfg_options_unknown_to_cfg_expr_match_no_platform() {
+    for target in &[
+      "cfg(target_has_atomic = \"64\")",
+      "cfg(target_abi = \"eabihf\")",
+      "cfg(all(unix, target_thread_local))",
+    ] {
+      assert_eq!(
+        get_matching_bazel_triples(target, &[]).unwrap(),
+        Vec::<String>::new()
+      );
+    }
+
+    // Only whole options are rewritten, and the values are left untouched
+    assert_eq!(
+      rewrite_unparsed_cfg_options("cfg(all(target_abi = \"target_abi\", my_target_abi))"),
+      "cfg(all(raze_target_abi = \"target_abi\", my_target_abi))"
+    );
+    assert_eq!(
+      get_matching_bazel_triples("cfg(not(target_has_atomic = \"ptr\"))", &[])
+        .unwrap()
+        .len(),
+      get_matching_bazel_triples("cfg(not(loom))", &[])
+        .unwrap()
+        .len()
+    );
+  }
+
+  #[test]
+  fn match_compound_cfg_expressions() {
+    let test_cases: Vec<(&str, Vec<&str>)> = vec![
+      (
+        "cfg(all(unix, target_arch = \"x86_64\"))",
+        vec![
+          "x86_64-apple-darwin",
+          "x86_64-apple-ios",
+          "x86_64-linux-android",
+          "x86_64-unknown-freebsd",
+          "x86_64-unknown-linux-gnu",
+        ],
+      ),
+      (
+        "cfg(all(target_os = \"linux\", target_env = \"gnu\", target_arch = \"aarch64\"))",
+        vec!["aarch64-unknown-linux-gnu"],
+      ),
+      (
+        "cfg(all(target_vendor = \"apple\", target_arch = \"x86\"))",
+        vec!["i686-apple-darwin"],
+      ),
+      (
+        "cfg(any(target_os = \"freebsd\", target_arch = \"s390x\"))",
+        vec![
+          "i686-unknown-freebsd",
+          "s390x-unknown-linux-gnu",
+          "x86_64-unknown-freebsd",
+        ],
+      ),
+      (
+        "cfg(any(target = \"x86_64-apple-darwin\", target_family = \"windows\"))",
+        vec![
+          "i686-pc-windows-gnu",
+          "x86_64-apple-darwin",
+          "x86_64-pc-windows-gnu",
+        ],
+      ),
+      (
+        "cfg(all(target_os = \"android\", not(target_arch = \"aarch64\")))",
+        vec!["i686-linux-android", "x86_64-linux-android"],
+      ),
+      (
+        "cfg(not(any(unix, target_family = \"windows\")))",
+        vec!["wasm32-unknown-unknown"],
+      ),
+      ("cfg(all(windows, not(target_env = \"gnu\")))", vec![]),
+      ("x86_64-apple-darwin", vec!["x86_64-apple-darwin"]),
+    ];
+
+    for (cfg, expected_triples) in test_cases {
+      let mut triples = get_matching_bazel_triples(cfg, &[]).unwrap();
+      triples.sort();
+      assert_eq!(triples, expected_triples, "unexpected triples for {}", cfg);
+    }
+  }
+
+  #[test]
+  fn target_vendor_cfgs_match_the_vendor_of_the_triple() {
+    let mut apple_triples =
+      get_matching_bazel_triples("cfg(target_vendor = \"apple\")", &[]).unwrap();
+    apple_triples.sort();
+    assert_eq!(
+      apple_triples,
+      vec![
+        "aarch64-apple-ios",
+        "i686-apple-darwin",
+        "x86_64-apple-darwin",
+        "x86_64-apple-ios",
+      ]
+    );
+    assert_eq!(
+      is_bazel_supported_platform_for_test("cfg(target_vendor = \"apple\")"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
+    );
+
+    let mut other_triples =
+      get_matching_bazel_triples("cfg(not(target_vendor = \"apple\"))", &[]).unwrap();
+    other_triples.sort();
+    assert!(!other_triples.is_empty());
+    assert!(other_triples
+      .iter()
+      .all(|triple| !triple.contains("-apple-")));
+    assert_eq!(
+      apple_triples.len() + other_triples.len(),
+      SUPPORTED_PLATFORM_TRIPLES.len()
+    );
+  }
+
+  #[test]
+  fn target_env_cfgs_match_the_environment_of_the_triple() {
+    let supported = vec![
+      "x86_64-unknown-linux-gnu".to_owned(),
+      "x86_64-unknown-linux-musl".to_owned(),
+      "x86_64-apple-darwin".to_owned(),
+    ];
+
+    assert_eq!(
+      get_matching_bazel_triples("cfg(target_env = \"musl\")", &supported).unwrap(),
+      vec!["x86_64-unknown-linux-musl"]
+    );
+    assert_eq!(
+      get_matching_bazel_triples("cfg(target_env = \"gnu\")", &supported).unwrap(),
+      vec!["x86_64-unknown-linux-gnu"]
+    );
+    assert_eq!(
+      get_matching_bazel_triples(
+        "cfg(all(target_os = \"linux\", not(target_env = \"musl\")))",
+        &supported
+      )
+      .unwrap(),
+      vec!["x86_64-unknown-linux-gnu"]
+    );
+
+    // Without a musl triple among the supported ones, musl specific dependencies are dropped
+    assert!(
+      get_matching_bazel_triples("cfg(target_env = \"musl\")", &[])
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn mobile_triples_are_matched_by_target_arch() {
+    let supported = vec![
+      "aarch64-apple-ios".to_owned(),
+      "aarch64-linux-android".to_owned(),
+      "armv7-apple-ios".to_owned(),
+      "armv7-linux-androideabi".to_owned(),
+      "i686-linux-android".to_owned(),
+      "x86_64-apple-ios".to_owned(),
+    ];
+    let matching_triples = |cfg: &str| {
+      let mut triples = get_matching_bazel_triples(cfg, &supported).unwrap();
+      triples.sort();
+      triples
+    };
+
+    let android_arm = matching_triples("cfg(all(target_os = \"android\", target_arch = \"arm\"))");
+    let android_aarch64 =
+      matching_triples("cfg(all(target_os = \"android\", target_arch = \"aarch64\"))");
+    assert_eq!(android_arm, vec!["armv7-linux-androideabi"]);
+    assert_eq!(android_aarch64, vec!["aarch64-linux-android"]);
+
+    assert_eq!(
+      matching_triples("cfg(all(target_os = \"ios\", target_arch = \"arm\"))"),
+      vec!["armv7-apple-ios"]
     );
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(not(target_os = \"redox\"))".to_string()),
-      (true, true)
+      matching_triples("cfg(all(target_os = \"ios\", not(target_arch = \"arm\")))"),
+      vec!["aarch64-apple-ios", "x86_64-apple-ios"]
     );
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(unix)".to_string()),
-      (true, false)
+      matching_triples("cfg(any(target_os = \"android\", target_os = \"ios\"))").len(),
+      supported.len()
+    );
+  }
+
+  #[test]
+  fn bare_metal_cfgs_only_match_bare_metal_triples() {
+    assert_eq!(
+      is_bazel_supported_platform("cfg(target_os = \"none\")", &[]),
+      PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      }
     );
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(not(windows))".to_string()),
-      (true, false)
+      is_bazel_supported_platform("cfg(not(target_os = \"none\"))", &[]),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: true,
+      }
     );
+
+    let supported = vec![
+      "thumbv7em-none-eabi".to_owned(),
+      "x86_64-unknown-linux-gnu".to_owned(),
+    ];
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(target = \"x86_64-apple-darwin\")".to_string()),
-      (true, false)
+      get_matching_bazel_triples("cfg(target_os = \"none\")", &supported).unwrap(),
+      vec!["thumbv7em-none-eabi"]
     );
     assert_eq!(
-      is_bazel_supported_platform(&"x86_64-apple-darwin".to_string()),
-      (true, false)
+      get_matching_bazel_triples("cfg(not(target_os = \"none\"))", &supported).unwrap(),
+      vec!["x86_64-unknown-linux-gnu"]
     );
+  }
+
+  #[test]
+  fn triples_are_matched_by_target_pointer_width() {
+    let supported = vec![
+      "aarch64-unknown-linux-gnu".to_owned(),
+      "armv7-linux-androideabi".to_owned(),
+      "i686-unknown-linux-gnu".to_owned(),
+      "wasm32-unknown-unknown".to_owned(),
+      "x86_64-unknown-linux-gnu".to_owned(),
+    ];
+
+    let mut triples =
+      get_matching_bazel_triples("cfg(target_pointer_width = \"64\")", &supported).unwrap();
+    triples.sort();
     assert_eq!(
-      is_bazel_supported_platform(&"unknown-unknown-unknown".to_string()),
-      (false, false)
+      triples,
+      vec!["aarch64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"]
     );
+
+    let mut triples =
+      get_matching_bazel_triples("cfg(target_pointer_width = \"32\")", &supported).unwrap();
+    triples.sort();
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(foo)".to_string()),
-      (false, false)
+      triples,
+      vec![
+        "armv7-linux-androideabi",
+        "i686-unknown-linux-gnu",
+        "wasm32-unknown-unknown"
+      ]
     );
+
     assert_eq!(
-      is_bazel_supported_platform(&"cfg(target_os = \"redox\")".to_string()),
-      (false, false)
+      is_bazel_supported_platform_for_test("cfg(target_pointer_width = \"64\")"),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: false,
+      }
     );
   }
 
+  #[test]
+  fn platform_support_converts_to_tuple() {
+    let support: (bool, bool) = PlatformSupport {
+      is_supported: true,
+      matches_all: false,
+    }
+    .into();
+    assert_eq!(support, (true, false));
+  }
+
   #[test]
   fn all_supported_platform_triples_unwrap() {
     for triple in SUPPORTED_PLATFORM_TRIPLES.iter() {
@@ -889,10 +4059,110 @@ mod tests {
     }
   }
 
+  #[test]
+  fn filtering_triples_dedups_the_result() {
+    let mut triples = vec![
+      "x86_64-unknown-linux-gnu".to_owned(),
+      "x86_64-apple-darwin".to_owned(),
+      "x86_64-unknown-linux-gnu".to_owned(),
+      "wasm32-unknown-unknown".to_owned(),
+    ];
+    filter_bazel_triples(
+      &mut triples,
+      &[
+        "x86_64-unknown-linux-gnu".to_owned(),
+        "x86_64-apple-darwin".to_owned(),
+        "x86_64-unknown-linux-gnu".to_owned(),
+      ],
+    )
+    .unwrap();
+
+    assert_eq!(
+      triples,
+      vec!["x86_64-apple-darwin", "x86_64-unknown-linux-gnu"]
+    );
+  }
+
+  #[test]
+  fn filtering_triples_rejects_unknown_whitelist_entries() {
+    let mut triples = vec!["x86_64-unknown-linux-gnu".to_owned()];
+    let err = filter_bazel_triples(
+      &mut triples,
+      &[
+        "x86_64-unknown-linux-gnu".to_owned(),
+        "x86_64-unknown-linux-gnuu".to_owned(),
+      ],
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("raze.targets"));
+    assert!(err.to_string().contains("x86_64-unknown-linux-gnuu"));
+  }
+
+  #[test]
+  fn detect_bazel_platforms_with_custom_triples() {
+    let supported_platform_triples = vec!["aarch64-pc-windows-msvc".to_string()];
+
+    assert_eq!(
+      is_bazel_supported_platform("cfg(windows)", &supported_platform_triples),
+      PlatformSupport {
+        is_supported: true,
+        matches_all: true,
+      }
+    );
+    assert_eq!(
+      is_bazel_supported_platform("cfg(unix)", &supported_platform_triples),
+      PlatformSupport {
+        is_supported: false,
+        matches_all: false,
+      }
+    );
+    assert_eq!(
+      get_matching_bazel_triples("cfg(windows)", &supported_platform_triples).unwrap(),
+      vec!["aarch64-pc-windows-msvc"]
+    );
+  }
+
+  #[test]
+  fn generate_condition_strings_with_custom_triples() {
+    let supported_platform_triples = vec!["aarch64-pc-windows-msvc".to_string()];
+
+    assert_eq!(
+      generate_bazel_conditions(
+        "io_bazel_rules_rust",
+        &["aarch64-pc-windows-msvc".to_string()],
+        &supported_platform_triples
+      )
+      .unwrap(),
+      vec!["@io_bazel_rules_rust//rust/platform:aarch64-pc-windows-msvc"]
+    );
+
+    // Real triples which are not among the supported ones are rejected
+    assert!(generate_bazel_conditions(
+      "io_bazel_rules_rust",
+      &["x86_64-unknown-linux-gnu".to_string()],
+      &supported_platform_triples
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn generate_condition_strings_with_custom_rules_rust_repository() {
+    assert_eq!(
+      generate_bazel_conditions(
+        "rules_rust",
+        &["aarch64-unknown-linux-gnu".to_string()],
+        &[]
+      )
+      .unwrap(),
+      vec!["@rules_rust//rust/platform:aarch64-unknown-linux-gnu"]
+    );
+  }
+
   #[test]
   fn generate_condition_strings() {
     assert_eq!(
-      generate_bazel_conditions(&vec![
+      generate_bazel_conditions_for_test(&vec![
         "aarch64-unknown-linux-gnu".to_string(),
         "aarch64-apple-ios".to_string(),
       ])
@@ -904,19 +4174,19 @@ mod tests {
     );
 
     assert_eq!(
-      generate_bazel_conditions(&vec!["aarch64-unknown-linux-gnu".to_string()]).unwrap(),
+      generate_bazel_conditions_for_test(&vec!["aarch64-unknown-linux-gnu".to_string()]).unwrap(),
       vec!["@io_bazel_rules_rust//rust/platform:aarch64-unknown-linux-gnu"]
     );
 
-    assert!(generate_bazel_conditions(&vec![
+    assert!(generate_bazel_conditions_for_test(&vec![
       "aarch64-unknown-linux-gnu".to_string(),
       "unknown-unknown-unknown".to_string(),
     ])
     .is_err());
 
-    assert!(generate_bazel_conditions(&vec!["unknown-unknown-unknown".to_string()]).is_err());
+    assert!(generate_bazel_conditions_for_test(&vec!["unknown-unknown-unknown".to_string()]).is_err());
 
-    assert!(generate_bazel_conditions(&vec![
+    assert!(generate_bazel_conditions_for_test(&vec![
       "foo".to_string(),
       "bar".to_string(),
       "baz".to_string()
@@ -13,21 +13,24 @@
 // limitations under the License.
 
 use std::{
-  fs::{self, File},
-  io::Write,
-  path::PathBuf,
+  fs,
+  path::{Path, PathBuf},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use docopt::Docopt;
 
 use cargo_raze::{
-  bazel::{find_workspace_root, BazelRenderer},
+  bazel::find_workspace_root,
+  lockfile::{verify_lockfile, LOCKFILE_NAME},
   metadata::{CargoMetadataFetcher, CargoWorkspaceFiles, MetadataFetcher},
   planning::{BuildPlanner, BuildPlannerImpl},
-  rendering::{BuildRenderer, FileOutputs, RenderDetails},
-  settings::{load_settings, GenMode},
+  rendering::{
+    check_path_dependency_outputs, render_file_outputs, serialize_file_outputs, write_file_outputs,
+    FileOutputs, RenderDetails, RendererRegistry,
+  },
+  settings::load_settings,
   util::PlatformDetails,
 };
 
@@ -42,6 +45,9 @@ struct Options {
   flag_color: Option<String>,
   flag_target: Option<String>,
   flag_dryrun: Option<bool>,
+  flag_output_to_stdout: Option<bool>,
+  flag_locked: Option<bool>,
+  flag_export_plan: Option<String>,
   flag_cargo_bin_path: Option<String>,
   flag_output: String,
 }
@@ -51,9 +57,8 @@ Generate BUILD files for your pre-vendored Cargo dependencies.
 
 Usage:
     cargo raze (-h | --help)
-    cargo raze [--verbose] [--quiet] [--color=<WHEN>] [--dryrun] [--cargo-bin-path=<PATH>] [--output=<PATH>]
-    cargo raze <buildprefix> [--verbose] [--quiet] [--color=<WHEN>] [--dryrun] [--cargo-bin-path=<PATH>]
-                             [--output=<PATH>]
+    cargo raze [--verbose] [--quiet] [--color=<WHEN>] [--dryrun] [--output-to-stdout] [--locked] [--export-plan=<PATH>] [--cargo-bin-path=<PATH>] [--output=<PATH>]
+    cargo raze <buildprefix> [--verbose] [--quiet] [--color=<WHEN>] [--dryrun] [--output-to-stdout] [--locked] [--export-plan=<PATH>] [--cargo-bin-path=<PATH>] [--output=<PATH>]
 
 Options:
     -h, --help                         Print this message
@@ -61,6 +66,9 @@ Options:
     -q, --quiet                        No output printed to stdout
     --color=<WHEN>                     Coloring: auto, always, never
     -d, --dryrun                       Do not emit any files
+    --output-to-stdout                 Print every file to stdout in a stable format, instead of writing them
+    --locked                           Fail if the crates drifted from those in cargo-raze.lock
+    --export-plan=<PATH>               Write the planned build as JSON to a path, without rendering
     --cargo-bin-path=<PATH>            Path to the cargo binary to be used for loading workspace metadata
     --output=<PATH>                    Path to output the generated into.
 "#;
@@ -70,8 +78,13 @@ fn main() -> Result<()> {
     .and_then(|d| d.deserialize())
     .unwrap_or_else(|e| e.exit());
 
+  let output_to_stdout = options.flag_output_to_stdout.unwrap_or(false);
+
   let settings = load_settings("Cargo.toml")?;
-  println!("Loaded override settings: {:#?}", settings);
+  // Stdout is reserved for the generated files when they are output there
+  if !output_to_stdout {
+    println!("Loaded override settings: {:#?}", settings);
+  }
 
   let mut metadata_fetcher: Box<dyn MetadataFetcher> = match options.flag_cargo_bin_path {
     Some(ref p) => Box::new(CargoMetadataFetcher::new(p)),
@@ -94,7 +107,16 @@ fn main() -> Result<()> {
   };
 
   let planned_build = planner.plan_build(&settings, files, platform_details)?;
-  let mut bazel_renderer = BazelRenderer::new();
+  for warning in &planned_build.warnings {
+    eprintln!("Warning: {}", warning);
+  }
+
+  if let Some(export_path) = &options.flag_export_plan {
+    fs::write(export_path, planned_build.to_json()?)?;
+    return Ok(());
+  }
+
+  let mut bazel_renderer = RendererRegistry::default().create(settings.renderer.registry_name())?;
 
   // Default to the current directory '.'
   let mut prefix_path: PathBuf = PathBuf::new();
@@ -122,43 +144,43 @@ fn main() -> Result<()> {
 
   let render_details = RenderDetails {
     path_prefix: prefix_path.display().to_string(),
-    buildfile_suffix: settings.output_buildfile_suffix,
+    buildfile_suffix: settings.output_buildfile_suffix.clone(),
+    legacy_buildfile_stubs: settings.output_legacy_buildfile_stubs,
+    fetch_mode: settings.fetch_mode,
+    aliases_package: settings.output_aliases_package.clone(),
+    normalize_line_endings: settings.output_normalize_line_endings,
   };
 
-  let dry_run = options.flag_dryrun.unwrap_or(false);
-  if !dry_run {
-    fs::create_dir_all(&render_details.path_prefix)?;
+  let bazel_file_outputs = render_file_outputs(
+    &mut *bazel_renderer,
+    &settings,
+    &render_details,
+    &planned_build,
+  )?;
+
+  if options.flag_locked.unwrap_or(false) {
+    let lockfile_output = bazel_file_outputs
+      .iter()
+      .find(|output| output.path.ends_with(LOCKFILE_NAME))
+      .ok_or_else(|| anyhow!("--locked requires the `raze.gen_lockfile` setting to be enabled"))?;
+    verify_lockfile(lockfile_output)?;
   }
 
-  let bazel_file_outputs = match settings.genmode {
-    GenMode::Vendored => bazel_renderer.render_planned_build(&render_details, &planned_build)?,
-    GenMode::Remote => {
-      if !dry_run {
-        // Create "remote/" if it doesn't exist
-        fs::create_dir_all(render_details.path_prefix.clone() + "/remote/")?;
-      }
-
-      bazel_renderer.render_remote_planned_build(&render_details, &planned_build)?
-    }, /* exhaustive, we control the definition */
-  };
-
-  for FileOutputs {
-    path,
-    contents,
-  } in bazel_file_outputs
-  {
-    if dry_run {
+  if output_to_stdout {
+    print!("{}", serialize_file_outputs(&bazel_file_outputs));
+  } else if options.flag_dryrun.unwrap_or(false) {
+    for FileOutputs {
+      path,
+      contents,
+    } in bazel_file_outputs
+    {
       println!("{}:\n{}", path, contents);
-    } else {
-      write_to_file_loudly(&path, &contents)?;
     }
+  } else {
+    // The paths of path dependencies are relative to the directory of `toml_path`
+    check_path_dependency_outputs(&render_details, &planned_build, Path::new("."))?;
+    write_file_outputs(&bazel_file_outputs)?;
   }
 
   Ok(())
 }
-
-fn write_to_file_loudly(path: &str, contents: &str) -> Result<()> {
-  File::create(&path).and_then(|mut f| f.write_all(contents.as_bytes()))?;
-  println!("Generated {} successfully", path);
-  Ok(())
-}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::settings::CrateSettings;
+use std::collections::BTreeMap;
+
+use crate::settings::{AliasNameStyle, CrateSettings};
 use serde_derive::Serialize;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
@@ -51,6 +53,9 @@ pub struct Metadep {
 pub struct LicenseData {
   pub name: String,
   pub rating: String,
+  // Whether the rating was assumed, as the license is not known to cargo-raze
+  #[serde(skip)]
+  pub is_unknown: bool,
 }
 
 impl Default for LicenseData {
@@ -58,6 +63,7 @@ impl Default for LicenseData {
     LicenseData {
       name: "no license".into(),
       rating: "restricted".into(),
+      is_unknown: false,
     }
   }
 }
@@ -68,9 +74,19 @@ pub struct GitRepo {
   pub commit: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PathSource {
+  /**
+   * The path in Bazel's format (i.e. with forward slashes) to the crate's directory, relative to
+   * the directory containing the root Cargo.toml.
+   */
+  pub path: String,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct SourceDetails {
   pub git_data: Option<GitRepo>,
+  pub path_data: Option<PathSource>,
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -82,6 +98,57 @@ pub struct CrateDependencyContext {
   pub dev_dependencies: Vec<BuildableDependency>,
   pub aliased_dependencies: Vec<DependencyAlias>,
 }
+
+/** The Bazel labels of each category of dependencies, in the order of the dependencies. */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DependencyLabels {
+  pub dependencies: Vec<String>,
+  pub proc_macro_dependencies: Vec<String>,
+  pub build_dependencies: Vec<String>,
+  pub build_proc_macro_dependencies: Vec<String>,
+  pub dev_dependencies: Vec<String>,
+}
+
+impl CrateDependencyContext {
+  /** The fully qualified Bazel label of every dependency, by category. */
+  pub fn rendered_labels(&self) -> DependencyLabels {
+    let labels = |deps: &[BuildableDependency]| {
+      deps
+        .iter()
+        .map(|dep| dep.buildable_target.clone())
+        .collect()
+    };
+
+    DependencyLabels {
+      dependencies: labels(&self.dependencies),
+      proc_macro_dependencies: labels(&self.proc_macro_dependencies),
+      build_dependencies: labels(&self.build_dependencies),
+      build_proc_macro_dependencies: labels(&self.build_proc_macro_dependencies),
+      dev_dependencies: labels(&self.dev_dependencies),
+    }
+  }
+
+  /**
+   * Sorts every dependency list by Bazel label.
+   *
+   * The sort is stable, so entries sharing a label keep their relative order.
+   */
+  pub fn sort_by_label(&mut self) {
+    for deps in [
+      &mut self.dependencies,
+      &mut self.proc_macro_dependencies,
+      &mut self.build_dependencies,
+      &mut self.build_proc_macro_dependencies,
+      &mut self.dev_dependencies,
+    ] {
+      deps.sort_by(|a, b| a.buildable_target.cmp(&b.buildable_target));
+    }
+    self
+      .aliased_dependencies
+      .sort_by(|a, b| a.target.cmp(&b.target));
+  }
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CrateTargetedDepContext {
   pub target: String,
@@ -89,6 +156,20 @@ pub struct CrateTargetedDepContext {
   pub conditions: Vec<String>,
 }
 
+/** Features enabled on top of the crate's common features, only on some platforms. */
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CrateTargetedFeatureContext {
+  pub features: Vec<String>,
+  pub conditions: Vec<String>,
+}
+
+/** An optional feature which is toggled by a flag, along with the features it enables. */
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CrateSelectableFeatureContext {
+  pub name: String,
+  pub features: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CrateContext {
   pub pkg_name: String,
@@ -97,8 +178,23 @@ pub struct CrateContext {
   pub raze_settings: CrateSettings,
   pub default_deps: CrateDependencyContext,
   pub targeted_deps: Vec<CrateTargetedDepContext>,
+  // The normal dependencies of the targets given `additional_target_deps`, by target kind and name
+  // such as "bin:{name}".
+  //
+  // These merge the default dependencies with those of the target, so that they sort together.
+  // Other targets get the default dependencies alone.
+  pub target_dependencies: BTreeMap<String, Vec<BuildableDependency>>,
   pub license: LicenseData,
+
+  // The features enabled on every targeted platform, which every target is built with.
+  //
+  // Cargo resolves a single set of features per crate, so this is the union of the features its
+  // dependents enable, rather than one set per dependent.
   pub features: Vec<String>,
+  pub targeted_features: Vec<CrateTargetedFeatureContext>,
+  // The optional features left out by the resolution, which can be toggled by flags. Empty unless
+  // the crate's `selectable_features` setting is enabled.
+  pub selectable_features: Vec<CrateSelectableFeatureContext>,
   pub workspace_path_to_crate: String,
   pub is_root_dependency: bool,
   pub targets: Vec<BuildableTarget>,
@@ -115,10 +211,23 @@ pub struct CrateContext {
   pub expected_build_path: String,
 
   // The name of the main lib target for this crate (if present).
-  // Currently only one such lib can exist per crate.
+  // Other library targets of the crate are rendered under their own names.
   pub lib_target_name: Option<String>,
 }
 
+impl CrateContext {
+  /** Sorts the default, per target and every targeted dependency list by Bazel label. */
+  pub fn sort_dependencies_by_label(&mut self) {
+    self.default_deps.sort_by_label();
+    for deps in self.target_dependencies.values_mut() {
+      deps.sort_by(|a, b| a.buildable_target.cmp(&b.buildable_target));
+    }
+    for targeted_deps in self.targeted_deps.iter_mut() {
+      targeted_deps.deps.sort_by_label();
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct WorkspaceContext {
   // The bazel path prefix to the vendor directory
@@ -134,4 +243,76 @@ pub struct WorkspaceContext {
   // Bare files will just be named after this setting. Named files, such as those passed to
   // repository rules, will take the form of $prefix.$this_value.
   pub output_buildfile_suffix: String,
+
+  // The name of the Bazel repository the rust rules are loaded from, without the leading "@".
+  pub rules_rust_repository_name: String,
+
+  // The tags of every generated target.
+  pub default_tags: Vec<String>,
+
+  // The notice at the top of every generated file.
+  pub generated_file_header: String,
+
+  // How the aliases of the root dependencies are named.
+  pub alias_name_style: AliasNameStyle,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dependency(name: &str, buildable_target: &str) -> BuildableDependency {
+    BuildableDependency {
+      name: name.to_owned(),
+      version: "1.0.0".to_owned(),
+      buildable_target: buildable_target.to_owned(),
+      is_proc_macro: false,
+    }
+  }
+
+  #[test]
+  fn rendered_labels_are_split_by_dependency_category() {
+    let deps = CrateDependencyContext {
+      dependencies: vec![
+        dependency("log", "//vendor/log-1.0.0:log"),
+        dependency("rand", "@raze__rand__1_0_0//:rand"),
+      ],
+      proc_macro_dependencies: vec![dependency(
+        "serde_derive",
+        "@raze__serde_derive__1_0_0//:serde_derive",
+      )],
+      build_dependencies: vec![dependency("cc", "//vendor/cc-1.0.0:cc")],
+      build_proc_macro_dependencies: Vec::new(),
+      dev_dependencies: vec![dependency("tempfile", "//vendor/tempfile-1.0.0:tempfile")],
+      aliased_dependencies: Vec::new(),
+    };
+
+    let labels = deps.rendered_labels();
+    assert_eq!(
+      labels.dependencies,
+      vec!["//vendor/log-1.0.0:log", "@raze__rand__1_0_0//:rand"]
+    );
+    assert_eq!(
+      labels.proc_macro_dependencies,
+      vec!["@raze__serde_derive__1_0_0//:serde_derive"]
+    );
+    assert_eq!(labels.build_dependencies, vec!["//vendor/cc-1.0.0:cc"]);
+    assert!(labels.build_proc_macro_dependencies.is_empty());
+    assert_eq!(
+      labels.dev_dependencies,
+      vec!["//vendor/tempfile-1.0.0:tempfile"]
+    );
+
+    // Every label is absolute, and names its target explicitly
+    for label in labels
+      .dependencies
+      .iter()
+      .chain(&labels.proc_macro_dependencies)
+      .chain(&labels.build_dependencies)
+      .chain(&labels.dev_dependencies)
+    {
+      assert!(label.starts_with("//") || label.starts_with('@'));
+      assert!(label.contains(':'));
+    }
+  }
 }
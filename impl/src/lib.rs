@@ -12,11 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[macro_use]
+extern crate lazy_static;
+
 pub mod bazel;
 pub mod context;
 pub mod license;
+pub mod lockfile;
 pub mod metadata;
 pub mod planning;
 pub mod rendering;
 pub mod settings;
+pub mod starlark;
 pub mod util;
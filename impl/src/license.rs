@@ -28,6 +28,8 @@ use spdx::{
 pub enum BazelLicenseType {
   Unencumbered,
   Notice,
+  // Not known to cargo-raze, assumed to be a notice license. Known licenses are preferred over it.
+  Unknown,
   Reciprocal,
   ByExceptionOnly,
   Restricted,
@@ -36,17 +38,71 @@ pub enum BazelLicenseType {
 }
 
 impl BazelLicenseType {
+  pub fn to_category(&self) -> LicenseCategory {
+    match self {
+      Self::Unencumbered => LicenseCategory::Unencumbered,
+      Self::Notice | Self::Unknown => LicenseCategory::Notice,
+      Self::Reciprocal => LicenseCategory::Reciprocal,
+      // N.B.: Bazel doesn't have a notion of "disallowed" or "by_exception_only", using restricted instead.
+      Self::Restricted | Self::ByExceptionOnly | Self::Disallowed => LicenseCategory::Restricted,
+    }
+  }
+
+  pub fn to_bazel_rating(&self) -> &'static str {
+    self.to_category().to_bazel_rating()
+  }
+}
+
+/**
+ * The license categories understood by Bazel's `licenses()` declarations.
+ *
+ * KEEP ORDERED: From the least to the most restrictive.
+ */
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Clone, Copy)]
+pub enum LicenseCategory {
+  Unencumbered,
+  Notice,
+  Reciprocal,
+  Restricted,
+}
+
+impl LicenseCategory {
   pub fn to_bazel_rating(&self) -> &'static str {
     match self {
       Self::Unencumbered => "unencumbered",
       Self::Notice => "notice",
       Self::Reciprocal => "reciprocal",
-      // N.B.: Bazel doesn't have a notion of "disallowed" or "by_exception_only", using restricted instead.
-      Self::Restricted | Self::ByExceptionOnly | Self::Disallowed => "restricted",
+      Self::Restricted => "restricted",
     }
   }
 }
 
+/**
+ * Classifies an SPDX license expression into the most restrictive category of its licenses.
+ *
+ * Unlike the rating of a crate's `licenses()`, which picks the least restrictive alternative of an
+ * `OR`, every license of the expression is considered. This suits audits which cannot assume which
+ * alternative was chosen. As when rendering, licenses unknown to cargo-raze are assumed to be
+ * notice licenses, and an empty expression is restricted.
+ */
+pub fn classify_license(spdx: &str) -> LicenseCategory {
+  if spdx.trim().is_empty() {
+    return LicenseCategory::Restricted;
+  }
+
+  let license_expression = match Expression::parse_mode(spdx, spdx::ParseMode::Lax) {
+    Ok(expression) => expression,
+    Err(_) => return BazelLicenseType::Unknown.to_category(),
+  };
+
+  license_expression
+    .requirements()
+    .filter_map(|requirement| requirement.req.license.id())
+    .map(|id| get_bazel_license_type(id.name).to_category())
+    .max()
+    .unwrap_or_else(|| BazelLicenseType::Unknown.to_category())
+}
+
 /**
  * A data structure for calculating a crate's license restrictions
  */
@@ -141,7 +197,8 @@ pub fn get_license_from_str(cargo_license_str: &str) -> LicenseData {
           "{} (Failed to parse as an SPDX license string)",
           cargo_license_str
         ),
-        rating: BazelLicenseType::Restricted.to_bazel_rating().into(),
+        rating: BazelLicenseType::Unknown.to_bazel_rating().into(),
+        is_unknown: true,
       };
     }
   };
@@ -179,6 +236,7 @@ pub fn get_license_from_str(cargo_license_str: &str) -> LicenseData {
   LicenseData {
     name: format!("{} from expression \"{}\"", crate_license.name, crate_license.expression),
     rating: crate_license.license.to_bazel_rating().into(),
+    is_unknown: crate_license.license == BazelLicenseType::Unknown,
   }
 }
 
@@ -553,7 +611,7 @@ fn get_bazel_license_type(license_str: &str) -> BazelLicenseType {
     "xinetd" => BazelLicenseType::Restricted,     /* unknown */
     "xpp" => BazelLicenseType::Restricted,        /* unknown */
     "zlib-acknowledgement" => BazelLicenseType::Restricted, /* unknown */
-    _ => BazelLicenseType::Restricted,
+    _ => BazelLicenseType::Unknown,
   }
 }
 
@@ -591,10 +649,50 @@ mod tests {
   }
 
   #[test]
-  fn unknown_licenses_are_restricted() {
+  fn unknown_licenses_are_assumed_to_be_notice() {
     let license = get_license_from_str("MIT5.0");
     assert_eq!(license.name, "MIT5.0 (Failed to parse as an SPDX license string)");
+    assert_eq!(license.rating, "notice");
+    assert!(license.is_unknown);
+
+    let license = get_license_from_str("BlueOak-1.0.0");
+    assert_eq!(license.name, "BlueOak-1.0.0 from expression \"BlueOak-1.0.0\"");
+    assert_eq!(license.rating, "notice");
+    assert!(license.is_unknown);
+  }
+
+  #[test]
+  fn known_licenses_are_preferred_over_unknown_ones() {
+    let license = get_license_from_str("BlueOak-1.0.0 OR MIT");
+    assert_eq!(license.name, "MIT from expression \"BlueOak-1.0.0 OR MIT\"");
+    assert!(!license.is_unknown);
+
+    let license = get_license_from_str("BlueOak-1.0.0 AND GPL-3.0");
     assert_eq!(license.rating, "restricted");
+    assert!(!license.is_unknown);
+
+    assert!(!get_license_from_str("MIT").is_unknown);
+  }
+
+  #[test]
+  fn licenses_are_classified_by_their_most_restrictive_license() {
+    let cases = vec![
+      ("MIT", LicenseCategory::Notice),
+      ("Apache-2.0", LicenseCategory::Notice),
+      ("GPL-3.0", LicenseCategory::Restricted),
+      ("MIT OR Apache-2.0", LicenseCategory::Notice),
+      ("MIT/Apache-2.0", LicenseCategory::Notice),
+      ("Unlicense", LicenseCategory::Unencumbered),
+      ("MPL-2.0", LicenseCategory::Reciprocal),
+      ("MIT OR GPL-3.0", LicenseCategory::Restricted),
+      ("Unlicense AND MIT", LicenseCategory::Notice),
+      ("BlueOak-1.0.0", LicenseCategory::Notice),
+      ("", LicenseCategory::Restricted),
+    ];
+
+    for (spdx, category) in cases {
+      assert_eq!(classify_license(spdx), category, "for \"{}\"", spdx);
+    }
   }
 
   #[test]
@@ -0,0 +1,243 @@
+// Copyright 2020 Google Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, path::Path};
+
+use anyhow::Result;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+  context::CrateContext,
+  planning::PlannedBuild,
+  rendering::{FileOutputs, RenderDetails},
+  util::RazeError,
+};
+
+/** The name of the lockfile written next to the generated files. */
+pub const LOCKFILE_NAME: &str = "cargo-raze.lock";
+
+const LOCKFILE_HEADER: &str = "# @generated\n# DO NOT EDIT! Replaced on runs of cargo-raze\n\n";
+
+/** What a run of cargo-raze resolved a single crate to. */
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LockedCrate {
+  pub name: String,
+  pub version: String,
+  // Where the crate is fetched from: a download URL, a git commit or a local path
+  pub source: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sha256: Option<String>,
+}
+
+impl From<&CrateContext> for LockedCrate {
+  fn from(package: &CrateContext) -> Self {
+    let source = match (
+      &package.source_details.git_data,
+      &package.source_details.path_data,
+    ) {
+      (Some(git_data), _) => format!("git+{}#{}", git_data.remote, git_data.commit),
+      (None, Some(path_data)) => format!("path+{}", path_data.path),
+      (None, None) => package.registry_url.clone(),
+    };
+
+    LockedCrate {
+      name: package.pkg_name.clone(),
+      version: package.pkg_version.clone(),
+      source,
+      sha256: package.sha256.clone(),
+    }
+  }
+}
+
+/**
+ * The crates a run of cargo-raze generated files for, used to detect drift between runs.
+ *
+ * The crates are kept sorted so that the serialized lockfile is deterministic.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RazeLockfile {
+  #[serde(rename = "crate", default, skip_serializing_if = "Vec::is_empty")]
+  pub crates: Vec<LockedCrate>,
+}
+
+impl RazeLockfile {
+  pub fn new(mut crates: Vec<LockedCrate>) -> Self {
+    crates.sort();
+    crates.dedup();
+    RazeLockfile {
+      crates,
+    }
+  }
+
+  pub fn from_planned_build(planned_build: &PlannedBuild) -> Self {
+    RazeLockfile::new(
+      planned_build
+        .crate_contexts
+        .iter()
+        .map(LockedCrate::from)
+        .collect(),
+    )
+  }
+
+  pub fn parse(contents: &str) -> Result<Self> {
+    Ok(RazeLockfile::new(
+      toml::from_str::<RazeLockfile>(contents)?.crates,
+    ))
+  }
+
+  pub fn to_toml_string(&self) -> Result<String> {
+    Ok(format!("{}{}", LOCKFILE_HEADER, toml::to_string(self)?))
+  }
+
+  /** Fails with a description of every difference from the `locked` crates, if any. */
+  pub fn verify(&self, locked: &RazeLockfile) -> Result<(), RazeError> {
+    let describe = |locked_crate: &LockedCrate| {
+      format!(
+        "{} {} from \"{}\" (sha256: {})",
+        locked_crate.name,
+        locked_crate.version,
+        locked_crate.source,
+        locked_crate.sha256.as_deref().unwrap_or("none")
+      )
+    };
+
+    let mut drifts = Vec::new();
+    for locked_crate in locked.crates.iter() {
+      if !self.crates.contains(locked_crate) {
+        drifts.push(format!("- {}", describe(locked_crate)));
+      }
+    }
+    for planned_crate in self.crates.iter() {
+      if !locked.crates.contains(planned_crate) {
+        drifts.push(format!("+ {}", describe(planned_crate)));
+      }
+    }
+
+    if drifts.is_empty() {
+      return Ok(());
+    }
+
+    Err(RazeError::Generic(format!(
+      "The planned crates drifted from the {}:\n{}",
+      LOCKFILE_NAME,
+      drifts.join("\n")
+    )))
+  }
+}
+
+/** Renders the lockfile of the planned build, to be written alongside the generated files. */
+pub fn render_lockfile(
+  render_details: &RenderDetails,
+  planned_build: &PlannedBuild,
+) -> Result<FileOutputs> {
+  Ok(FileOutputs {
+    path: format!("{}/{}", render_details.path_prefix, LOCKFILE_NAME),
+    contents: RazeLockfile::from_planned_build(planned_build).to_toml_string()?,
+  })
+}
+
+/**
+ * Verifies a rendered lockfile against the one previously written to the same path.
+ *
+ * A missing previous lockfile is an error, as there is nothing to verify the planned build against.
+ */
+pub fn verify_lockfile(lockfile_output: &FileOutputs) -> Result<()> {
+  let path = Path::new(&lockfile_output.path);
+  let locked_contents = fs::read_to_string(path).map_err(|err| {
+    RazeError::Generic(format!(
+      "Unable to read the lockfile at {}: {}",
+      path.display(),
+      err
+    ))
+  })?;
+
+  let locked = RazeLockfile::parse(&locked_contents)?;
+  RazeLockfile::parse(&lockfile_output.contents)?.verify(&locked)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn locked_crate(name: &str, version: &str, sha256: &str) -> LockedCrate {
+    LockedCrate {
+      name: name.to_owned(),
+      version: version.to_owned(),
+      source: format!(
+        "https://crates.io/api/v1/crates/{}/{}/download",
+        name, version
+      ),
+      sha256: Some(sha256.to_owned()),
+    }
+  }
+
+  #[test]
+  fn lockfile_round_trips_in_a_stable_order() {
+    let crates = vec![
+      locked_crate("zeta", "1.0.0", "abc"),
+      locked_crate("alpha", "0.2.0", "def"),
+      LockedCrate {
+        name: "local".to_owned(),
+        version: "0.1.0".to_owned(),
+        source: "path+local".to_owned(),
+        sha256: None,
+      },
+      locked_crate("alpha", "0.1.0", "123"),
+    ];
+
+    let lockfile = RazeLockfile::new(crates.clone());
+    let mut reversed_crates = crates;
+    reversed_crates.reverse();
+    let contents = lockfile.to_toml_string().unwrap();
+    assert_eq!(
+      RazeLockfile::new(reversed_crates).to_toml_string().unwrap(),
+      contents
+    );
+
+    let names = lockfile
+      .crates
+      .iter()
+      .map(|locked_crate| format!("{}-{}", locked_crate.name, locked_crate.version))
+      .collect::<Vec<String>>();
+    assert_eq!(
+      names,
+      vec!["alpha-0.1.0", "alpha-0.2.0", "local-0.1.0", "zeta-1.0.0"]
+    );
+
+    assert!(contents.starts_with(LOCKFILE_HEADER));
+    assert_eq!(RazeLockfile::parse(&contents).unwrap(), lockfile);
+  }
+
+  #[test]
+  fn verifying_a_lockfile_reports_drift() {
+    let locked = RazeLockfile::new(vec![
+      locked_crate("alpha", "0.1.0", "123"),
+      locked_crate("zeta", "1.0.0", "abc"),
+    ]);
+    assert!(locked.verify(&locked).is_ok());
+
+    let drifted = RazeLockfile::new(vec![
+      locked_crate("alpha", "0.1.0", "456"),
+      locked_crate("zeta", "1.0.0", "abc"),
+    ]);
+    let message = drifted.verify(&locked).unwrap_err().to_string();
+    assert!(message.contains("- alpha 0.1.0"));
+    assert!(message.contains("(sha256: 123)"));
+    assert!(message.contains("+ alpha 0.1.0"));
+    assert!(message.contains("(sha256: 456)"));
+    assert!(!message.contains("zeta"));
+  }
+}
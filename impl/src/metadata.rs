@@ -12,16 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fs, path::PathBuf};
+use std::{
+  collections::{BTreeSet, HashMap},
+  env, fs,
+  path::{Path, PathBuf},
+  process::Command,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+use cargo_lock::SourceId;
 
 use cargo_metadata::MetadataCommand;
 pub use cargo_metadata::{DependencyKind, Metadata, Node, Package, PackageId};
 
 use tempfile::TempDir;
 
+use toml::{value::Table, Value};
+
 const SYSTEM_CARGO_BIN_PATH: &str = "cargo";
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
 
 /**
  * An entity that can retrive deserialized metadata for a Cargo Workspace.
@@ -31,8 +41,25 @@ const SYSTEM_CARGO_BIN_PATH: &str = "cargo";
  */
 pub trait MetadataFetcher {
   fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata>;
+
+  /**
+   * Resolves the features enabled on each package when building for the given platform triple.
+   *
+   * Fetchers that cannot resolve features per platform resolve none, which leaves the features
+   * resolved across all platforms in place.
+   */
+  fn fetch_platform_features(
+    &mut self,
+    _files: &CargoWorkspaceFiles,
+    _platform_triple: &str,
+  ) -> Result<PackageFeatures> {
+    Ok(PackageFeatures::new())
+  }
 }
 
+/** The features enabled on packages, keyed by package name and version. */
+pub type PackageFeatures = HashMap<(String, String), BTreeSet<String>>;
+
 /** The local Cargo workspace files to be used for build planning .*/
 pub struct CargoWorkspaceFiles {
   pub toml_path: PathBuf,
@@ -58,8 +85,12 @@ impl Default for CargoMetadataFetcher {
   }
 }
 
-impl MetadataFetcher for CargoMetadataFetcher {
-  fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+impl CargoMetadataFetcher {
+  /**
+   * Copies the workspace files into a temporary directory, from which Cargo subcommands can run
+   * without touching the original files.
+   */
+  fn copy_workspace_files(files: &CargoWorkspaceFiles) -> Result<TempDir> {
     assert!(files.toml_path.is_file());
     assert!(files.lock_path_opt.as_ref().map_or(true, |p| p.is_file()));
 
@@ -70,7 +101,15 @@ impl MetadataFetcher for CargoMetadataFetcher {
 
       let dir_path = dir.path();
       let new_toml_path = dir_path.join(files.toml_path.file_name().unwrap());
-      fs::copy(files.toml_path.as_path(), new_toml_path)?;
+      let manifest_dir = fs::canonicalize(&files.toml_path)?
+        .parent()
+        .unwrap()
+        .to_path_buf();
+      let toml_contents = fs::read_to_string(&files.toml_path)?;
+      fs::write(
+        new_toml_path,
+        absolutize_path_dependencies(&toml_contents, &manifest_dir)?,
+      )?;
       if let Some(lock_path) = files.lock_path_opt.as_ref() {
         let new_lock_path = dir_path.join(lock_path.file_name().unwrap());
         fs::copy(lock_path.as_path(), new_lock_path)?;
@@ -79,12 +118,188 @@ impl MetadataFetcher for CargoMetadataFetcher {
       dir
     };
 
+    Ok(cargo_tempdir)
+  }
+}
+
+impl MetadataFetcher for CargoMetadataFetcher {
+  fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+    let cargo_tempdir = CargoMetadataFetcher::copy_workspace_files(files)?;
+
     MetadataCommand::new()
       .cargo_path(&self.cargo_bin_path)
       .current_dir(cargo_tempdir.path())
       .exec()
       .map_err(|e| e.into())
   }
+
+  /**
+   * Resolves features with the Cargo Tree subcommand.
+   *
+   * Unlike the Cargo Metadata subcommand, which unifies features across all platforms, it runs
+   * Cargo's feature resolution for a single target.
+   */
+  fn fetch_platform_features(
+    &mut self,
+    files: &CargoWorkspaceFiles,
+    platform_triple: &str,
+  ) -> Result<PackageFeatures> {
+    let cargo_tempdir = CargoMetadataFetcher::copy_workspace_files(files)?;
+
+    let output = Command::new(&self.cargo_bin_path)
+      .current_dir(cargo_tempdir.path())
+      .args([
+        "tree",
+        "--target",
+        platform_triple,
+        "--edges",
+        "normal,build,dev",
+        "--prefix",
+        "none",
+        "--format",
+        "{p}|{f}",
+      ])
+      .output()?;
+    if !output.status.success() {
+      return Err(anyhow!(
+        "Failed to resolve the features of platform {}: {}",
+        platform_triple,
+        String::from_utf8_lossy(&output.stderr)
+      ));
+    }
+
+    Ok(parse_cargo_tree_features(&String::from_utf8_lossy(
+      &output.stdout,
+    )))
+  }
+}
+
+/**
+ * Parses the `{p}|{f}` formatted output of the Cargo Tree subcommand.
+ *
+ * Packages printed several times, such as those built for both the host and the target, are
+ * enabled the union of their features.
+ */
+fn parse_cargo_tree_features(tree_output: &str) -> PackageFeatures {
+  let mut package_features = PackageFeatures::new();
+  for line in tree_output.lines() {
+    let (package, features) = match line.split_once('|') {
+      Some(split) => split,
+      None => continue,
+    };
+    let mut package_words = package.split_whitespace();
+    let (name, version) = match (package_words.next(), package_words.next()) {
+      (Some(name), Some(version)) => (name, version.trim_start_matches('v')),
+      _ => continue,
+    };
+
+    package_features
+      .entry((name.to_owned(), version.to_owned()))
+      .or_default()
+      .extend(
+        features
+          .trim_end_matches("(*)")
+          .trim()
+          .split(',')
+          .filter(|feature| !feature.is_empty())
+          .map(str::to_owned),
+      );
+  }
+  package_features
+}
+
+/**
+ * Rewrites the relative paths of path dependencies in a Cargo.toml to absolute ones.
+ *
+ * Metadata is fetched from a copy of the Cargo.toml in a temporary directory, from which the
+ * relative paths of path dependencies would not resolve. The contents are returned unchanged if
+ * there is nothing to rewrite.
+ */
+fn absolutize_path_dependencies(toml_contents: &str, manifest_dir: &Path) -> Result<String> {
+  let mut manifest = toml::from_str::<Value>(toml_contents)?;
+  let mut rewritten = false;
+
+  if let Some(root) = manifest.as_table_mut() {
+    rewritten |= absolutize_dependency_tables(root, manifest_dir);
+    if let Some(targets) = root.get_mut("target").and_then(Value::as_table_mut) {
+      for target in targets.values_mut().filter_map(Value::as_table_mut) {
+        rewritten |= absolutize_dependency_tables(target, manifest_dir);
+      }
+    }
+  }
+
+  if !rewritten {
+    return Ok(toml_contents.to_owned());
+  }
+  Ok(toml::to_string(&manifest)?)
+}
+
+fn absolutize_dependency_tables(table: &mut Table, manifest_dir: &Path) -> bool {
+  let mut rewritten = false;
+  for (name, deps) in table.iter_mut() {
+    if !DEPENDENCY_TABLES.contains(&name.as_str()) {
+      continue;
+    }
+    for dep in deps
+      .as_table_mut()
+      .into_iter()
+      .flat_map(|deps| deps.values_mut())
+      .filter_map(Value::as_table_mut)
+    {
+      if let Some(Value::String(path)) = dep.get_mut("path") {
+        if Path::new(path.as_str()).is_relative() {
+          *path = manifest_dir.join(path.as_str()).display().to_string();
+          rewritten = true;
+        }
+      }
+    }
+  }
+  rewritten
+}
+
+/** Yields the Cargo home directory, in which Cargo caches downloaded crates. */
+pub fn cargo_home_dir() -> Option<PathBuf> {
+  env::var_os("CARGO_HOME")
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+}
+
+/**
+ * Finds the archive of the given crate in the download cache of a Cargo home directory.
+ *
+ * Cargo downloads the archives of all registry crates while fetching metadata, caching them per
+ * registry in `$CARGO_HOME/registry/cache/{host}-{hash}/{name}-{version}.crate`. The hash depends
+ * on the Cargo version, so the archive is looked up in every directory of the registry's host. If
+ * several of them hold an archive of the crate, the one of its registry cannot be told apart, and
+ * none is yielded.
+ */
+pub fn find_cached_crate_archive(
+  cargo_home: &Path,
+  source_id: &SourceId,
+  name: &str,
+  version: &str,
+) -> Option<PathBuf> {
+  let archive_name = format!("{}-{}.crate", name, version);
+  // crates.io is cached under the host of its git index, or of its sparse one
+  let registry_hosts = match source_id.is_default_registry() {
+    true => vec!["github.com", "index.crates.io"],
+    false => vec![source_id.url().host_str()?],
+  };
+
+  let mut archive_paths = fs::read_dir(cargo_home.join("registry").join("cache"))
+    .ok()?
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| {
+      let dir_name = entry.file_name().to_string_lossy().to_string();
+      matches!(dir_name.rsplit_once('-'), Some((host, _)) if registry_hosts.contains(&host))
+    })
+    .map(|entry| entry.path().join(&archive_name))
+    .filter(|path| path.is_file());
+
+  match (archive_paths.next(), archive_paths.next()) {
+    (Some(archive_path), None) => Some(archive_path),
+    _ => None,
+  }
 }
 
 #[cfg(test)]
@@ -115,6 +330,31 @@ dependencies = [
     "
   }
 
+  #[test]
+  fn test_parse_cargo_tree_features() {
+    let package_features = parse_cargo_tree_features(
+      "test v0.0.1 (/tmp/cargo)|\n\
+       local_dep v0.1.0 (/tmp/local_dep)|default,std\n\
+       serde v1.0.112|default,std (*)\n\
+       serde v1.0.112|derive\n",
+    );
+
+    let features_of = |name: &str, version: &str| {
+      package_features
+        .get(&(name.to_owned(), version.to_owned()))
+        .map(|features| features.iter().map(String::as_str).collect::<Vec<_>>())
+    };
+    assert_eq!(features_of("test", "0.0.1"), Some(Vec::new()));
+    assert_eq!(
+      features_of("local_dep", "0.1.0"),
+      Some(vec!["default", "std"])
+    );
+    assert_eq!(
+      features_of("serde", "1.0.112"),
+      Some(vec!["default", "derive", "std"])
+    );
+  }
+
   #[test]
   fn test_metadata_deserializes_correctly() {
     let metadata_file_contents = include_str!("../test_fixtures/metadata.txt");
@@ -162,6 +402,96 @@ dependencies = [
     fetcher.fetch_metadata(&files).unwrap();
   }
 
+  #[test]
+  fn test_absolutize_path_dependencies() {
+    let toml_contents = "
+[package]
+name = \"test\"
+version = \"0.0.1\"
+
+[dependencies]
+local = { path = \"../local\" }
+remote = \"1.0\"
+
+[target.'cfg(unix)'.dev-dependencies]
+unix_local = { path = \"unix_local\" }
+";
+    let manifest: toml::Value = toml::from_str(
+      &absolutize_path_dependencies(toml_contents, Path::new("/some/workspace")).unwrap(),
+    )
+    .unwrap();
+
+    let local_path = Path::new("/some/workspace").join("../local");
+    assert_eq!(
+      manifest["dependencies"]["local"]["path"].as_str(),
+      Some(local_path.to_str().unwrap())
+    );
+    assert_eq!(manifest["dependencies"]["remote"].as_str(), Some("1.0"));
+    let unix_local_path = Path::new("/some/workspace").join("unix_local");
+    assert_eq!(
+      manifest["target"]["cfg(unix)"]["dev-dependencies"]["unix_local"]["path"].as_str(),
+      Some(unix_local_path.to_str().unwrap())
+    );
+  }
+
+  #[test]
+  fn test_absolutize_path_dependencies_keeps_contents_without_path_dependencies() {
+    assert_eq!(
+      absolutize_path_dependencies(basic_toml(), Path::new("/some/workspace")).unwrap(),
+      basic_toml()
+    );
+  }
+
+  #[test]
+  fn test_find_cached_crate_archive() {
+    let cargo_home = TempDir::new().unwrap();
+    let cache_dir = |registry_dir: &str| {
+      let cache_dir = cargo_home
+        .path()
+        .join("registry")
+        .join("cache")
+        .join(registry_dir);
+      fs::create_dir_all(&cache_dir).unwrap();
+      fs::copy(
+        "test_fixtures/test-crate-0.1.0.crate",
+        cache_dir.join("test-crate-0.1.0.crate"),
+      )
+      .unwrap();
+      cache_dir
+    };
+    let crates_io =
+      SourceId::from_url("registry+https://github.com/rust-lang/crates.io-index").unwrap();
+    let mirror = SourceId::from_url("registry+https://mirror.example.com/index").unwrap();
+
+    let crates_io_dir = cache_dir("index.crates.io-1949cf8c6b5b557f");
+    assert_eq!(
+      find_cached_crate_archive(cargo_home.path(), &crates_io, "test-crate", "0.1.0"),
+      Some(crates_io_dir.join("test-crate-0.1.0.crate"))
+    );
+    assert_eq!(
+      find_cached_crate_archive(cargo_home.path(), &crates_io, "test-crate", "0.2.0"),
+      None
+    );
+
+    // The archives of other registries are not used
+    assert_eq!(
+      find_cached_crate_archive(cargo_home.path(), &mirror, "test-crate", "0.1.0"),
+      None
+    );
+    let mirror_dir = cache_dir("mirror.example.com-0123456789abcdef");
+    assert_eq!(
+      find_cached_crate_archive(cargo_home.path(), &mirror, "test-crate", "0.1.0"),
+      Some(mirror_dir.join("test-crate-0.1.0.crate"))
+    );
+
+    // Nor are the archives of a registry that cannot be told apart from another one
+    cache_dir("github.com-1ecc6299db9ec823");
+    assert_eq!(
+      find_cached_crate_archive(cargo_home.path(), &crates_io, "test-crate", "0.1.0"),
+      None
+    );
+  }
+
   #[test]
   fn test_cargo_subcommand_metadata_fetcher_handles_bad_files() {
     let dir = TempDir::new().unwrap();
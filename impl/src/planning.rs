@@ -13,9 +13,9 @@
 // limitations under the License.
 
 use std::{
-  collections::{HashMap, HashSet},
-  io,
-  path::PathBuf,
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+  fmt, fs, io,
+  path::{Path, PathBuf},
   str::{self, FromStr},
 };
 
@@ -26,26 +26,31 @@ use cargo_platform::Platform;
 
 use itertools::Itertools;
 
+use semver::VersionReq;
+
+use serde_derive::Serialize;
+
 use crate::{
   bazel::{
     filter_bazel_triples, find_workspace_root, generate_bazel_conditions,
-    get_matching_bazel_triples, is_bazel_supported_platform,
+    get_matching_bazel_triples, get_supported_bazel_triples, is_bazel_supported_platform,
   },
   context::{
     BuildableDependency, BuildableTarget, CrateContext, CrateDependencyContext,
-    CrateTargetedDepContext, DependencyAlias, GitRepo, LicenseData, SourceDetails,
-    WorkspaceContext,
+    CrateSelectableFeatureContext, CrateTargetedDepContext, CrateTargetedFeatureContext,
+    DependencyAlias, GitRepo, LicenseData, PathSource, SourceDetails, WorkspaceContext,
   },
   license,
   metadata::{
-    CargoWorkspaceFiles, DependencyKind, Metadata, MetadataFetcher, Node, Package, PackageId,
+    cargo_home_dir, find_cached_crate_archive, CargoWorkspaceFiles, DependencyKind, Metadata,
+    MetadataFetcher, Node, Package, PackageFeatures, PackageId,
+  },
+  settings::{
+    format_registry_url, is_excluded_crate, is_skipped_dep, CrateSettings, GenMode, RazeSettings,
   },
-  settings::{format_registry_url, CrateSettings, GenMode, RazeSettings},
   util::{self, PlatformDetails, RazeError, PLEASE_FILE_A_BUG},
 };
 
-pub const VENDOR_DIR: &str = "vendor/";
-
 /** An entity that can produce an organized, planned build ready to be rendered. */
 pub trait BuildPlanner {
   /**
@@ -69,7 +74,17 @@ struct DependencyNames {
   // Dependencies that are required for tests
   dev_dep_names: Vec<String>,
   // Dependencies that have been renamed and need to be aliased in the build rule
-  aliased_dep_names: HashMap<String, String>,
+  aliased_dep_names: Vec<DependencyRename>,
+}
+
+/** A dependency that has been given a new name in a package manifest. */
+struct DependencyRename {
+  // The name of the package depended upon
+  name: String,
+  // The version requirement, telling apart several versions of the same package
+  req: VersionReq,
+  // The name the package is known by in the dependent crate
+  alias: String,
 }
 
 // TODO(acmcarther): Remove this struct -- move it into CrateContext.
@@ -88,6 +103,34 @@ struct DependencySet {
   aliased_deps: Vec<DependencyAlias>,
 }
 
+impl DependencySet {
+  /** Yields the sorted, deduplicated names of every dependency in the set. */
+  fn names(&self) -> BTreeSet<String> {
+    self
+      .normal_deps
+      .iter()
+      .chain(&self.proc_macro_deps)
+      .chain(&self.build_deps)
+      .chain(&self.build_proc_macro_deps)
+      .chain(&self.dev_deps)
+      .map(|dep| dep.name.clone())
+      .collect()
+  }
+}
+
+/** Turns dependency labels added through the crate settings into dependencies of the crate. */
+fn additional_dependencies(labels: &[String], is_proc_macro: bool) -> Vec<BuildableDependency> {
+  labels
+    .iter()
+    .map(|label| BuildableDependency {
+      name: label.clone(),
+      version: String::new(),
+      buildable_target: label.clone(),
+      is_proc_macro,
+    })
+    .collect()
+}
+
 struct TargetedDependencySet {
   target: String,
   dependencies: DependencySet,
@@ -97,10 +140,9 @@ struct TargetedDependencySet {
 pub struct CrateCatalogEntry {
   // The package metadata for the crate
   package: Package,
-  // The name of the package sanitized for use within Bazel
-  sanitized_name: String,
-  // The version of the package sanitized for use within Bazel
-  sanitized_version: String,
+  // The name of the library target of the package sanitized for use within Bazel, which the
+  // dependents of the crate reference
+  sanitized_lib_target_name: String,
   // A unique identifier for the package derived from Cargo usage of the form {name}-{version}
   package_ident: String,
   // Is this the root crate in the whole catalog?
@@ -109,8 +151,13 @@ pub struct CrateCatalogEntry {
   is_root_dep: bool,
   // Is this a member of the root crate workspace?
   is_workspace_crate: bool,
+  // The path to the crate's directory, relative to the root Cargo.toml, if it is a path dependency
+  local_path: Option<String>,
 }
 
+/** The features of each package, as resolved for each platform triple. */
+type PlatformFeatures = BTreeMap<String, PackageFeatures>;
+
 /** An intermediate structure that contains details about all crates in the workspace. */
 pub struct CrateCatalog {
   entries: Vec<CrateCatalogEntry>,
@@ -128,6 +175,7 @@ struct WorkspaceSubplanner<'planner> {
   settings: &'planner RazeSettings,
   platform_details: &'planner Option<PlatformDetails>,
   crate_catalog: &'planner CrateCatalog,
+  platform_features: &'planner PlatformFeatures,
   files: &'planner CargoWorkspaceFiles,
 }
 
@@ -137,6 +185,7 @@ struct CrateSubplanner<'planner> {
   settings: &'planner RazeSettings,
   platform_details: &'planner Option<PlatformDetails>,
   crate_catalog: &'planner CrateCatalog,
+  platform_features: &'planner PlatformFeatures,
   // Crate specific content
   crate_catalog_entry: &'planner CrateCatalogEntry,
   source_id: &'planner Option<SourceId>,
@@ -146,10 +195,196 @@ struct CrateSubplanner<'planner> {
 }
 
 /** A ready-to-be-rendered build, containing renderable context for each crate. */
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct PlannedBuild {
   pub workspace_context: WorkspaceContext,
   pub crate_contexts: Vec<CrateContext>,
+  // Non-fatal problems found while planning the build
+  pub warnings: Vec<PlanningWarning>,
+}
+
+impl PlannedBuild {
+  /**
+   * Serializes the planned build to JSON, for use by external tooling.
+   *
+   * The JSON mirrors the context given to the renderers, with one object per crate.
+   */
+  pub fn to_json(&self) -> Result<String> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+}
+
+/**
+ * Merges two planned builds, such as those of several Cargo workspaces rendered together.
+ *
+ * Both builds must share the same workspace context. Crates planned by both collapse into one, and
+ * must have been resolved identically, otherwise their differences (eg: their checksums or
+ * features) would be lost. A crate remains a root dependency if either workspace depends on it
+ * directly.
+ */
+pub fn merge(a: PlannedBuild, b: PlannedBuild) -> Result<PlannedBuild> {
+  if a.workspace_context != b.workspace_context {
+    return Err(
+      RazeError::Planning {
+        dependency_name_opt: None,
+        message: format!(
+          "Planned builds with different workspace contexts cannot be merged: {:?} and {:?}",
+          a.workspace_context, b.workspace_context
+        ),
+      }
+      .into(),
+    );
+  }
+
+  let mut crate_contexts = a.crate_contexts;
+  for crate_context in b.crate_contexts {
+    let planned = crate_contexts.iter_mut().find(|planned| {
+      planned.pkg_name == crate_context.pkg_name && planned.pkg_version == crate_context.pkg_version
+    });
+    match planned {
+      None => crate_contexts.push(crate_context),
+      Some(planned) => {
+        let differences = resolution_differences(planned, &crate_context);
+        if !differences.is_empty() {
+          return Err(
+            RazeError::Planning {
+              dependency_name_opt: Some(crate_context.pkg_name.clone()),
+              message: format!(
+                "Version {} of the crate was planned with different {} by the merged builds",
+                crate_context.pkg_version,
+                differences.join(", ")
+              ),
+            }
+            .into(),
+          );
+        }
+        planned.is_root_dependency |= crate_context.is_root_dependency;
+      },
+    }
+  }
+
+  let mut warnings = a.warnings;
+  warnings.extend(b.warnings);
+  warnings.sort();
+  warnings.dedup();
+
+  Ok(PlannedBuild {
+    workspace_context: a.workspace_context,
+    crate_contexts,
+    warnings,
+  })
+}
+
+/** Names the parts of the resolution of a crate that differ between two of its plans. */
+fn resolution_differences(a: &CrateContext, b: &CrateContext) -> Vec<&'static str> {
+  let mut differences = Vec::new();
+  if a.sha256 != b.sha256 {
+    differences.push("checksums");
+  }
+  if a.features != b.features || a.targeted_features != b.targeted_features {
+    differences.push("features");
+  }
+  if a.default_deps != b.default_deps || a.targeted_deps != b.targeted_deps {
+    differences.push("dependencies");
+  }
+  differences
+}
+
+/** A non-fatal problem found while planning a build. */
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum PlanningWarning {
+  /**
+   * A targeted dependency that was left out of the build because its target matches none of the
+   * supported platform triples.
+   */
+  UnsupportedPlatformDependency {
+    crate_name: String,
+    dependency_name: String,
+    target: String,
+  },
+  /** A crate whose license is not known to cargo-raze, and was assumed to be a notice license. */
+  UnknownLicense { crate_name: String, license: String },
+  /** A dependency that is among the `excluded_crates`, so has no generated BUILD file. */
+  ExcludedDependency {
+    crate_name: String,
+    dependency_name: String,
+    dependency_version: String,
+  },
+  /** A dependency that the crate does not declare as optional, but is among its `skipped_deps`. */
+  SkippedDependency {
+    crate_name: String,
+    dependency_name: String,
+    dependency_version: String,
+  },
+  /**
+   * A targeted dependency whose target uses a cfg option unknown to rustc that resembles a builtin
+   * one, such as `unx` for `unix`. It may be a typo, or a custom cfg option of the crate.
+   */
+  CfgOptionTypo {
+    crate_name: String,
+    dependency_name: String,
+    target: String,
+    option: String,
+    builtin: String,
+  },
+}
+
+impl fmt::Display for PlanningWarning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnsupportedPlatformDependency {
+        crate_name,
+        dependency_name,
+        target,
+      } => write!(
+        f,
+        "Dependency \"{}\" of crate \"{}\" was dropped since \"{}\" matches no supported \
+         platform triple. Consider adding a triple to `supported_platform_triples`.",
+        dependency_name, crate_name, target
+      ),
+      Self::UnknownLicense {
+        crate_name,
+        license,
+      } => write!(
+        f,
+        "The license \"{}\" of crate \"{}\" is not known to cargo-raze, so it was declared as \
+         \"notice\" in `licenses()`. Check that its terms allow this.",
+        license, crate_name
+      ),
+      Self::ExcludedDependency {
+        crate_name,
+        dependency_name,
+        dependency_version,
+      } => write!(
+        f,
+        "Dependency \"{}-{}\" of crate \"{}\" is excluded from generation, so its targets must \
+         be provided some other way.",
+        dependency_name, dependency_version, crate_name
+      ),
+      Self::SkippedDependency {
+        crate_name,
+        dependency_name,
+        dependency_version,
+      } => write!(
+        f,
+        "Dependency \"{}-{}\" of crate \"{}\" is required, but was removed by `skipped_deps`. \
+         Consider providing it through `additional_deps`.",
+        dependency_name, dependency_version, crate_name
+      ),
+      Self::CfgOptionTypo {
+        crate_name,
+        dependency_name,
+        target,
+        option,
+        builtin,
+      } => write!(
+        f,
+        "Dependency \"{}\" of crate \"{}\" targets \"{}\", whose cfg option `{}` is unknown to \
+         rustc. Did you mean `{}`?",
+        dependency_name, crate_name, target, option, builtin
+      ),
+    }
+  }
 }
 
 impl CrateCatalogEntry {
@@ -158,25 +393,35 @@ impl CrateCatalogEntry {
     is_root: bool,
     is_root_dep: bool,
     is_workspace_crate: bool,
+    local_path: Option<String>,
   ) -> Self {
-    let sanitized_name = package.name.replace("-", "_");
-    let sanitized_version = util::sanitize_ident(&package.version.clone().to_string());
+    // Cargo allows a single library target per package, which may be named apart from the package
+    let sanitized_lib_target_name = package
+      .targets
+      .iter()
+      .find(|target| {
+        target
+          .kind
+          .iter()
+          .any(|kind| matches!(kind.as_str(), "lib" | "proc-macro" | "dylib" | "rlib"))
+      })
+      .map(|target| target.name.replace("-", "_"))
+      .unwrap_or_else(|| package.name.replace("-", "_"));
 
     Self {
       package: package.clone(),
       package_ident: format!("{}-{}", &package.name, &package.version),
-      sanitized_name,
-      sanitized_version,
+      sanitized_lib_target_name,
       is_root,
       is_root_dep,
       is_workspace_crate,
+      local_path,
     }
   }
 
-  /** Yields the name of the default target for this crate (sanitized). */
-  #[allow(dead_code)]
+  /** Yields the name of the default target for this crate (sanitized), its library. */
   pub fn default_build_target_name(&self) -> &str {
-    &self.sanitized_name
+    &self.sanitized_lib_target_name
   }
 
   /** Returns a reference to the contained package. */
@@ -199,77 +444,143 @@ impl CrateCatalogEntry {
     self.is_root_dep
   }
 
+  /** Returns the path to the crate's directory if this is a path dependency. */
+  pub fn local_path(&self) -> Option<&String> {
+    self.local_path.as_ref()
+  }
+
+  /** Yields the Bazel package of a path dependency, which lives in its own directory. */
+  fn local_package(&self, settings: &RazeSettings, local_path: &str) -> String {
+    let mut components = settings
+      .workspace_path
+      .trim_start_matches('/')
+      .split('/')
+      .filter(|component| !component.is_empty())
+      .collect::<Vec<&str>>();
+
+    for component in local_path.split('/') {
+      match component {
+        "" | "." => {},
+        // A path out of the Bazel workspace keeps its ".." so that Bazel rejects the label
+        ".." if matches!(components.last(), Some(last) if *last != "..") => {
+          components.pop();
+        },
+        component => components.push(component),
+      }
+    }
+
+    format!("//{}", components.join("/"))
+  }
+
   /**
    * Returns the packages expected path during current execution.
    *
    * Not for use except during planning as path is local to run location.
    */
-  pub fn expected_vendored_path(&self, workspace_path: &String) -> String {
+  pub fn expected_vendored_path(&self, settings: &RazeSettings) -> String {
     let mut dir = find_workspace_root().unwrap_or(PathBuf::from("."));
 
     // Trim the absolute label identifier from the start of the workspace path
-    dir.push(workspace_path.trim_start_matches('/'));
+    dir.push(settings.workspace_path.trim_start_matches('/'));
 
-    dir.push(VENDOR_DIR);
-    dir.push(&self.package_ident);
+    dir.push(self.vendored_dir(settings));
 
     return dir.display().to_string();
   }
 
+  /** Yields the directory of the vendored crate, relative to the workspace path. */
+  fn vendored_dir(&self, settings: &RazeSettings) -> String {
+    settings
+      .vendor_dir_template
+      .replace("{crate}", &self.package.name)
+      .replace("{version}", &self.package.version.to_string())
+  }
+
   /** Yields the expected location of the build file (relative to execution path). */
-  pub fn local_build_path(&self, settings: &RazeSettings) -> String {
+  pub fn local_build_path(&self, settings: &RazeSettings, buildfile_suffix: &str) -> String {
+    // Path dependencies are not fetched, their build file is placed next to their sources
+    if let Some(local_path) = &self.local_path {
+      return format!("{}/{}", local_path, buildfile_suffix);
+    }
+
     match settings.genmode {
-      GenMode::Remote => format!(
-        "remote/{}.{}",
-        &self.package_ident, settings.output_buildfile_suffix,
-      ),
-      GenMode::Vendored => format!(
-        "vendor/{}/{}",
-        &self.package_ident, settings.output_buildfile_suffix,
-      ),
+      GenMode::Remote => format!("remote/{}.{}", &self.package_ident, buildfile_suffix),
+      GenMode::Vendored => format!("{}/{}", self.vendored_dir(settings), buildfile_suffix),
     }
   }
 
+  /**
+   * Yields the name of the Bazel repository fetching this dependency under Remote GenMode.
+   *
+   * This must match the repository names declared in the generated crates.bzl, which take the
+   * form {gen_workspace_prefix}__{name}__{version}.
+   */
+  pub fn workspace_repository_name(&self, settings: &RazeSettings) -> String {
+    util::sanitize_repository_name(
+      &settings.gen_workspace_prefix,
+      &self.package.name,
+      &self.package.version.to_string(),
+    )
+  }
+
   /** Yields the precise path to this dependency for the provided settings. */
   #[allow(dead_code)]
   pub fn workspace_path(&self, settings: &RazeSettings) -> String {
+    if let Some(local_path) = &self.local_path {
+      return self.local_package(settings, local_path);
+    }
+
     match settings.genmode {
-      GenMode::Remote => format!(
-        "@{}__{}__{}//",
-        &settings.gen_workspace_prefix, &self.sanitized_name, &self.sanitized_version
-      ),
+      GenMode::Remote => format!("@{}//", self.workspace_repository_name(settings)),
       GenMode::Vendored => {
+        let vendored_dir = self.vendored_dir(settings);
         // Convert "settings.workspace_path" to dir. Workspace roots are special cased, no need to append /
         if settings.workspace_path.ends_with("//") {
-          format!("{}vendor/{}", settings.workspace_path, &self.package_ident)
+          format!("{}{}", settings.workspace_path, vendored_dir)
         } else {
-          format!("{}/vendor/{}", settings.workspace_path, &self.package_ident)
+          format!("{}/{}", settings.workspace_path, vendored_dir)
         }
       },
     }
   }
 
-  /** Emits a complete path to this dependency and default target using the given settings. */
+  /**
+   * Emits a complete path to this dependency and default target using the given settings.
+   *
+   * The target is the library of the crate, named explicitly rather than through the alias named
+   * after the crate, since the two names may differ.
+   */
   pub fn workspace_path_and_default_target(&self, settings: &RazeSettings) -> String {
+    if let Some(local_path) = &self.local_path {
+      return format!(
+        "{}:{}",
+        self.local_package(settings, local_path),
+        self.default_build_target_name()
+      );
+    }
+
     match settings.genmode {
       GenMode::Remote => format!(
-        "@{}__{}__{}//:{}",
-        &settings.gen_workspace_prefix,
-        &self.sanitized_name,
-        &self.sanitized_version,
-        &self.sanitized_name
+        "@{}//:{}",
+        self.workspace_repository_name(settings),
+        self.default_build_target_name()
       ),
       GenMode::Vendored => {
+        let vendored_dir = self.vendored_dir(settings);
         // Convert "settings.workspace_path" to dir. Workspace roots are special cased, no need to append /
         if settings.workspace_path.ends_with("//") {
           format!(
-            "{}vendor/{}:{}",
-            settings.workspace_path, &self.package_ident, &self.sanitized_name
+            "{}{}:{}",
+            settings.workspace_path,
+            vendored_dir,
+            self.default_build_target_name()
           )
         } else {
           format!(
-            "{}/vendor/{}:{}",
-            settings.workspace_path, &self.package_ident, &self.sanitized_name
+            "{}/{}:{}",
+            settings.workspace_path,
+            vendored_dir,
+            self.default_build_target_name()
           )
         }
       },
@@ -278,8 +589,13 @@ impl CrateCatalogEntry {
 }
 
 impl CrateCatalog {
-  /** Produces a CrateCatalog using the package entries from a metadata blob.*/
-  pub fn new(metadata: &Metadata) -> Result<Self> {
+  /**
+   * Produces a CrateCatalog using the package entries from a metadata blob.
+   *
+   * The paths of path dependencies are made relative to `manifest_dir`, the directory containing
+   * the root Cargo.toml.
+   */
+  pub fn new(metadata: &Metadata, manifest_dir: &Path) -> Result<Self> {
     let resolve = metadata
       .resolve
       .as_ref()
@@ -313,11 +629,28 @@ impl CrateCatalog {
       .packages
       .iter()
       .map(|package| {
+        let is_root = root_resolve_node.id == package.id;
+        let is_workspace_crate = workspace_crates.contains(&package.id);
+
+        // Path dependencies are the only crates without a source
+        let local_path = if package.source.is_none() && !is_root && !is_workspace_crate {
+          package.manifest_path.parent().map(|package_dir| {
+            let package_dir =
+              fs::canonicalize(package_dir).unwrap_or_else(|_| package_dir.to_path_buf());
+            util::relative_path(manifest_dir, &package_dir)
+              .to_string_lossy()
+              .replace("\\", "/")
+          })
+        } else {
+          None
+        };
+
         CrateCatalogEntry::new(
           package,
-          root_resolve_node.id == package.id,
+          is_root,
           root_direct_deps.contains(&package.id),
-          workspace_crates.contains(&package.id),
+          is_workspace_crate,
+          local_path,
         )
       })
       .collect::<Vec<_>>();
@@ -359,12 +692,34 @@ impl<'fetcher> BuildPlanner for BuildPlannerImpl<'fetcher> {
     platform_details: Option<PlatformDetails>,
   ) -> Result<PlannedBuild> {
     let metadata = self.metadata_fetcher.fetch_metadata(&files)?;
-    let crate_catalog = CrateCatalog::new(&metadata)?;
+    let manifest_dir = fs::canonicalize(&files.toml_path)?
+      .parent()
+      .map(Path::to_path_buf)
+      .unwrap_or_default();
+    let crate_catalog = CrateCatalog::new(&metadata, &manifest_dir)?;
+
+    // Resolve features separately for each of the targeted platforms, as they may differ
+    let mut platform_features = PlatformFeatures::new();
+    if let Some(targets) = settings
+      .targets
+      .as_ref()
+      .filter(|targets| !targets.is_empty())
+    {
+      let mut feature_triples = get_supported_bazel_triples(&settings.supported_platform_triples);
+      filter_bazel_triples(&mut feature_triples, targets)?;
+      for triple in feature_triples {
+        let package_features = self
+          .metadata_fetcher
+          .fetch_platform_features(&files, &triple)?;
+        platform_features.insert(triple, package_features);
+      }
+    }
 
     // Generate additional PlatformDetails
 
     let workspace_subplanner = WorkspaceSubplanner {
       crate_catalog: &crate_catalog,
+      platform_features: &platform_features,
       metadata: &metadata,
       settings: &settings,
       platform_details: &platform_details,
@@ -387,21 +742,30 @@ impl<'planner> WorkspaceSubplanner<'planner> {
   /** Produces a planned build using internal state. */
   pub fn produce_planned_build(&self) -> Result<PlannedBuild> {
     checks::check_resolve_matches_packages(&self.metadata)?;
+    checks::check_dependency_targets(&self.metadata.packages)?;
 
+    let skipped_package_ids = self.produce_skipped_dev_package_ids()?;
     if self.settings.genmode != GenMode::Remote {
       checks::check_all_vendored(
         self.crate_catalog.entries(),
-        &self.settings.workspace_path.to_string(),
+        self.settings,
+        &skipped_package_ids,
       )?;
     }
 
     checks::warn_unused_settings(&self.settings.crates, &self.metadata.packages);
 
-    let crate_contexts = self.produce_crate_contexts()?;
+    let (crate_contexts, mut warnings) = self.produce_crate_contexts(&skipped_package_ids)?;
+    warnings.extend(checks::cfg_option_typo_warnings(&self.metadata.packages));
+    checks::check_dependencies_are_planned(&crate_contexts, &self.settings.excluded_crates)?;
+    if self.settings.strict_platform_support {
+      checks::check_strict_platform_support(&warnings)?;
+    }
 
     Ok(PlannedBuild {
       workspace_context: self.produce_workspace_context(),
       crate_contexts,
+      warnings,
     })
   }
 
@@ -411,11 +775,73 @@ impl<'planner> WorkspaceSubplanner<'planner> {
       workspace_path: self.settings.workspace_path.clone(),
       gen_workspace_prefix: self.settings.gen_workspace_prefix.clone(),
       output_buildfile_suffix: self.settings.output_buildfile_suffix.clone(),
+      rules_rust_repository_name: self.settings.rules_rust_repository_name.clone(),
+      default_tags: self.settings.default_tags.clone(),
+      generated_file_header: self.settings.generated_file_header.clone(),
+      alias_name_style: self.settings.alias_name_style,
+    }
+  }
+
+  /**
+   * Produces the packages only reached through dev-dependencies of the workspace crates, which are
+   * skipped unless `render_dev_dependencies` is set.
+   */
+  fn produce_skipped_dev_package_ids(&self) -> Result<HashSet<PackageId>> {
+    if self.settings.render_dev_dependencies {
+      return Ok(HashSet::new());
+    }
+
+    let nodes = &self
+      .metadata
+      .resolve
+      .as_ref()
+      .ok_or_else(|| RazeError::Generic("Missing resolve graph".into()))?
+      .nodes;
+    let nodes_by_id = nodes
+      .iter()
+      .map(|node| (&node.id, node))
+      .collect::<HashMap<&PackageId, &Node>>();
+
+    let mut reached_ids = HashSet::new();
+    let mut pending_ids = self.metadata.workspace_members.iter().collect::<Vec<_>>();
+    while let Some(id) = pending_ids.pop() {
+      if !reached_ids.insert(id) {
+        continue;
+      }
+
+      if let Some(node) = nodes_by_id.get(id) {
+        // Dependency kinds are unknown to cargo versions predating 1.41, in which case every
+        // dependency is followed
+        pending_ids.extend(node.dependencies.iter().filter(|dep_id| {
+          match node.deps.iter().find(|dep| dep.pkg == **dep_id) {
+            Some(dep) if !dep.dep_kinds.is_empty() => dep
+              .dep_kinds
+              .iter()
+              .any(|info| info.kind != DependencyKind::Development),
+            _ => true,
+          }
+        }));
+      }
     }
+
+    Ok(
+      nodes
+        .iter()
+        .map(|node| &node.id)
+        .filter(|id| !reached_ids.contains(id))
+        .cloned()
+        .collect(),
+    )
   }
 
-  /** Produces a crate context for each declared crate and dependency. */
-  fn produce_crate_contexts(&self) -> Result<Vec<CrateContext>> {
+  /**
+   * Produces a crate context for each declared crate and dependency, along with any warnings
+   * raised while planning them. The `skipped_package_ids` are left out.
+   */
+  fn produce_crate_contexts(
+    &self,
+    skipped_package_ids: &HashSet<PackageId>,
+  ) -> Result<(Vec<CrateContext>, Vec<PlanningWarning>)> {
     // Gather the checksums for all packages in the lockfile
     // which have them.
     //
@@ -434,7 +860,7 @@ impl<'planner> WorkspaceSubplanner<'planner> {
       }
     }
 
-    self
+    let planned_crates = self
       .metadata
       .resolve
       .as_ref()
@@ -464,6 +890,20 @@ impl<'planner> WorkspaceSubplanner<'planner> {
           return None;
         }
 
+        // Skip crates only needed by skipped dev dependencies
+        if skipped_package_ids.contains(&node.id) {
+          return None;
+        }
+
+        // Skip excluded crates, which are provided some other way
+        if is_excluded_crate(
+          &self.settings.excluded_crates,
+          &own_package.name,
+          &own_package.version.to_string(),
+        ) {
+          return None;
+        }
+
         let crate_settings = self
           .settings
           .crates
@@ -480,6 +920,7 @@ impl<'planner> WorkspaceSubplanner<'planner> {
 
         let crate_subplanner = CrateSubplanner {
           crate_catalog: &self.crate_catalog,
+          platform_features: self.platform_features,
           settings: self.settings,
           platform_details: self.platform_details,
           crate_catalog_entry: &own_crate_catalog_entry,
@@ -491,29 +932,101 @@ impl<'planner> WorkspaceSubplanner<'planner> {
 
         Some(crate_subplanner.produce_context())
       })
-      .collect()
+      .collect::<Result<Vec<_>>>()?;
+
+    let (crate_contexts, warnings): (Vec<_>, Vec<_>) = planned_crates.into_iter().unzip();
+    let mut warnings = warnings.into_iter().flatten().collect::<Vec<_>>();
+    warnings.extend(self.excluded_dependency_warnings(&crate_contexts));
+    Ok((crate_contexts, warnings))
+  }
+
+  /** Warns about the dependencies of planned crates that are among the `excluded_crates`. */
+  fn excluded_dependency_warnings(&self, crate_contexts: &[CrateContext]) -> Vec<PlanningWarning> {
+    let mut warnings = Vec::new();
+    for crate_context in crate_contexts {
+      let dependency_contexts = std::iter::once(&crate_context.default_deps).chain(
+        crate_context
+          .targeted_deps
+          .iter()
+          .map(|targeted_deps| &targeted_deps.deps),
+      );
+      for deps in dependency_contexts {
+        let all_dependencies = deps
+          .dependencies
+          .iter()
+          .chain(deps.proc_macro_dependencies.iter())
+          .chain(deps.build_dependencies.iter())
+          .chain(deps.build_proc_macro_dependencies.iter())
+          .chain(deps.dev_dependencies.iter());
+        for dependency in all_dependencies {
+          if is_excluded_crate(
+            &self.settings.excluded_crates,
+            &dependency.name,
+            &dependency.version,
+          ) {
+            warnings.push(PlanningWarning::ExcludedDependency {
+              crate_name: crate_context.pkg_name.clone(),
+              dependency_name: dependency.name.clone(),
+              dependency_version: dependency.version.clone(),
+            });
+          }
+        }
+      }
+    }
+
+    warnings.sort();
+    warnings.dedup();
+    warnings
   }
 }
 
 impl<'planner> CrateSubplanner<'planner> {
-  /** Builds a crate context from internal state. */
-  fn produce_context(&self) -> Result<CrateContext> {
+  /** Builds a crate context from internal state, along with any warnings raised on the way. */
+  fn produce_context(&self) -> Result<(CrateContext, Vec<PlanningWarning>)> {
     let (
       DependencySet {
         build_deps,
         build_proc_macro_deps,
-        proc_macro_deps,
+        mut proc_macro_deps,
         dev_deps,
-        normal_deps,
+        mut normal_deps,
         aliased_deps,
       },
       targeted_deps,
+      mut warnings,
     ) = self.produce_deps()?;
+    warnings.extend(self.skipped_dependency_warnings());
+    normal_deps.extend(additional_dependencies(
+      &self.crate_settings.additional_deps,
+      false,
+    ));
+    proc_macro_deps.extend(additional_dependencies(
+      &self.crate_settings.additional_proc_macro_deps,
+      true,
+    ));
+    let target_dependencies = self
+      .crate_settings
+      .additional_target_deps
+      .iter()
+      .map(|(target_name, labels)| {
+        let mut target_deps = normal_deps.clone();
+        target_deps.extend(additional_dependencies(labels, false));
+        (target_name.clone(), target_deps)
+      })
+      .collect();
 
     let mut targets = self.produce_targets()?;
     let build_script_target_opt = self.take_build_script_target(&mut targets);
 
     let package = self.crate_catalog_entry.package();
+    let has_dev_deps = package
+      .dependencies
+      .iter()
+      .any(|dep| dep.kind == DependencyKind::Development);
+    if has_dev_deps && !self.settings.render_dev_dependencies {
+      // Tests and examples would not build without the skipped dev dependencies
+      targets.retain(|target| target.kind != "test" && target.kind != "example");
+    }
     let mut lib_target_name = None;
     {
       for target in &targets {
@@ -527,7 +1040,12 @@ impl<'planner> CrateSubplanner<'planner> {
     // Build a list of dependencies while addression a potential whitelist of target triples
     let mut filtered_deps = Vec::new();
     for dep_set in targeted_deps.iter() {
-      let mut target_triples = get_matching_bazel_triples(&dep_set.target)?;
+      let mut target_triples = get_matching_bazel_triples(
+        &dep_set.target,
+        &self.settings.supported_platform_triples,
+      )?;
+      // Dependencies only excluded by the `targets` whitelist are dropped deliberately
+      let is_unsupported = target_triples.is_empty();
       filter_bazel_triples(
         &mut target_triples,
         self
@@ -535,9 +1053,24 @@ impl<'planner> CrateSubplanner<'planner> {
           .targets
           .as_ref()
           .unwrap_or(&Vec::<String>::new()),
-      );
+      )?;
 
-      if target_triples.len() == 0 {
+      if is_unsupported {
+        warnings.extend(
+          dep_set
+            .dependencies
+            .names()
+            .into_iter()
+            .map(
+              |dependency_name| PlanningWarning::UnsupportedPlatformDependency {
+                crate_name: package.name.clone(),
+                dependency_name,
+                target: dep_set.target.clone(),
+              },
+            ),
+        );
+      }
+      if target_triples.is_empty() {
         continue;
       }
 
@@ -551,18 +1084,42 @@ impl<'planner> CrateSubplanner<'planner> {
           dev_dependencies: dep_set.dependencies.dev_deps.clone(),
           aliased_dependencies: dep_set.dependencies.aliased_deps.clone(),
         },
-        conditions: generate_bazel_conditions(&target_triples)?,
+        conditions: generate_bazel_conditions(
+          &self.settings.rules_rust_repository_name,
+          &target_triples,
+          &self.settings.supported_platform_triples,
+        )?,
+      });
+    }
+
+    let license = self.produce_license();
+    if license.is_unknown {
+      warnings.push(PlanningWarning::UnknownLicense {
+        crate_name: package.name.clone(),
+        license: package.license.clone().unwrap_or_default(),
       });
     }
 
-    filtered_deps.sort();
+    // Each platform's select is ordered by its conditions, so regenerating never reorders them
+    filtered_deps.sort_by(|a, b| (&a.conditions, &a.target).cmp(&(&b.conditions, &b.target)));
+    warnings.sort();
+    warnings.dedup();
 
-    let context = CrateContext {
+    let (features, targeted_features) = self.produce_features()?;
+    let selectable_features = self.produce_selectable_features(&features, &targeted_features);
+
+    let mut context = CrateContext {
       pkg_name: package.name.clone(),
       pkg_version: package.version.to_string(),
-      edition: package.edition.clone(),
-      license: self.produce_license(),
-      features: self.node.features.clone(),
+      edition: checks::check_edition(
+        &package.name,
+        &package.edition,
+        &self.settings.default_edition,
+      )?,
+      license,
+      features,
+      targeted_features,
+      selectable_features,
       is_root_dependency: self.crate_catalog_entry.is_root_dep(),
       default_deps: CrateDependencyContext {
         dependencies: normal_deps,
@@ -573,22 +1130,254 @@ impl<'planner> CrateSubplanner<'planner> {
         aliased_dependencies: aliased_deps,
       },
       targeted_deps: filtered_deps,
+      target_dependencies,
       workspace_path_to_crate: self.crate_catalog_entry.workspace_path(&self.settings),
       build_script_target: build_script_target_opt,
-      raze_settings: self.crate_settings.clone(),
+      raze_settings: self.produce_crate_settings(),
       source_details: self.produce_source_details(),
-      expected_build_path: self.crate_catalog_entry.local_build_path(&self.settings),
-      sha256: self.sha256.clone(),
-      registry_url: format_registry_url(
-        &self.settings.registry,
-        &package.name,
-        &package.version.to_string(),
+      expected_build_path: self.crate_catalog_entry.local_build_path(
+        self.settings,
+        self
+          .crate_settings
+          .buildfile_suffix_override
+          .as_ref()
+          .unwrap_or(&self.settings.output_buildfile_suffix),
       ),
+      sha256: self.produce_sha256()?,
+      registry_url: self.produce_registry_url()?,
       lib_target_name,
       targets,
     };
+    context.sort_dependencies_by_label();
+
+    Ok((context, warnings))
+  }
+
+  /**
+   * Produces the settings of the crate, supplying the outputs of a skipped build script as flags
+   * and environment variables of the crate compilation.
+   */
+  fn produce_crate_settings(&self) -> CrateSettings {
+    let mut crate_settings = self.crate_settings.clone();
+    if crate_settings.skip_build_script {
+      crate_settings
+        .additional_flags
+        .extend(self.crate_settings.build_script_rustc_flags.iter().cloned());
+      crate_settings.additional_env.extend(
+        self
+          .crate_settings
+          .build_script_rustc_env
+          .iter()
+          .map(|(key, value)| (key.clone(), value.clone())),
+      );
+    }
+    crate_settings
+  }
+
+  /**
+   * Splits the features of the crate into those enabled on every targeted platform, and those only
+   * enabled on some of them.
+   *
+   * Platforms enabling the same extra features share their conditions. Without features resolved
+   * per platform, all of the crate's features are common.
+   */
+  fn produce_features(&self) -> Result<(Vec<String>, Vec<CrateTargetedFeatureContext>)> {
+    let package = self.crate_catalog_entry.package();
+    let package_key = (package.name.clone(), package.version.to_string());
+    let features_by_triple: BTreeMap<&String, &BTreeSet<String>> = self
+      .platform_features
+      .iter()
+      .filter_map(|(triple, package_features)| Some((triple, package_features.get(&package_key)?)))
+      .collect();
+
+    let mut feature_sets = features_by_triple.values();
+    let common_features = match feature_sets.next() {
+      Some(first) => feature_sets.fold((*first).clone(), |common, features| &common & *features),
+      None => return Ok((self.node.features.clone(), Vec::new())),
+    };
+
+    let mut triples_by_extra_features: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+    for (triple, features) in &features_by_triple {
+      let extra_features: Vec<String> = features.difference(&common_features).cloned().collect();
+      if !extra_features.is_empty() {
+        triples_by_extra_features
+          .entry(extra_features)
+          .or_default()
+          .push(triple.to_string());
+      }
+    }
+
+    let targeted_features = triples_by_extra_features
+      .into_iter()
+      .map(|(features, triples)| {
+        Ok(CrateTargetedFeatureContext {
+          features,
+          conditions: generate_bazel_conditions(
+            &self.settings.rules_rust_repository_name,
+            &triples,
+            &self.settings.supported_platform_triples,
+          )?,
+        })
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok((common_features.into_iter().collect(), targeted_features))
+  }
+
+  /**
+   * Yields the features of the crate which are not enabled on any platform, but could be toggled
+   * at build time, along with the other features each one enables.
+   *
+   * Features that enable dependencies, directly or through other features, are left out.
+   */
+  fn produce_selectable_features(
+    &self,
+    features: &[String],
+    targeted_features: &[CrateTargetedFeatureContext],
+  ) -> Vec<CrateSelectableFeatureContext> {
+    if !self.crate_settings.selectable_features {
+      return Vec::new();
+    }
+
+    let package = self.crate_catalog_entry.package();
+    let enabled_features = features
+      .iter()
+      .chain(
+        targeted_features
+          .iter()
+          .flat_map(|targeted_feature| targeted_feature.features.iter()),
+      )
+      .collect::<HashSet<_>>();
+
+    let mut selectable_features = Vec::new();
+    for name in package.features.keys().sorted() {
+      if name == "default" || enabled_features.contains(name) {
+        continue;
+      }
+
+      // Entries which are not features of the crate itself, such as "dep:foo" or "foo/bar", enable
+      // dependencies
+      let mut enabled_by_feature = BTreeSet::new();
+      let mut pending = vec![name];
+      let mut enables_dependencies = false;
+      while let Some(feature) = pending.pop() {
+        if enabled_features.contains(feature) || !enabled_by_feature.insert(feature.clone()) {
+          continue;
+        }
+        match package.features.get(feature) {
+          Some(implied_features) => pending.extend(implied_features),
+          None => {
+            enables_dependencies = true;
+            break;
+          },
+        }
+      }
+
+      if !enables_dependencies {
+        selectable_features.push(CrateSelectableFeatureContext {
+          name: name.clone(),
+          features: enabled_by_feature.into_iter().collect(),
+        });
+      }
+    }
+
+    selectable_features
+  }
+
+  /**
+   * Generates the download URL of the crate from the registry it was resolved from.
+   *
+   * In Remote genmode, crates from registries other than the default one must have their registry
+   * configured in the `registries` setting. Vendored crates are not downloaded, so the default
+   * registry stands in for unconfigured ones.
+   */
+  fn produce_registry_url(&self) -> Result<String> {
+    let package = self.crate_catalog_entry.package();
+
+    let registry_pattern = match self.source_id {
+      Some(source_id) if source_id.is_registry() && !source_id.is_default_registry() => {
+        let index_url = source_id.url().as_str().trim_end_matches('/');
+        let configured_pattern = self
+          .settings
+          .registries
+          .iter()
+          .find(|(url, _)| url.trim_end_matches('/') == index_url)
+          .map(|(_, pattern)| pattern);
+        match configured_pattern {
+          Some(pattern) => pattern,
+          None if self.settings.genmode != GenMode::Remote => &self.settings.registry,
+          None => {
+            return Err(
+              RazeError::Planning {
+                dependency_name_opt: Some(package.name.to_string()),
+                message: format!(
+                  "The crate comes from the registry at \"{}\", which is not configured. Add its \
+                   download URL pattern to raze.registries.",
+                  index_url
+                ),
+              }
+              .into(),
+            )
+          },
+        }
+      },
+      _ => &self.settings.registry,
+    };
+
+    Ok(format_registry_url(
+      registry_pattern,
+      &package.name,
+      &package.version.to_string(),
+    ))
+  }
+
+  /**
+   * Produces the sha256 checksum of the crate's archive.
+   *
+   * The checksum is taken from the lockfile if present, or computed from the archive downloaded by
+   * Cargo otherwise. It is verified against the checksum in the crate's settings, if any.
+   */
+  fn produce_sha256(&self) -> Result<Option<String>> {
+    let package = self.crate_catalog_entry.package();
+    let is_registry_crate = matches!(&self.source_id, Some(source_id) if source_id.is_registry());
+
+    let sha256_opt = match self.sha256 {
+      Some(sha256) => Some(sha256.clone()),
+      None if is_registry_crate => cargo_home_dir()
+        .zip(self.source_id.as_ref())
+        .and_then(|(cargo_home, source_id)| {
+          find_cached_crate_archive(
+            &cargo_home,
+            source_id,
+            &package.name,
+            &package.version.to_string(),
+          )
+        })
+        .map(|archive_path| util::sha256_of_file(&archive_path))
+        .transpose()?,
+      None => None,
+    };
 
-    Ok(context)
+    let sha256_opt = checks::check_sha256_matches(
+      &package.name,
+      sha256_opt,
+      self.crate_settings.sha256.as_ref(),
+    )?;
+
+    // Remote crates are fetched by Bazel, which should always verify what it downloads
+    if sha256_opt.is_none() && is_registry_crate && self.settings.genmode == GenMode::Remote {
+      return Err(
+        RazeError::Planning {
+          dependency_name_opt: Some(package.name.to_string()),
+          message: "Unable to determine the sha256 of the crate's archive. Provide a Cargo.lock \
+                    with checksums, or set the crate's sha256 setting."
+            .to_owned(),
+        }
+        .into(),
+      );
+    }
+
+    Ok(sha256_opt)
   }
 
   /** Generates license data from internal crate details. */
@@ -616,13 +1405,6 @@ impl<'planner> CrateSubplanner<'planner> {
     let mut normal_deps = Vec::new();
     let mut aliased_deps = Vec::new();
 
-    let all_skipped_deps = self
-      .crate_settings
-      .skipped_deps
-      .iter()
-      .cloned()
-      .collect::<HashSet<_>>();
-
     for dep_id in &self.node.dependencies {
       // UNWRAP(s): Safe from verification of packages_by_id
       let dep_package = self
@@ -632,7 +1414,11 @@ impl<'planner> CrateSubplanner<'planner> {
         .package();
 
       // Skip settings-indicated deps to skip
-      if all_skipped_deps.contains(&format!("{}-{}", dep_package.name, dep_package.version)) {
+      if is_skipped_dep(
+        &self.crate_settings.skipped_deps,
+        &dep_package.name,
+        &dep_package.version.to_string(),
+      ) {
         continue;
       }
 
@@ -688,21 +1474,20 @@ impl<'planner> CrateSubplanner<'planner> {
           normal_deps.push(buildable_dependency);
         }
         // Only add aliased normal deps to the Vec
-        if let Some(alias) = aliased_dep_names.get(&dep_package.name) {
+        // N.B. The same package may be depended upon at several versions, only some of which may
+        // have been renamed.
+        let rename_opt = aliased_dep_names.iter().find(|rename| {
+          rename.name == dep_package.name && rename.req.matches(&dep_package.version)
+        });
+        if let Some(rename) = rename_opt {
           aliased_deps.push(DependencyAlias {
             target: buildable_target.clone(),
-            alias: util::sanitize_ident(alias),
+            alias: util::sanitize_ident(&rename.alias),
           })
         }
       }
     }
 
-    build_deps.sort();
-    build_proc_macro_deps.sort();
-    proc_macro_deps.sort();
-    dev_deps.sort();
-    normal_deps.sort();
-
     Ok(DependencySet {
       build_deps,
       build_proc_macro_deps,
@@ -713,9 +1498,56 @@ impl<'planner> CrateSubplanner<'planner> {
     })
   }
 
-  /** Generates the set of dependencies for the contained crate. */
-  fn produce_deps(&self) -> Result<(DependencySet, Vec<TargetedDependencySet>)> {
-    let (default_deps, targeted_deps) = self.identify_named_deps()?;
+  /** Warns about the resolved dependencies that are required, but among the `skipped_deps`. */
+  fn skipped_dependency_warnings(&self) -> Vec<PlanningWarning> {
+    let package = self.crate_catalog_entry.package();
+    let mut warnings = Vec::new();
+    for dep_id in &self.node.dependencies {
+      // UNWRAP: Safe from verification of packages_by_id
+      let dep_package = self
+        .crate_catalog
+        .entry_for_package_id(dep_id)
+        .unwrap()
+        .package();
+      let dep_version = dep_package.version.to_string();
+      if !is_skipped_dep(
+        &self.crate_settings.skipped_deps,
+        &dep_package.name,
+        &dep_version,
+      ) {
+        continue;
+      }
+
+      let is_required = package.dependencies.iter().any(|dep| {
+        dep.name == dep_package.name
+          && dep.kind != DependencyKind::Development
+          && !dep.optional
+          && dep.req.matches(&dep_package.version)
+      });
+      if is_required {
+        warnings.push(PlanningWarning::SkippedDependency {
+          crate_name: package.name.clone(),
+          dependency_name: dep_package.name.clone(),
+          dependency_version: dep_version,
+        });
+      }
+    }
+
+    warnings
+  }
+
+  /**
+   * Generates the set of dependencies for the contained crate, along with warnings for any
+   * dependencies dropped on the way.
+   */
+  fn produce_deps(
+    &self,
+  ) -> Result<(
+    DependencySet,
+    Vec<TargetedDependencySet>,
+    Vec<PlanningWarning>,
+  )> {
+    let (default_deps, targeted_deps, warnings) = self.identify_named_deps()?;
 
     let targeted_set = targeted_deps
       .iter()
@@ -725,23 +1557,37 @@ impl<'planner> CrateSubplanner<'planner> {
       })
       .collect::<Vec<TargetedDependencySet>>();
 
-    Ok((self._produce_deps(&default_deps)?, targeted_set))
+    Ok((self._produce_deps(&default_deps)?, targeted_set, warnings))
   }
 
-  /** Yields the list of dependencies as described by the manifest (without version). */
-  fn identify_named_deps(&self) -> Result<(DependencyNames, HashMap<String, DependencyNames>)> {
+  /**
+   * Yields the list of dependencies as described by the manifest (without version), along with
+   * warnings for those dropped since their target is not supported.
+   */
+  fn identify_named_deps(
+    &self,
+  ) -> Result<(
+    DependencyNames,
+    HashMap<String, DependencyNames>,
+    Vec<PlanningWarning>,
+  )> {
     // Resolve dependencies into types
     let mut default_dep_names = DependencyNames {
       build_dep_names: Vec::new(),
       dev_dep_names: Vec::new(),
       normal_dep_names: Vec::new(),
-      aliased_dep_names: HashMap::new(),
+      aliased_dep_names: Vec::new(),
     };
 
     let mut targeted_dep_names: HashMap<String, DependencyNames> = HashMap::new();
+    let mut warnings = Vec::new();
 
     let package = self.crate_catalog_entry.package();
     for dep in &package.dependencies {
+      if dep.kind == DependencyKind::Development && !self.settings.render_dev_dependencies {
+        continue;
+      }
+
       // This shadow allow for dependencies with target restrictions to override where
       // to write data about itself.
       let mut dep_names = &mut default_dep_names;
@@ -762,14 +1608,20 @@ impl<'planner> CrateSubplanner<'planner> {
           }
         }
 
-        let (is_bazel_platform, matches_all_platforms) = is_bazel_supported_platform(&target_str);
+        let platform_support =
+          is_bazel_supported_platform(&target_str, &self.settings.supported_platform_triples);
         // If the target is not supported by Bazel, we ignore it
-        if !is_bazel_platform {
+        if !platform_support.is_supported {
+          warnings.push(PlanningWarning::UnsupportedPlatformDependency {
+            crate_name: package.name.clone(),
+            dependency_name: dep.name.clone(),
+            target: target_str,
+          });
           continue;
         }
 
         // In cases where the cfg target matches all platforms, we consider it a default dependency
-        if !matches_all_platforms {
+        if !platform_support.matches_all {
           // Ensure an entry is created for the 'conditional' dependency
           dep_names = match targeted_dep_names.get_mut(&target_str) {
             Some(targeted) => targeted,
@@ -781,7 +1633,7 @@ impl<'planner> CrateSubplanner<'planner> {
                   normal_dep_names: Vec::new(),
                   build_dep_names: Vec::new(),
                   dev_dep_names: Vec::new(),
-                  aliased_dep_names: HashMap::new(),
+                  aliased_dep_names: Vec::new(),
                 },
               );
               // This unwrap should be safe given the insert above
@@ -810,13 +1662,15 @@ impl<'planner> CrateSubplanner<'planner> {
 
       // Check if the dependency has been renamed
       if let Some(alias) = dep.rename.as_ref() {
-        dep_names
-          .aliased_dep_names
-          .insert(dep.name.clone(), alias.clone());
+        dep_names.aliased_dep_names.push(DependencyRename {
+          name: dep.name.clone(),
+          req: dep.req.clone(),
+          alias: alias.clone(),
+        });
       }
     }
 
-    Ok((default_dep_names, targeted_dep_names))
+    Ok((default_dep_names, targeted_dep_names, warnings))
   }
 
   /** Generates source details for internal crate. */
@@ -830,6 +1684,12 @@ impl<'planner> CrateSubplanner<'planner> {
           remote: id.url().to_string(),
           commit: id.precise().unwrap().to_owned(),
         }),
+      path_data: self
+        .crate_catalog_entry
+        .local_path()
+        .map(|path| PathSource {
+          path: path.clone(),
+        }),
     }
   }
 
@@ -843,10 +1703,11 @@ impl<'planner> CrateSubplanner<'planner> {
     &self,
     all_targets: &mut Vec<BuildableTarget>,
   ) -> Option<BuildableTarget> {
-    if !self
-      .crate_settings
-      .gen_buildrs
-      .unwrap_or(self.settings.default_gen_buildrs)
+    if self.crate_settings.skip_build_script
+      || !self
+        .crate_settings
+        .gen_buildrs
+        .unwrap_or(self.settings.default_gen_buildrs)
     {
       return None;
     }
@@ -898,7 +1759,11 @@ impl<'planner> CrateSubplanner<'planner> {
           name: target.name.clone(),
           path: package_root_path_str.clone(),
           kind: kind.clone(),
-          edition: target.edition.clone(),
+          edition: checks::check_edition(
+            &package.name,
+            &target.edition,
+            &self.settings.default_edition,
+          )?,
         });
       }
     }
@@ -956,35 +1821,95 @@ impl<'planner> CrateSubplanner<'planner> {
 
 mod checks {
   use std::{
-    collections::{HashMap, HashSet},
-    env, fs,
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
   };
 
   use anyhow::Result;
 
+  use cfg_expr::{expr::Predicate, Expression};
+
   use crate::{
+    bazel::parse_target_expression,
+    context::CrateContext,
     metadata::{Metadata, Package, PackageId},
-    planning::{CrateCatalogEntry, VENDOR_DIR},
-    settings::CrateSettingsPerVersion,
+    planning::{CrateCatalogEntry, PlanningWarning},
+    settings::{is_excluded_crate, CrateSettingsPerVersion, RazeSettings},
     util::{collect_up_to, RazeError},
   };
 
   // TODO(acmcarther): Consider including a switch to disable limiting
   const MAX_DISPLAYED_MISSING_VENDORED_CRATES: usize = 5;
   const MAX_DISPLAYED_MISSING_RESOLVE_PACKAGES: usize = 5;
+  const MAX_DISPLAYED_UNPARSEABLE_TARGETS: usize = 5;
+  const MAX_DISPLAYED_DANGLING_DEPENDENCIES: usize = 5;
+  const SUPPORTED_EDITIONS: &[&str] = &["2015", "2018", "2021", "2024"];
+  // The cfg options known to rustc, which custom cfg options closely resembling are typos of
+  const BUILTIN_CFG_OPTIONS: &[&str] = &[
+    "debug_assertions",
+    "feature",
+    "proc_macro",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_family",
+    "target_feature",
+    "target_os",
+    "target_pointer_width",
+    "target_vendor",
+    "test",
+    "unix",
+    "windows",
+  ];
+  const MAX_CFG_OPTION_TYPO_DISTANCE: usize = 2;
+
+  /**
+   * Yields the first unknown cfg option of the expression that resembles a builtin one, along with
+   * that builtin option.
+   */
+  fn find_cfg_option_typo(expression: &Expression) -> Option<(String, String)> {
+    expression.predicates().find_map(|predicate| {
+      let option = match predicate {
+        Predicate::Flag(option) => option,
+        Predicate::KeyValue {
+          key: option, ..
+        } => option,
+        _ => return None,
+      };
+      let (_, builtin) = BUILTIN_CFG_OPTIONS
+        .iter()
+        .map(|builtin| (strsim::levenshtein(option, builtin), builtin))
+        .filter(|(distance, _)| *distance <= MAX_CFG_OPTION_TYPO_DISTANCE)
+        .min()?;
+      Some((option.to_string(), builtin.to_string()))
+    })
+  }
 
-  // Verifies that all provided packages are vendored (in VENDOR_DIR relative to CWD)
+  // Verifies that all provided packages are vendored (following the `vendor_dir_template`)
   pub fn check_all_vendored(
     crate_catalog_entries: &[CrateCatalogEntry],
-    workspace_path: &String,
+    settings: &RazeSettings,
+    skipped_package_ids: &HashSet<PackageId>,
   ) -> Result<()> {
     let missing_package_ident_iter = crate_catalog_entries
       .iter()
       // Root does not need to be vendored -- usually it is a wrapper package.
       .filter(|p| !p.is_root())
       .filter(|p| !p.is_workspace_crate())
-      .filter(|p| fs::metadata(p.expected_vendored_path(workspace_path)).is_err())
-      .map(|p| p.package_ident.clone());
+      // Path dependencies are not vendored, they are built from their own directory
+      .filter(|p| p.local_path().is_none())
+      // Crates only needed by skipped dev dependencies are not rendered
+      .filter(|p| !skipped_package_ids.contains(&p.package().id))
+      // Excluded crates are provided some other way
+      .filter(|p| {
+        !is_excluded_crate(
+          &settings.excluded_crates,
+          &p.package().name,
+          &p.package().version.to_string(),
+        )
+      })
+      .map(|p| p.expected_vendored_path(settings))
+      .filter(|path| fs::metadata(path).is_err());
 
     let limited_missing_crates = collect_up_to(
       MAX_DISPLAYED_MISSING_VENDORED_CRATES,
@@ -996,17 +1921,11 @@ mod checks {
     }
 
     // Oops, missing some crates. Yield a nice message
-    let expected_full_path = env::current_dir()
-      .unwrap()
-      .join(format!("./{}", VENDOR_DIR));
-
     Err(
       RazeError::Planning {
         dependency_name_opt: None,
         message: format!(
-          "Failed to find expected vendored crates in {:?}: {:?}. Did you forget to run cargo \
-           vendor?",
-          expected_full_path.to_str(),
+          "Failed to find expected vendored crates at {:?}. Did you forget to run cargo vendor?",
           limited_missing_crates
         ),
       }
@@ -1014,6 +1933,59 @@ mod checks {
     )
   }
 
+  /**
+   * Verifies that the sha256 of a crate's archive matches the one expected by the user, if any.
+   *
+   * Yields the resulting sha256, which is the expected one if the actual one is unknown.
+   */
+  pub fn check_sha256_matches(
+    crate_name: &str,
+    actual_sha256_opt: Option<String>,
+    expected_sha256_opt: Option<&String>,
+  ) -> Result<Option<String>> {
+    match (actual_sha256_opt, expected_sha256_opt) {
+      (Some(actual), Some(expected)) if !actual.eq_ignore_ascii_case(expected) => Err(
+        RazeError::Planning {
+          dependency_name_opt: Some(crate_name.to_owned()),
+          message: format!(
+            "The sha256 of the crate's archive is {}, but {} was expected",
+            actual, expected
+          ),
+        }
+        .into(),
+      ),
+      (None, Some(expected)) => Ok(Some(expected.to_lowercase())),
+      (actual_sha256_opt, _) => Ok(actual_sha256_opt),
+    }
+  }
+
+  /**
+   * Verifies that the edition of a crate, or of one of its targets, is one Bazel can build.
+   *
+   * Yields the edition stripped of surrounding whitespace, or the `default_edition` if the
+   * metadata does not specify one.
+   */
+  pub fn check_edition(crate_name: &str, edition: &str, default_edition: &str) -> Result<String> {
+    let normalized_edition = match edition.trim() {
+      "" => default_edition.trim(),
+      edition => edition,
+    };
+    if SUPPORTED_EDITIONS.contains(&normalized_edition) {
+      return Ok(normalized_edition.to_owned());
+    }
+
+    Err(
+      RazeError::Planning {
+        dependency_name_opt: Some(crate_name.to_owned()),
+        message: format!(
+          "The edition {:?} is not supported, expected one of {:?}",
+          normalized_edition, SUPPORTED_EDITIONS
+        ),
+      }
+      .into(),
+    )
+  }
+
   pub fn check_resolve_matches_packages(metadata: &Metadata) -> Result<()> {
     let known_package_ids = metadata
       .packages
@@ -1052,6 +2024,172 @@ mod checks {
     )
   }
 
+  /**
+   * Verifies that every dependency of the planned crates is planned as well, unless it is among the
+   * `excluded_crates`, so that every rendered dependency label references an existing target.
+   */
+  pub fn check_dependencies_are_planned(
+    crate_contexts: &[CrateContext],
+    excluded_crates: &[String],
+  ) -> Result<()> {
+    let planned_crates = crate_contexts
+      .iter()
+      .map(|crate_context| (&crate_context.pkg_name, &crate_context.pkg_version))
+      .collect::<HashSet<_>>();
+
+    let mut dangling_dependencies = BTreeSet::new();
+    for crate_context in crate_contexts {
+      let dependency_contexts = std::iter::once(&crate_context.default_deps).chain(
+        crate_context
+          .targeted_deps
+          .iter()
+          .map(|targeted_deps| &targeted_deps.deps),
+      );
+      for deps in dependency_contexts {
+        let all_dependencies = deps
+          .dependencies
+          .iter()
+          .chain(deps.proc_macro_dependencies.iter())
+          .chain(deps.build_dependencies.iter())
+          .chain(deps.build_proc_macro_dependencies.iter())
+          .chain(deps.dev_dependencies.iter());
+        for dependency in all_dependencies {
+          // The labels of `additional_deps`, which have no version, are left for the user to check
+          if !dependency.version.is_empty()
+            && !planned_crates.contains(&(&dependency.name, &dependency.version))
+            && !is_excluded_crate(excluded_crates, &dependency.name, &dependency.version)
+          {
+            dangling_dependencies.insert(format!(
+              "{}-{} -> {}",
+              crate_context.pkg_name, crate_context.pkg_version, dependency.buildable_target
+            ));
+          }
+        }
+      }
+    }
+
+    let limited_dangling_dependencies = collect_up_to(
+      MAX_DISPLAYED_DANGLING_DEPENDENCIES,
+      dangling_dependencies.into_iter(),
+    );
+    if limited_dangling_dependencies.is_empty() {
+      return Ok(());
+    }
+
+    Err(
+      RazeError::Planning {
+        dependency_name_opt: None,
+        message: format!(
+          "Planned crates depend on crates missing from the plan, so their labels would reference \
+           no target: {:?}. {}",
+          limited_dangling_dependencies,
+          crate::util::PLEASE_FILE_A_BUG
+        ),
+      }
+      .into(),
+    )
+  }
+
+  /**
+   * Verifies that the target of every platform-specific dependency can be parsed.
+   *
+   * Unparseable targets would otherwise be treated like valid targets that no supported platform
+   * matches, silently dropping the dependency. Custom cfg options (eg: `cfg(loom)`) and values
+   * unknown to rustc (eg: an unknown `target_os`) are valid, and left to be dropped as unsupported.
+   */
+  pub fn check_dependency_targets(all_packages: &[Package]) -> Result<()> {
+    let unparseable_target_iter = all_packages.iter().flat_map(|package| {
+      package.dependencies.iter().filter_map(move |dep| {
+        let target = dep.target.as_ref()?.to_string();
+        let err = parse_target_expression(&target).err()?;
+        Some(format!(
+          "{}-{} (dependency {}): `{}` {}",
+          package.name, package.version, dep.name, target, err.reason
+        ))
+      })
+    });
+
+    let limited_unparseable_targets =
+      collect_up_to(MAX_DISPLAYED_UNPARSEABLE_TARGETS, unparseable_target_iter);
+
+    if limited_unparseable_targets.is_empty() {
+      return Ok(());
+    }
+
+    Err(
+      RazeError::Planning {
+        dependency_name_opt: None,
+        message: format!(
+          "Failed to parse the targets of platform-specific dependencies: {:?}. Please check the \
+           cfg expressions of the offending crates.",
+          limited_unparseable_targets
+        ),
+      }
+      .into(),
+    )
+  }
+
+  /**
+   * Warns about the targets of platform-specific dependencies using a cfg option that resembles a
+   * builtin one (eg: `cfg(unx)`).
+   *
+   * These are likely typos, but may also be custom cfg options of crates the user cannot edit, such
+   * as `cfg(tests)`, so they do not fail planning.
+   */
+  pub fn cfg_option_typo_warnings(all_packages: &[Package]) -> Vec<PlanningWarning> {
+    all_packages
+      .iter()
+      .flat_map(|package| {
+        package.dependencies.iter().filter_map(move |dep| {
+          let target = dep.target.as_ref()?.to_string();
+          let expression = parse_target_expression(&target).ok()?;
+          let (option, builtin) = find_cfg_option_typo(&expression)?;
+          Some(PlanningWarning::CfgOptionTypo {
+            crate_name: package.name.clone(),
+            dependency_name: dep.name.clone(),
+            target,
+            option,
+            builtin,
+          })
+        })
+      })
+      .collect()
+  }
+
+  /** Fails on any targeted dependency that was dropped for matching no supported platform. */
+  pub fn check_strict_platform_support(warnings: &[PlanningWarning]) -> Result<()> {
+    let unsupported_dependencies = warnings
+      .iter()
+      .filter_map(|warning| match warning {
+        PlanningWarning::UnsupportedPlatformDependency {
+          crate_name,
+          dependency_name,
+          target,
+        } => Some(format!(
+          "{} (dependency {}): `{}`",
+          crate_name, dependency_name, target
+        )),
+        _ => None,
+      })
+      .collect::<Vec<String>>();
+
+    if unsupported_dependencies.is_empty() {
+      return Ok(());
+    }
+
+    Err(
+      RazeError::Planning {
+        dependency_name_opt: None,
+        message: format!(
+          "Targeted dependencies match none of the supported platform triples:\n{}\nAdd a \
+           triple to `supported_platform_triples` or disable `strict_platform_support`.",
+          unsupported_dependencies.join("\n")
+        ),
+      }
+      .into(),
+    )
+  }
+
   pub fn warn_unused_settings(
     all_crate_settings: &HashMap<String, CrateSettingsPerVersion>,
     all_packages: &[Package],
@@ -1098,15 +2236,18 @@ mod checks {
 #[cfg(test)]
 mod tests {
   use crate::{
+    bazel::BazelRenderer,
     metadata::{CargoMetadataFetcher, Metadata, MetadataFetcher},
     planning::checks,
-    settings::testing as settings_testing,
+    rendering::{BuildRenderer, RenderDetails},
+    settings::{testing as settings_testing, FetchMode},
   };
 
   use super::*;
   use semver::Version;
   use std::fs::File;
   use std::io::Write;
+  use std::path::Path;
   use tempfile::TempDir;
 
   fn basic_toml() -> &'static str {
@@ -1120,6 +2261,11 @@ path = \"not_a_file.rs\"
     "
   }
 
+  // The checksum of the "test_dep" crate injected by `DependencyInjectingMetadataFetcher`, as
+  // recorded in `basic_lock`.
+  const TEST_DEP_CHECKSUM: &str =
+    "0b5b93c6d8e8b6e3d3f4cb3e5d4b1f9b7a0b6e3c4a7e6f1d2c3b4a5968778695";
+
   fn basic_lock() -> &'static str {
     "
 [[package]]
@@ -1127,6 +2273,12 @@ name = \"test\"
 version = \"0.0.1\"
 dependencies = [
 ]
+
+[[package]]
+name = \"test_dep\"
+version = \"0.0.1\"
+source = \"registry+https://github.com/rust-lang/crates.io-index\"
+checksum = \"0b5b93c6d8e8b6e3d3f4cb3e5d4b1f9b7a0b6e3c4a7e6f1d2c3b4a5968778695\"
     "
   }
 
@@ -1162,6 +2314,55 @@ dependencies = [
     make_workspace(basic_toml(), Some(basic_lock()))
   }
 
+  fn write_workspace_file(dir: &TempDir, path: &str, contents: &str) -> PathBuf {
+    let path = dir.path().join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    File::create(&path)
+      .unwrap()
+      .write_all(contents.as_bytes())
+      .unwrap();
+    path
+  }
+
+  // Writes a workspace whose root crate `test`, in `cargo/`, depends on the path dependency
+  // `local_dep`. The given TOML is appended to the manifests of `test` and `local_dep` respectively,
+  // which both end within their dependency or package table. Returns the path of the root manifest.
+  fn make_path_dependency_workspace(root_toml: &str, local_dep_toml: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let toml_path = write_workspace_file(
+      &dir,
+      "cargo/Cargo.toml",
+      &format!(
+        "
+    [package]
+    name = \"test\"
+    version = \"0.0.1\"
+
+    [lib]
+    path = \"not_a_file.rs\"
+
+    [dependencies]
+    local_dep = {{ path = \"../local_dep\" }}
+    {}",
+        root_toml
+      ),
+    );
+    write_workspace_file(
+      &dir,
+      "local_dep/Cargo.toml",
+      &format!(
+        "
+    [package]
+    name = \"local_dep\"
+    version = \"0.1.0\"
+    {}",
+        local_dep_toml
+      ),
+    );
+    write_workspace_file(&dir, "local_dep/src/lib.rs", "");
+    (dir, toml_path)
+  }
+
   #[test]
   #[allow(non_snake_case)]
   fn test__checks__check_resolve_matches_packages_fails_correctly() {
@@ -1185,78 +2386,317 @@ dependencies = [
     checks::check_resolve_matches_packages(&metadata).unwrap();
   }
 
-  // A wrapper around a MetadataFetcher which drops the
-  // resolved dependency graph from the acquired metadata.
-  #[derive(Default)]
-  struct ResolveDroppingMetadataFetcher {
-    fetcher: CargoMetadataFetcher,
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__check_dependencies_are_planned_fails_on_dangling_dependencies() {
+    let mut planned_build = plan_basic_workspace();
+    checks::check_dependencies_are_planned(&planned_build.crate_contexts, &[]).unwrap();
+
+    planned_build.crate_contexts[0]
+      .default_deps
+      .dependencies
+      .push(BuildableDependency {
+        name: "missing".to_owned(),
+        version: "1.0.0".to_owned(),
+        buildable_target: "//cargo/vendor/missing-1.0.0:missing".to_owned(),
+        is_proc_macro: false,
+      });
+    let err =
+      checks::check_dependencies_are_planned(&planned_build.crate_contexts, &[]).unwrap_err();
+    assert!(matches!(
+      err.downcast_ref::<RazeError>(),
+      Some(RazeError::Planning { .. })
+    ));
+    assert!(err
+      .to_string()
+      .contains("//cargo/vendor/missing-1.0.0:missing"));
+
+    // Excluded crates are expected to be missing
+    checks::check_dependencies_are_planned(&planned_build.crate_contexts, &["missing".to_owned()])
+      .unwrap();
   }
 
-  impl MetadataFetcher for ResolveDroppingMetadataFetcher {
-    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
-      let mut metadata = self.fetcher.fetch_metadata(&files)?;
-      assert!(metadata.resolve.is_some());
-      metadata.resolve = None;
-      Ok(metadata)
-    }
+  fn metadata_with_targeted_dependency(target: &str) -> (TempDir, Metadata) {
+    let (temp_dir, files) = make_basic_workspace();
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut metadata = fetcher.fetch_metadata(&files).unwrap();
+
+    metadata.packages[0].dependencies = vec![serde_json::from_value(serde_json::json!({
+      "name": "targeted_dep",
+      "source": null,
+      "req": "*",
+      "kind": null,
+      "optional": false,
+      "uses_default_features": true,
+      "features": [],
+      "target": target,
+      "rename": null,
+      "registry": null,
+    }))
+    .unwrap()];
+
+    (temp_dir, metadata)
   }
 
   #[test]
-  fn test_plan_build_missing_resolve_returns_error() {
-    let (_temp_dir, files) = make_basic_workspace();
-    let mut fetcher = ResolveDroppingMetadataFetcher::default();
-    let mut planner = BuildPlannerImpl::new(&mut fetcher);
-    let res = planner.plan_build(
-      &settings_testing::dummy_raze_settings(),
-      files,
-      Some(PlatformDetails::new(
-        "some_target_triple".to_owned(),
-        Vec::new(), /* attrs */
-      )),
+  #[allow(non_snake_case)]
+  fn test__checks__check_dependency_targets_fails_on_unparseable_cfg() {
+    let (_temp_dir, metadata) = metadata_with_targeted_dependency("cfg(all(unix)");
+
+    let err = checks::check_dependency_targets(&metadata.packages).unwrap_err();
+    let message = err.to_string();
+    assert!(
+      message.contains("test-0.0.1 (dependency targeted_dep)"),
+      "{}",
+      message
     );
-    assert!(res.is_err());
+    assert!(message.contains("`cfg(all(unix)`"), "{}", message);
   }
 
-  // A wrapper around a MetadataFetcher which drops the
-  // list of packages from the acquired metadata.
-  #[derive(Default)]
-  struct PackageDroppingMetadataFetcher {
-    fetcher: CargoMetadataFetcher,
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__cfg_option_typo_warnings_warns_on_unknown_cfg_options() {
+    for (target, option, builtin) in &[
+      ("cfg(unx)", "unx", "unix"),
+      ("cfg(all(unix, not(tset)))", "tset", "test"),
+    ] {
+      let (_temp_dir, metadata) = metadata_with_targeted_dependency(target);
+
+      // Custom cfg options may resemble builtin ones, so they do not fail planning
+      checks::check_dependency_targets(&metadata.packages).unwrap();
+      assert_eq!(
+        checks::cfg_option_typo_warnings(&metadata.packages),
+        vec![PlanningWarning::CfgOptionTypo {
+          crate_name: "test".to_owned(),
+          dependency_name: "targeted_dep".to_owned(),
+          target: target.to_string(),
+          option: option.to_string(),
+          builtin: builtin.to_string(),
+        }]
+      );
+    }
+
+    let (_temp_dir, metadata) = metadata_with_targeted_dependency("cfg(loom)");
+    assert_eq!(
+      checks::cfg_option_typo_warnings(&metadata.packages),
+      Vec::new()
+    );
   }
 
-  impl MetadataFetcher for PackageDroppingMetadataFetcher {
-    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
-      let mut metadata = self.fetcher.fetch_metadata(&files)?;
-      metadata.packages.clear();
-      Ok(metadata)
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__check_dependency_targets_accepts_unsupported_cfg() {
+    for target in &[
+      "cfg(target_os = \"redox\")",
+      "cfg(loom)",
+      "cfg(not(miri))",
+      "cfg(target_has_atomic = \"64\")",
+      "cfg(target_abi = \"eabihf\")",
+      "x86_64-unknown-redox",
+    ] {
+      let (_temp_dir, metadata) = metadata_with_targeted_dependency(target);
+
+      checks::check_dependency_targets(&metadata.packages).unwrap();
     }
   }
 
   #[test]
-  fn test_plan_build_missing_package_in_metadata() {
-    let (_temp_dir, files) = make_basic_workspace();
-    let mut fetcher = PackageDroppingMetadataFetcher::default();
-    let mut planner = BuildPlannerImpl::new(&mut fetcher);
-    let planned_build_res = planner.plan_build(
-      &settings_testing::dummy_raze_settings(),
-      files,
-      Some(PlatformDetails::new(
-        "some_target_triple".to_owned(),
-        Vec::new(), /* attrs */
-      )),
+  #[allow(non_snake_case)]
+  fn test__checks__check_sha256_matches_works_correctly() {
+    let fixture_sha256 =
+      util::sha256_of_file(Path::new("test_fixtures/test-crate-0.1.0.crate")).unwrap();
+
+    assert_eq!(
+      checks::check_sha256_matches("test-crate", Some(fixture_sha256.clone()), None).unwrap(),
+      Some(fixture_sha256.clone())
+    );
+    assert_eq!(
+      checks::check_sha256_matches(
+        "test-crate",
+        Some(fixture_sha256.clone()),
+        Some(&fixture_sha256.to_uppercase())
+      )
+      .unwrap(),
+      Some(fixture_sha256.clone())
     );
+    assert_eq!(
+      checks::check_sha256_matches("test-crate", None, Some(&fixture_sha256)).unwrap(),
+      Some(fixture_sha256.clone())
+    );
+  }
 
-    println!("{:#?}", planned_build_res);
-    assert!(planned_build_res.is_err());
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__check_sha256_matches_fails_on_mismatch() {
+    let fixture_sha256 =
+      util::sha256_of_file(Path::new("test_fixtures/test-crate-0.1.0.crate")).unwrap();
+    let other_sha256 = "0".repeat(64);
+
+    assert!(
+      checks::check_sha256_matches("test-crate", Some(fixture_sha256), Some(&other_sha256))
+        .is_err()
+    );
   }
 
   #[test]
-  fn test_plan_build_minimum_workspace() {
-    let (_temp_dir, files) = make_basic_workspace();
-    let mut fetcher = CargoMetadataFetcher::default();
-    let mut planner = BuildPlannerImpl::new(&mut fetcher);
-    let planned_build_res = planner.plan_build(
-      &settings_testing::dummy_raze_settings(),
+  #[allow(non_snake_case)]
+  fn test__checks__check_edition_normalizes_supported_editions() {
+    assert_eq!(
+      checks::check_edition("test-crate", "2018", "2015").unwrap(),
+      "2018"
+    );
+    assert_eq!(
+      checks::check_edition("test-crate", "2015 ", "2018").unwrap(),
+      "2015"
+    );
+  }
+
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__check_edition_defaults_missing_editions() {
+    assert_eq!(
+      checks::check_edition("test-crate", "", "2021").unwrap(),
+      "2021"
+    );
+    assert!(checks::check_edition("test-crate", "", "2027").is_err());
+  }
+
+  #[test]
+  #[allow(non_snake_case)]
+  fn test__checks__check_edition_fails_on_unsupported_editions() {
+    for edition in &["2027", "18"] {
+      let err = checks::check_edition("test-crate", edition, "2015").unwrap_err();
+      assert!(matches!(
+        err.downcast_ref::<RazeError>(),
+        Some(RazeError::Planning { .. })
+      ));
+    }
+  }
+
+  // A wrapper around a MetadataFetcher which drops the
+  // editions of the packages and their targets from the acquired metadata.
+  #[derive(Default)]
+  struct EditionDroppingMetadataFetcher {
+    fetcher: CargoMetadataFetcher,
+  }
+
+  impl MetadataFetcher for EditionDroppingMetadataFetcher {
+    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+      let mut metadata = self.fetcher.fetch_metadata(files)?;
+      for package in metadata.packages.iter_mut() {
+        package.edition = String::new();
+        for target in package.targets.iter_mut() {
+          target.edition = String::new();
+        }
+      }
+      Ok(metadata)
+    }
+  }
+
+  #[test]
+  fn test_plan_build_applies_the_default_edition() {
+    let (_dir, toml_path) = make_path_dependency_workspace("", "");
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.default_edition = "2018".to_owned();
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = EditionDroppingMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert_eq!(local_dep.edition, "2018");
+    assert!(local_dep
+      .targets
+      .iter()
+      .all(|target| target.edition == "2018"));
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(rendered.contains("    edition = \"2018\",\n"));
+    assert!(!rendered.contains("edition = \"\""));
+  }
+
+  // A wrapper around a MetadataFetcher which drops the
+  // resolved dependency graph from the acquired metadata.
+  #[derive(Default)]
+  struct ResolveDroppingMetadataFetcher {
+    fetcher: CargoMetadataFetcher,
+  }
+
+  impl MetadataFetcher for ResolveDroppingMetadataFetcher {
+    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+      let mut metadata = self.fetcher.fetch_metadata(&files)?;
+      assert!(metadata.resolve.is_some());
+      metadata.resolve = None;
+      Ok(metadata)
+    }
+  }
+
+  #[test]
+  fn test_plan_build_missing_resolve_returns_error() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = ResolveDroppingMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let res = planner.plan_build(
+      &settings_testing::dummy_raze_settings(),
+      files,
+      Some(PlatformDetails::new(
+        "some_target_triple".to_owned(),
+        Vec::new(), /* attrs */
+      )),
+    );
+    assert!(res.is_err());
+  }
+
+  // A wrapper around a MetadataFetcher which drops the
+  // list of packages from the acquired metadata.
+  #[derive(Default)]
+  struct PackageDroppingMetadataFetcher {
+    fetcher: CargoMetadataFetcher,
+  }
+
+  impl MetadataFetcher for PackageDroppingMetadataFetcher {
+    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+      let mut metadata = self.fetcher.fetch_metadata(&files)?;
+      metadata.packages.clear();
+      Ok(metadata)
+    }
+  }
+
+  #[test]
+  fn test_plan_build_missing_package_in_metadata() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = PackageDroppingMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build_res = planner.plan_build(
+      &settings_testing::dummy_raze_settings(),
+      files,
+      Some(PlatformDetails::new(
+        "some_target_triple".to_owned(),
+        Vec::new(), /* attrs */
+      )),
+    );
+
+    println!("{:#?}", planned_build_res);
+    assert!(planned_build_res.is_err());
+  }
+
+  #[test]
+  fn test_plan_build_minimum_workspace() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build_res = planner.plan_build(
+      &settings_testing::dummy_raze_settings(),
       files,
       Some(PlatformDetails::new(
         "some_target_triple".to_owned(),
@@ -1308,6 +2748,9 @@ dependencies = [
         repr: name_id.to_string(),
       };
       new_package.version = Version::new(0, 0, 1);
+      new_package.source = Some(serde_json::from_str(
+        "\"registry+https://github.com/rust-lang/crates.io-index\"",
+      )?);
       metadata.packages.push(new_package);
 
       Ok(metadata)
@@ -1346,97 +2789,324 @@ dependencies = [
     );
   }
 
+  fn plan_basic_workspace() -> PlannedBuild {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = DependencyInjectingMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    planner
+      .plan_build(
+        &settings_testing::dummy_raze_settings(),
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap()
+  }
+
   #[test]
-  fn test_plan_build_verifies_vendored_state() {
+  fn test_merge_collapses_identical_crates() {
+    let mut other = plan_basic_workspace();
+    let mut other_crate = other.crate_contexts[0].clone();
+    other_crate.pkg_name = "other_dep".to_owned();
+    other.crate_contexts.push(other_crate);
+
+    let merged = merge(plan_basic_workspace(), other).unwrap();
+
+    let crate_names: Vec<_> = merged
+      .crate_contexts
+      .iter()
+      .map(|ctx| ctx.pkg_name.as_str())
+      .collect();
+    assert_eq!(crate_names, vec!["test_dep", "other_dep"]);
+  }
+
+  #[test]
+  fn test_merge_fails_on_conflicting_crates() {
+    let mut other = plan_basic_workspace();
+    other.crate_contexts[0].features.push("extra".to_owned());
+
+    let err = merge(plan_basic_workspace(), other).unwrap_err();
+    assert!(err.to_string().contains("test_dep"), "{}", err);
+    assert!(err.to_string().contains("different features"), "{}", err);
+  }
+
+  #[test]
+  fn test_merge_keeps_crates_depended_on_directly_by_either_build() {
+    let mut other = plan_basic_workspace();
+    other.crate_contexts[0].is_root_dependency = false;
+    other.crate_contexts[0].workspace_path_to_crate = "//other/cargo".to_owned();
+
+    let merged = merge(other, plan_basic_workspace()).unwrap();
+    assert_eq!(merged.crate_contexts.len(), 1);
+    assert!(merged.crate_contexts[0].is_root_dependency);
+
+    let mut other = plan_basic_workspace();
+    other.crate_contexts[0].is_root_dependency = false;
+    let merged = merge(plan_basic_workspace(), other).unwrap();
+    assert!(merged.crate_contexts[0].is_root_dependency);
+  }
+
+  #[test]
+  fn test_merge_fails_on_different_workspace_contexts() {
+    let mut other = plan_basic_workspace();
+    other.workspace_context.gen_workspace_prefix = "other_prefix".to_owned();
+
+    assert!(merge(plan_basic_workspace(), other).is_err());
+  }
+
+  #[test]
+  fn test_plan_build_serializes_to_json() {
     let (_temp_dir, files) = make_basic_workspace();
     let mut fetcher = DependencyInjectingMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner
+      .plan_build(
+        &settings_testing::dummy_raze_settings(),
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&planned_build.to_json().unwrap()).unwrap();
+    assert_eq!(json["workspace_context"]["workspace_path"], "//cargo");
+    assert_eq!(json["warnings"], serde_json::json!([]));
+
+    let dep = &json["crate_contexts"][0];
+    assert_eq!(dep["pkg_name"], "test_dep");
+    assert_eq!(dep["pkg_version"], "0.0.1");
+    assert_eq!(dep["is_root_dependency"], true);
+    for field in &["default_deps", "targeted_deps", "targets", "license"] {
+      assert!(dep.get(field).is_some(), "missing field {}", field);
+    }
+  }
 
+  #[test]
+  fn test_plan_build_applies_gen_workspace_prefix() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = DependencyInjectingMetadataFetcher::default();
     let mut settings = settings_testing::dummy_raze_settings();
-    settings.genmode = GenMode::Vendored;
+    settings.gen_workspace_prefix = "my_prefix".to_owned();
+
     let mut planner = BuildPlannerImpl::new(&mut fetcher);
-    let planned_build_res = planner.plan_build(
-      &settings,
-      files,
-      Some(PlatformDetails::new(
-        "some_target_triple".to_owned(),
-        Vec::new(), /* attrs */
-      )),
-    );
+    let planned_build = planner
+      .plan_build(
+        &settings,
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
 
-    println!("{:#?}", planned_build_res);
-    assert!(planned_build_res.is_err());
+    assert_eq!(
+      planned_build.workspace_context.gen_workspace_prefix,
+      "my_prefix"
+    );
+    assert_eq!(
+      planned_build.crate_contexts[0].workspace_path_to_crate,
+      "@my_prefix__test_dep__0_0_1//"
+    );
   }
 
-  // A wrapper around a MetadataFetcher which injects a fake
-  // package into the workspace.
+  // A wrapper around a MetadataFetcher which injects a fake dependency, resolved from an
+  // alternative registry, into the acquired metadata.
   #[derive(Default)]
-  struct WorkspaceCrateMetadataFetcher {
-    fetcher: CargoMetadataFetcher,
+  struct AlternativeRegistryMetadataFetcher {
+    fetcher: DependencyInjectingMetadataFetcher,
   }
 
-  impl MetadataFetcher for WorkspaceCrateMetadataFetcher {
+  impl MetadataFetcher for AlternativeRegistryMetadataFetcher {
     fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
-      let mut metadata = self.fetcher.fetch_metadata(&files)?;
-
-      // Phase 1: Create a workspace package, add it to the packages list.
-
-      let name = "ws_crate_dep";
-      let name_id = "ws_crate_dep_id";
-      let id = PackageId {
-        repr: name_id.to_string(),
-      };
-      let mut new_package = metadata.packages[0].clone();
-      new_package.name = name.to_string();
-      new_package.id = id.clone();
-      new_package.version = Version::new(0, 0, 1);
-      metadata.packages.push(new_package);
-
-      // Phase 2: Add the workspace packages to the workspace members.
+      let mut metadata = self.fetcher.fetch_metadata(files)?;
 
-      metadata.workspace_members.push(id);
+      let new_package = metadata
+        .packages
+        .iter_mut()
+        .find(|package| package.name == "test_dep")
+        .unwrap();
+      new_package.source = Some(serde_json::from_str(
+        "\"registry+https://my-registry.example.com/index\"",
+      )?);
 
       Ok(metadata)
     }
   }
 
   #[test]
-  fn test_plan_build_ignores_workspace_crates() {
+  fn test_plan_build_uses_alternative_registry_url() {
     let (_temp_dir, files) = make_basic_workspace();
-    let mut fetcher = WorkspaceCrateMetadataFetcher::default();
+    let mut fetcher = AlternativeRegistryMetadataFetcher::default();
     let mut settings = settings_testing::dummy_raze_settings();
-    settings.genmode = GenMode::Vendored;
+    settings.registries.insert(
+      "https://my-registry.example.com/index".to_owned(),
+      "https://my-registry.example.com/api/v1/crates/{crate}/{version}/download".to_owned(),
+    );
 
     let mut planner = BuildPlannerImpl::new(&mut fetcher);
-    // N.B. This will fail if we don't correctly ignore workspace crates.
-    let planned_build_res = planner.plan_build(
-      &settings,
-      files,
-      Some(PlatformDetails::new(
-        "some_target_triple".to_owned(),
-        Vec::new(), /* attrs */
-      )),
+    let planned_build = planner
+      .plan_build(
+        &settings,
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
+
+    assert_eq!(planned_build.crate_contexts.len(), 1);
+    assert_eq!(
+      planned_build.crate_contexts[0].registry_url,
+      "https://my-registry.example.com/api/v1/crates/test_dep/0.0.1/download"
+    );
+    assert_eq!(
+      planned_build.crate_contexts[0].sha256,
+      Some(TEST_DEP_CHECKSUM.to_owned())
     );
-    assert!(planned_build_res.unwrap().crate_contexts.is_empty());
   }
 
   #[test]
-  fn test_plan_build_produces_aliased_dependencies() {
-    let toml_file = "
-    [package]
-    name = \"advanced_toml\"
-    version = \"0.1.0\"
+  fn test_plan_build_only_requires_alternative_registries_in_remote_mode() {
+    let (temp_dir, files) = make_basic_workspace();
+    let mut fetcher = AlternativeRegistryMetadataFetcher::default();
+    let mut settings = settings_testing::dummy_raze_settings();
+    let plan = |fetcher: &mut AlternativeRegistryMetadataFetcher, settings: &RazeSettings| {
+      let files = CargoWorkspaceFiles {
+        toml_path: files.toml_path.clone(),
+        lock_path_opt: files.lock_path_opt.clone(),
+      };
+      BuildPlannerImpl::new(fetcher).plan_build(settings, files, None)
+    };
 
-    [lib]
-    path = \"not_a_file.rs\"
+    let err = plan(&mut fetcher, &settings).unwrap_err();
+    assert!(err
+      .to_string()
+      .contains("https://my-registry.example.com/index"));
 
-    [dependencies]
-    actix-web = \"2.0.0\"
-    actix-rt = \"1.0.0\"
-        ";
-    let (_temp_dir, files) = make_workspace(toml_file, None);
-    let mut fetcher = WorkspaceCrateMetadataFetcher::default();
+    // Vendored crates are not downloaded from their registry
+    fs::create_dir_all(temp_dir.path().join("vendor/test_dep-0.0.1")).unwrap();
+    settings.genmode = GenMode::Vendored;
+    settings.vendor_dir_template = temp_dir
+      .path()
+      .join("vendor/{crate}-{version}")
+      .display()
+      .to_string();
+    let planned_build = plan(&mut fetcher, &settings).unwrap();
+    assert_eq!(planned_build.crate_contexts.len(), 1);
+  }
+
+  #[test]
+  fn test_plan_build_fetches_crates_through_the_registry_url_pattern() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = DependencyInjectingMetadataFetcher::default();
     let mut settings = settings_testing::dummy_raze_settings();
     settings.genmode = GenMode::Remote;
+    settings.registry = "https://mirror.example.com/crates/{crate}-{version}.crate".to_owned();
+
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner
+      .plan_build(
+        &settings,
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
+
+    let bzl_fetch = BazelRenderer::new()
+      .render_bzl_fetch(
+        &planned_build.workspace_context,
+        &planned_build.crate_contexts,
+      )
+      .unwrap();
+    assert!(bzl_fetch.contains("url = \"https://mirror.example.com/crates/test_dep-0.0.1.crate\","));
+  }
+
+  #[test]
+  fn test_plan_build_fails_for_unknown_alternative_registry() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = AlternativeRegistryMetadataFetcher::default();
+
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build_res = planner.plan_build(
+      &settings_testing::dummy_raze_settings(),
+      files,
+      Some(PlatformDetails::new(
+        "some_target_triple".to_owned(),
+        Vec::new(), /* attrs */
+      )),
+    );
+
+    assert!(planned_build_res.is_err());
+  }
+
+  #[test]
+  fn test_plan_build_verifies_vendored_state() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = DependencyInjectingMetadataFetcher::default();
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.genmode = GenMode::Vendored;
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build_res = planner.plan_build(
+      &settings,
+      files,
+      Some(PlatformDetails::new(
+        "some_target_triple".to_owned(),
+        Vec::new(), /* attrs */
+      )),
+    );
+
+    println!("{:#?}", planned_build_res);
+    assert!(planned_build_res.is_err());
+  }
+
+  // A wrapper around a MetadataFetcher which injects a fake
+  // package into the workspace.
+  #[derive(Default)]
+  struct WorkspaceCrateMetadataFetcher {
+    fetcher: CargoMetadataFetcher,
+  }
+
+  impl MetadataFetcher for WorkspaceCrateMetadataFetcher {
+    fn fetch_metadata(&mut self, files: &CargoWorkspaceFiles) -> Result<Metadata> {
+      let mut metadata = self.fetcher.fetch_metadata(&files)?;
+
+      // Phase 1: Create a workspace package, add it to the packages list.
+
+      let name = "ws_crate_dep";
+      let name_id = "ws_crate_dep_id";
+      let id = PackageId {
+        repr: name_id.to_string(),
+      };
+      let mut new_package = metadata.packages[0].clone();
+      new_package.name = name.to_string();
+      new_package.id = id.clone();
+      new_package.version = Version::new(0, 0, 1);
+      metadata.packages.push(new_package);
+
+      // Phase 2: Add the workspace packages to the workspace members.
+
+      metadata.workspace_members.push(id);
+
+      Ok(metadata)
+    }
+  }
+
+  #[test]
+  fn test_plan_build_ignores_workspace_crates() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = WorkspaceCrateMetadataFetcher::default();
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.genmode = GenMode::Vendored;
 
     let mut planner = BuildPlannerImpl::new(&mut fetcher);
     // N.B. This will fail if we don't correctly ignore workspace crates.
@@ -1448,35 +3118,1207 @@ dependencies = [
         Vec::new(), /* attrs */
       )),
     );
+    assert!(planned_build_res.unwrap().crate_contexts.is_empty());
+  }
 
-    let crates_with_aliased_deps: Vec<CrateContext> = planned_build_res
-      .unwrap()
+  #[test]
+  fn test_vendored_paths_follow_the_vendor_dir_template() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut package = fetcher.fetch_metadata(&files).unwrap().packages[0].clone();
+    package.name = "test-dep".to_owned();
+    for target in package.targets.iter_mut() {
+      target.name = "test-dep".to_owned();
+    }
+    let entry = CrateCatalogEntry::new(&package, false, true, false, None);
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.genmode = GenMode::Vendored;
+    assert_eq!(
+      entry.local_build_path(&settings, "BUILD"),
+      "vendor/test-dep-0.0.1/BUILD"
+    );
+
+    settings.vendor_dir_template = "third_party/rust/{crate}/{version}".to_owned();
+    assert_eq!(
+      entry.local_build_path(&settings, "BUILD.bazel"),
+      "third_party/rust/test-dep/0.0.1/BUILD.bazel"
+    );
+    assert_eq!(
+      entry.workspace_path(&settings),
+      "//cargo/third_party/rust/test-dep/0.0.1"
+    );
+    assert_eq!(
+      entry.workspace_path_and_default_target(&settings),
+      "//cargo/third_party/rust/test-dep/0.0.1:test_dep"
+    );
+    assert!(entry
+      .expected_vendored_path(&settings)
+      .ends_with("cargo/third_party/rust/test-dep/0.0.1"));
+  }
+
+  #[test]
+  fn test_vendored_dependencies_are_referenced_by_workspace_labels() {
+    let (_temp_dir, files) = make_basic_workspace();
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut package = fetcher.fetch_metadata(&files).unwrap().packages[0].clone();
+    package.name = "test-dep".to_owned();
+    for target in package.targets.iter_mut() {
+      target.name = "test-dep".to_owned();
+    }
+    let entry = CrateCatalogEntry::new(&package, false, true, false, None);
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.genmode = GenMode::Vendored;
+    assert_eq!(
+      entry.workspace_path_and_default_target(&settings),
+      "//cargo/vendor/test-dep-0.0.1:test_dep"
+    );
+
+    settings.workspace_path = "//".to_owned();
+    assert_eq!(entry.workspace_path(&settings), "//vendor/test-dep-0.0.1");
+    assert_eq!(
+      entry.workspace_path_and_default_target(&settings),
+      "//vendor/test-dep-0.0.1:test_dep"
+    );
+
+    // Only remote crates are referenced through their external repository
+    settings.genmode = GenMode::Remote;
+    assert_eq!(
+      entry.workspace_path_and_default_target(&settings),
+      "@raze_test__test_dep__0_0_1//:test_dep"
+    );
+  }
+
+  #[test]
+  fn test_plan_build_produces_path_dependencies() {
+    let (_dir, toml_path) = make_path_dependency_workspace("", "");
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner
+      .plan_build(
+        &settings_testing::dummy_raze_settings(),
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
+
+    assert_eq!(planned_build.crate_contexts.len(), 1);
+    let dep = &planned_build.crate_contexts[0];
+    assert_eq!(
+      dep.source_details.path_data,
+      Some(PathSource {
+        path: "../local_dep".to_owned(),
+      })
+    );
+    assert_eq!(dep.workspace_path_to_crate, "//local_dep");
+    assert_eq!(dep.expected_build_path, "../local_dep/BUILD");
+    assert_eq!(dep.sha256, None);
+  }
+
+  #[test]
+  fn test_plan_build_warns_about_unsupported_platform_dependencies() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(target_os = \"redox\")'.dependencies]
+    redox_dep = { path = \"../redox_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "redox_dep/Cargo.toml",
+      "
+    [package]
+    name = \"redox_dep\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("redox_dep/src/lib.rs", "");
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    // N.B. Without a `target`, dependencies are planned for every supported platform
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    assert_eq!(
+      planned_build.warnings,
+      vec![PlanningWarning::UnsupportedPlatformDependency {
+        crate_name: "local_dep".to_owned(),
+        dependency_name: "redox_dep".to_owned(),
+        target: "cfg(target_os = \"redox\")".to_owned(),
+      }]
+    );
+
+    let local_dep = planned_build
       .crate_contexts
-      .into_iter()
-      .filter(|krate| krate.default_deps.aliased_dependencies.len() != 0)
-      .collect();
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert!(local_dep.targeted_deps.is_empty());
+  }
 
-    // Vec length shouldn't be 0
-    assert!(
-      crates_with_aliased_deps.len() != 0,
-      "Crates with aliased dependencies is 0"
+  #[test]
+  fn test_plan_build_does_not_warn_about_dependencies_excluded_by_targets() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(windows)'.dependencies]
+    windows_dep = { path = \"../windows_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "windows_dep/Cargo.toml",
+      "
+    [package]
+    name = \"windows_dep\"
+    version = \"0.1.0\"
+    ",
     );
+    write_file("windows_dep/src/lib.rs", "");
 
-    // Find the actix-web crate
-    let actix_web_position = crates_with_aliased_deps
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    // The dependency is supported, but excluded by the `targets` whitelist
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+    settings.targets = Some(vec!["x86_64-unknown-linux-gnu".to_owned()]);
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    assert_eq!(planned_build.warnings, Vec::new());
+    let local_dep = planned_build
+      .crate_contexts
       .iter()
-      .position(|krate| krate.pkg_name == "actix-http");
-    assert!(actix_web_position.is_some());
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert!(local_dep.targeted_deps.is_empty());
+  }
 
-    // Get crate context using computed position
-    let actix_http_context = crates_with_aliased_deps[actix_web_position.unwrap()].clone();
+  #[test]
+  fn test_plan_build_drops_dependencies_on_cfg_options_unknown_to_cfg_expr() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(target_has_atomic = \"64\")'.dependencies]
+    atomic_dep = { path = \"../atomic_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "atomic_dep/Cargo.toml",
+      "
+    [package]
+    name = \"atomic_dep\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("atomic_dep/src/lib.rs", "");
 
-    assert!(actix_http_context.default_deps.aliased_dependencies.len() == 1);
-    assert!(
-      actix_http_context.default_deps.aliased_dependencies[0].target
-        == "@raze_test__failure__0_1_8//:failure"
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    // The target is valid, but cannot be matched against the supported platforms
+    assert_eq!(
+      planned_build.warnings,
+      vec![PlanningWarning::UnsupportedPlatformDependency {
+        crate_name: "local_dep".to_owned(),
+        dependency_name: "atomic_dep".to_owned(),
+        target: "cfg(target_has_atomic = \"64\")".to_owned(),
+      }]
     );
-    assert!(actix_http_context.default_deps.aliased_dependencies[0].alias == "fail_ure");
+  }
+
+  #[test]
+  fn test_plan_build_drops_bare_metal_dependencies_and_keeps_their_negation() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(target_os = \"none\")'.dependencies]
+    bare_metal_dep = { path = \"../bare_metal_dep\" }
+
+    [target.'cfg(not(target_os = \"none\"))'.dependencies]
+    hosted_dep = { path = \"../hosted_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    for dep in &["bare_metal_dep", "hosted_dep"] {
+      write_file(
+        &format!("{}/Cargo.toml", dep),
+        &format!(
+          "
+    [package]
+    name = \"{}\"
+    version = \"0.1.0\"
+    ",
+          dep
+        ),
+      );
+      write_file(&format!("{}/src/lib.rs", dep), "");
+    }
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    assert_eq!(
+      planned_build.warnings,
+      vec![PlanningWarning::UnsupportedPlatformDependency {
+        crate_name: "local_dep".to_owned(),
+        dependency_name: "bare_metal_dep".to_owned(),
+        target: "cfg(target_os = \"none\")".to_owned(),
+      }]
+    );
+
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert!(local_dep.targeted_deps.is_empty());
+    let dependency_names: Vec<_> = local_dep
+      .default_deps
+      .dependencies
+      .iter()
+      .map(|dep| dep.name.as_str())
+      .collect();
+    assert_eq!(dependency_names, vec!["hosted_dep"]);
+  }
+
+  #[test]
+  fn test_plan_build_orders_targeted_dependencies_by_condition() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(target_arch = \"wasm32\")'.dependencies]
+    wasm_dep = { path = \"../wasm_dep\" }
+
+    [target.'cfg(windows)'.dependencies]
+    windows_dep = { path = \"../windows_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    for dep in &["wasm_dep", "windows_dep"] {
+      write_file(
+        &format!("{}/Cargo.toml", dep),
+        &format!(
+          "
+    [package]
+    name = \"{}\"
+    version = \"0.1.0\"
+    ",
+          dep
+        ),
+      );
+      write_file(&format!("{}/src/lib.rs", dep), "");
+    }
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+
+    // "i686-pc-windows-gnu" sorts before "wasm32-unknown-unknown", unlike their cfg expressions
+    let targets: Vec<_> = local_dep
+      .targeted_deps
+      .iter()
+      .map(|targeted_dep| targeted_dep.target.as_str())
+      .collect();
+    assert_eq!(
+      targets,
+      vec!["cfg(windows)", "cfg(target_arch = \"wasm32\")"]
+    );
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    let windows_select = rendered.find("# cfg(windows)").unwrap();
+    let wasm_select = rendered.find("# cfg(target_arch = \"wasm32\")").unwrap();
+    assert!(windows_select < wasm_select);
+  }
+
+  #[test]
+  fn test_plan_build_strict_platform_support_fails_on_unsupported_dependencies() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [target.'cfg(target_os = \"redox\")'.dependencies]
+    redox_dep = { path = \"../redox_dep\" }
+
+    [target.'cfg(windows)'.dependencies]
+    windows_dep = { path = \"../windows_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    for dep in ["redox_dep", "windows_dep"].iter() {
+      write_file(
+        &format!("{}/Cargo.toml", dep),
+        &format!(
+          "
+    [package]
+    name = \"{}\"
+    version = \"0.1.0\"
+    ",
+          dep
+        ),
+      );
+      write_file(&format!("{}/src/lib.rs", dep), "");
+    }
+
+    let files = || CargoWorkspaceFiles {
+      toml_path: toml_path.clone(),
+      lock_path_opt: None,
+    };
+    // N.B. Without a `target`, dependencies are planned for every supported platform
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.target = None;
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    assert!(planner.plan_build(&settings, files(), None).is_ok());
+
+    settings.strict_platform_support = true;
+    let message = planner
+      .plan_build(&settings, files(), None)
+      .unwrap_err()
+      .to_string();
+    assert!(message.contains("\nlocal_dep (dependency redox_dep): `cfg(target_os = \"redox\")`\n"));
+
+    // Dependencies excluded by the `targets` whitelist are not reported
+    settings.targets = Some(vec!["x86_64-unknown-linux-gnu".to_owned()]);
+    let message = planner
+      .plan_build(&settings, files(), None)
+      .unwrap_err()
+      .to_string();
+    assert!(message.contains("redox_dep"));
+    assert!(!message.contains("windows_dep"));
+  }
+
+  #[test]
+  fn test_plan_build_produces_aliased_dependencies() {
+    let toml_file = "
+    [package]
+    name = \"advanced_toml\"
+    version = \"0.1.0\"
+
+    [lib]
+    path = \"not_a_file.rs\"
+
+    [dependencies]
+    actix-web = \"2.0.0\"
+    actix-rt = \"1.0.0\"
+        ";
+    let (_temp_dir, files) = make_workspace(toml_file, None);
+    let mut fetcher = WorkspaceCrateMetadataFetcher::default();
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.genmode = GenMode::Remote;
+
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    // N.B. This will fail if we don't correctly ignore workspace crates.
+    let planned_build_res = planner.plan_build(
+      &settings,
+      files,
+      Some(PlatformDetails::new(
+        "some_target_triple".to_owned(),
+        Vec::new(), /* attrs */
+      )),
+    );
+
+    let crates_with_aliased_deps: Vec<CrateContext> = planned_build_res
+      .unwrap()
+      .crate_contexts
+      .into_iter()
+      .filter(|krate| krate.default_deps.aliased_dependencies.len() != 0)
+      .collect();
+
+    // Vec length shouldn't be 0
+    assert!(
+      crates_with_aliased_deps.len() != 0,
+      "Crates with aliased dependencies is 0"
+    );
+
+    // Find the actix-web crate
+    let actix_web_position = crates_with_aliased_deps
+      .iter()
+      .position(|krate| krate.pkg_name == "actix-http");
+    assert!(actix_web_position.is_some());
+
+    // Get crate context using computed position
+    let actix_http_context = crates_with_aliased_deps[actix_web_position.unwrap()].clone();
+
+    assert!(actix_http_context.default_deps.aliased_dependencies.len() == 1);
+    assert!(
+      actix_http_context.default_deps.aliased_dependencies[0].target
+        == "@raze_test__failure__0_1_8//:failure"
+    );
+    assert!(actix_http_context.default_deps.aliased_dependencies[0].alias == "fail_ure");
+  }
+
+  #[test]
+  fn test_plan_build_aliases_only_renamed_versions_of_a_package() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [dependencies]
+    shared = { path = \"../shared_v2\", version = \"0.2.0\" }
+    old_shared = { path = \"../shared_v1\", version = \"0.1.0\", package = \"shared\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    for (dir_name, version) in &[("shared_v1", "0.1.0"), ("shared_v2", "0.2.0")] {
+      write_file(
+        &format!("{}/Cargo.toml", dir_name),
+        &format!(
+          "
+    [package]
+    name = \"shared\"
+    version = \"{}\"
+    ",
+          version
+        ),
+      );
+      write_file(&format!("{}/src/lib.rs", dir_name), "");
+    }
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner
+      .plan_build(
+        &settings_testing::dummy_raze_settings(),
+        files,
+        Some(PlatformDetails::new(
+          "some_target_triple".to_owned(),
+          Vec::new(), /* attrs */
+        )),
+      )
+      .unwrap();
+
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+
+    let dep_targets = local_dep
+      .default_deps
+      .dependencies
+      .iter()
+      .map(|dep| dep.buildable_target.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      dep_targets,
+      vec!["//shared_v1:shared", "//shared_v2:shared"]
+    );
+    assert_eq!(
+      local_dep.default_deps.aliased_dependencies,
+      vec![DependencyAlias {
+        target: "//shared_v1:shared".to_owned(),
+        alias: "old_shared".to_owned(),
+      }]
+    );
+  }
+
+  #[test]
+  fn test_plan_build_skips_build_scripts_and_supplies_their_outputs() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    build = \"build.rs\"
+    ",
+    );
+    write_workspace_file(&dir, "local_dep/build.rs", "fn main() {}");
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.default_gen_buildrs = true;
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        skip_build_script: true,
+        build_script_rustc_flags: vec!["--cfg=has_feature".to_owned()],
+        build_script_rustc_env: vec![("GENERATED".to_owned(), "value".to_owned())]
+          .into_iter()
+          .collect(),
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("local_dep".to_owned(), crate_settings_per_version);
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert_eq!(local_dep.build_script_target, None);
+
+    let crate_build_file = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(!crate_build_file.contains("cargo_build_script("));
+    assert!(!crate_build_file.contains(":local_dep_build_script"));
+    assert!(crate_build_file.contains(
+      "    rustc_flags = [\n        \"--cap-lints=allow\",\n        \"--cfg=has_feature\",\n"
+    ));
+    assert!(crate_build_file.contains("    rustc_env = {\n        \"GENERATED\": \"value\",\n"));
+  }
+
+  #[test]
+  fn test_plan_build_lets_crates_override_whether_their_build_script_is_generated() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    build = \"build.rs\"
+    ",
+    );
+    write_workspace_file(&dir, "local_dep/build.rs", "fn main() {}");
+
+    let render_local_dep = |default_gen_buildrs: bool, gen_buildrs: Option<bool>| {
+      let mut settings = settings_testing::dummy_raze_settings();
+      settings.default_gen_buildrs = default_gen_buildrs;
+      let mut crate_settings_per_version = HashMap::new();
+      crate_settings_per_version.insert(
+        Version::parse("0.1.0").unwrap(),
+        CrateSettings {
+          gen_buildrs,
+          ..CrateSettings::default()
+        },
+      );
+      settings
+        .crates
+        .insert("local_dep".to_owned(), crate_settings_per_version);
+
+      let files = CargoWorkspaceFiles {
+        toml_path: toml_path.clone(),
+        lock_path_opt: None,
+      };
+      let mut fetcher = CargoMetadataFetcher::default();
+      let mut planner = BuildPlannerImpl::new(&mut fetcher);
+      let planned_build = planner.plan_build(&settings, files, None).unwrap();
+      let local_dep = planned_build
+        .crate_contexts
+        .iter()
+        .find(|ctx| ctx.pkg_name == "local_dep")
+        .unwrap();
+      BazelRenderer::new()
+        .render_crate(&planned_build.workspace_context, local_dep)
+        .unwrap()
+    };
+
+    // Forced on, despite build scripts being off by default
+    assert!(render_local_dep(false, Some(true)).contains("cargo_build_script("));
+    // Forced off, despite build scripts being on by default
+    assert!(!render_local_dep(true, Some(false)).contains("cargo_build_script("));
+    // Unset, following the default
+    assert!(render_local_dep(true, None).contains("cargo_build_script("));
+    assert!(!render_local_dep(false, None).contains("cargo_build_script("));
+  }
+
+  #[test]
+  fn test_plan_build_sorts_additional_deps_among_resolved_deps() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [dependencies]
+    local_transitive_dep = { path = \"../local_transitive_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "local_transitive_dep/Cargo.toml",
+      "
+    [package]
+    name = \"local_transitive_dep\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("local_transitive_dep/src/lib.rs", "");
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        additional_deps: vec!["@//third_party:zlib".to_owned(), "//aaa:first".to_owned()],
+        additional_proc_macro_deps: vec!["//macros:derive".to_owned()],
+        additional_target_deps: vec![("lib:local_dep".to_owned(), vec!["//bbb:second".to_owned()])]
+          .into_iter()
+          .collect(),
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("local_dep".to_owned(), crate_settings_per_version);
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(rendered.contains(
+      "    deps = [
+        \"//aaa:first\",
+        \"//bbb:second\",
+        \"//local_transitive_dep:local_transitive_dep\",
+        \"@//third_party:zlib\",
+    ],"
+    ));
+    assert!(rendered.contains(
+      "    proc_macro_deps = [
+        \"//macros:derive\",
+    ],"
+    ));
+  }
+
+  #[test]
+  fn test_plan_build_references_dependencies_by_their_lib_target() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [dependencies]
+    local_transitive_dep = { path = \"../local_transitive_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "local_transitive_dep/Cargo.toml",
+      "
+    [package]
+    name = \"local_transitive_dep\"
+    version = \"0.1.0\"
+
+    [lib]
+    name = \"renamed_lib\"
+    ",
+    );
+    write_file("local_transitive_dep/src/lib.rs", "");
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner
+      .plan_build(&settings_testing::dummy_raze_settings(), files, None)
+      .unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert_eq!(
+      local_dep.default_deps.dependencies[0].buildable_target,
+      "//local_transitive_dep:renamed_lib"
+    );
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(rendered.contains(
+      "    deps = [
+        \"//local_transitive_dep:renamed_lib\",
+    ],"
+    ));
+  }
+
+  #[test]
+  fn test_plan_build_removes_skipped_deps() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [dependencies]
+    local_transitive_dep = { path = \"../local_transitive_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "local_transitive_dep/Cargo.toml",
+      "
+    [package]
+    name = \"local_transitive_dep\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("local_transitive_dep/src/lib.rs", "");
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        skipped_deps: vec!["local_transitive_dep".to_owned()],
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("local_dep".to_owned(), crate_settings_per_version);
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert!(local_dep.default_deps.dependencies.is_empty());
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(!rendered.contains("//local_transitive_dep:local_transitive_dep"));
+
+    assert!(planned_build
+      .warnings
+      .contains(&PlanningWarning::SkippedDependency {
+        crate_name: "local_dep".to_owned(),
+        dependency_name: "local_transitive_dep".to_owned(),
+        dependency_version: "0.1.0".to_owned(),
+      }));
+  }
+
+  #[test]
+  fn test_plan_build_skips_dev_dependencies_unless_rendered() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "
+    [dev-dependencies]
+    test_helper = { path = \"../test_helper\" }
+    ",
+      "
+    [dev-dependencies]
+    test_helper = { path = \"../test_helper\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file("local_dep/tests/it.rs", "");
+    write_file(
+      "test_helper/Cargo.toml",
+      "
+    [package]
+    name = \"test_helper\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("test_helper/src/lib.rs", "");
+
+    let plan = |render_dev_dependencies: bool| {
+      let mut settings = settings_testing::dummy_raze_settings();
+      settings.render_dev_dependencies = render_dev_dependencies;
+      let files = CargoWorkspaceFiles {
+        toml_path: toml_path.clone(),
+        lock_path_opt: None,
+      };
+      let mut fetcher = CargoMetadataFetcher::default();
+      let mut planner = BuildPlannerImpl::new(&mut fetcher);
+      planner.plan_build(&settings, files, None).unwrap()
+    };
+    let has_test_target = |planned_build: &PlannedBuild| {
+      planned_build
+        .crate_contexts
+        .iter()
+        .find(|ctx| ctx.pkg_name == "local_dep")
+        .unwrap()
+        .targets
+        .iter()
+        .any(|target| target.kind == "test")
+    };
+    let crate_names = |planned_build: &PlannedBuild| {
+      planned_build
+        .crate_contexts
+        .iter()
+        .map(|ctx| ctx.pkg_name.clone())
+        .collect::<Vec<_>>()
+    };
+
+    let planned_build = plan(false);
+    assert_eq!(crate_names(&planned_build), vec!["local_dep"]);
+    assert!(!has_test_target(&planned_build));
+
+    let planned_build = plan(true);
+    assert_eq!(
+      crate_names(&planned_build),
+      vec!["local_dep", "test_helper"]
+    );
+    assert!(has_test_target(&planned_build));
+  }
+
+  #[test]
+  fn test_plan_build_uses_per_crate_buildfile_suffix_overrides() {
+    let dir = TempDir::new().unwrap();
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    let toml_path = write_file(
+      "cargo/Cargo.toml",
+      "
+    [package]
+    name = \"test\"
+    version = \"0.0.1\"
+
+    [lib]
+    path = \"not_a_file.rs\"
+
+    [dependencies]
+    local_a = { path = \"../local_a\" }
+    local_b = { path = \"../local_b\" }
+    ",
+    );
+    for name in &["local_a", "local_b"] {
+      write_file(
+        &format!("{}/Cargo.toml", name),
+        &format!(
+          "
+    [package]
+    name = \"{}\"
+    version = \"0.1.0\"
+    ",
+          name
+        ),
+      );
+      write_file(&format!("{}/src/lib.rs", name), "");
+    }
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        buildfile_suffix_override: Some("BUILD.bazel".to_owned()),
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("local_a".to_owned(), crate_settings_per_version);
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(
+        &RenderDetails {
+          path_prefix: "./cargo".to_owned(),
+          buildfile_suffix: "BUILD".to_owned(),
+          legacy_buildfile_stubs: false,
+          fetch_mode: FetchMode::Workspace,
+          aliases_package: None,
+          normalize_line_endings: true,
+        },
+        &planned_build,
+      )
+      .unwrap();
+    let paths = file_outputs
+      .iter()
+      .map(|output| output.path.as_str())
+      .collect::<Vec<&str>>();
+
+    assert!(paths.contains(&"./cargo/../local_a/BUILD.bazel"));
+    assert!(paths.contains(&"./cargo/../local_b/BUILD"));
+    assert!(!paths.contains(&"./cargo/../local_a/BUILD"));
+    assert!(paths.contains(&"./cargo/BUILD"));
+  }
+
+  #[test]
+  fn test_plan_build_skips_excluded_crates() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "
+    system_dep = { path = \"../system_dep\" }
+    ",
+      "
+    [dependencies]
+    system_dep = { path = \"../system_dep\" }
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "system_dep/Cargo.toml",
+      "
+    [package]
+    name = \"system_dep\"
+    version = \"0.2.0\"
+    ",
+    );
+    write_file("system_dep/src/lib.rs", "");
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.excluded_crates = vec!["system_dep==0.2.0".to_owned()];
+
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    assert_eq!(
+      planned_build.warnings,
+      vec![PlanningWarning::ExcludedDependency {
+        crate_name: "local_dep".to_owned(),
+        dependency_name: "system_dep".to_owned(),
+        dependency_version: "0.2.0".to_owned(),
+      }]
+    );
+
+    let file_outputs = BazelRenderer::new()
+      .render_planned_build(
+        &RenderDetails {
+          path_prefix: "./cargo".to_owned(),
+          buildfile_suffix: "BUILD".to_owned(),
+          legacy_buildfile_stubs: false,
+          fetch_mode: FetchMode::Workspace,
+          aliases_package: None,
+          normalize_line_endings: true,
+        },
+        &planned_build,
+      )
+      .unwrap();
+    let paths = file_outputs
+      .iter()
+      .map(|output| output.path.as_str())
+      .collect::<Vec<&str>>();
+    assert!(paths.contains(&"./cargo/../local_dep/BUILD"));
+    assert!(!paths.contains(&"./cargo/../system_dep/BUILD"));
+
+    let workspace_build_contents = &file_outputs
+      .iter()
+      .find(|output| output.path == "./cargo/BUILD")
+      .unwrap()
+      .contents;
+    assert!(workspace_build_contents.contains("name = \"local_dep\""));
+    assert!(!workspace_build_contents.contains("system_dep"));
+  }
+
+  #[test]
+  fn test_plan_build_selects_platform_specific_features() {
+    let (_dir, toml_path) = make_path_dependency_workspace(
+      "
+    [workspace]
+    resolver = \"2\"
+
+    [target.'cfg(target_arch = \"x86_64\")'.dependencies]
+    local_dep = { path = \"../local_dep\", features = [\"simd\"] }
+    ",
+      "
+    [features]
+    default = [\"std\"]
+    simd = []
+    std = []
+    ",
+    );
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut settings = settings_testing::dummy_raze_settings();
+    settings.targets = Some(vec![
+      "aarch64-unknown-linux-gnu".to_owned(),
+      "x86_64-apple-darwin".to_owned(),
+      "x86_64-unknown-linux-gnu".to_owned(),
+    ]);
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert_eq!(local_dep.features, vec!["default", "std"]);
+    assert_eq!(
+      local_dep.targeted_features,
+      vec![CrateTargetedFeatureContext {
+        features: vec!["simd".to_owned()],
+        conditions: vec![
+          "@io_bazel_rules_rust//rust/platform:x86_64-apple-darwin".to_owned(),
+          "@io_bazel_rules_rust//rust/platform:x86_64-unknown-linux-gnu".to_owned(),
+        ],
+      }]
+    );
+  }
+
+  #[test]
+  fn test_plan_build_makes_optional_features_selectable() {
+    let (dir, toml_path) = make_path_dependency_workspace(
+      "",
+      "
+    [dependencies]
+    local_transitive_dep = { path = \"../local_transitive_dep\", optional = true }
+
+    [features]
+    default = [\"std\"]
+    fast = []
+    nightly = [\"fast\"]
+    std = []
+    transitive = [\"local_transitive_dep\"]
+    ",
+    );
+    let write_file = |path: &str, contents: &str| write_workspace_file(&dir, path, contents);
+    write_file(
+      "local_transitive_dep/Cargo.toml",
+      "
+    [package]
+    name = \"local_transitive_dep\"
+    version = \"0.1.0\"
+    ",
+    );
+    write_file("local_transitive_dep/src/lib.rs", "");
+
+    let mut settings = settings_testing::dummy_raze_settings();
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        selectable_features: true,
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("local_dep".to_owned(), crate_settings_per_version);
+
+    let files = CargoWorkspaceFiles {
+      toml_path,
+      lock_path_opt: None,
+    };
+    let mut fetcher = CargoMetadataFetcher::default();
+    let mut planner = BuildPlannerImpl::new(&mut fetcher);
+    let planned_build = planner.plan_build(&settings, files, None).unwrap();
+    let local_dep = planned_build
+      .crate_contexts
+      .iter()
+      .find(|ctx| ctx.pkg_name == "local_dep")
+      .unwrap();
+    assert_eq!(local_dep.features, vec!["default", "std"]);
+    assert_eq!(
+      local_dep.selectable_features,
+      vec![
+        CrateSelectableFeatureContext {
+          name: "fast".to_owned(),
+          features: vec!["fast".to_owned()],
+        },
+        CrateSelectableFeatureContext {
+          name: "nightly".to_owned(),
+          features: vec!["fast".to_owned(), "nightly".to_owned()],
+        },
+      ]
+    );
+
+    let rendered = BazelRenderer::new()
+      .render_crate(&planned_build.workspace_context, local_dep)
+      .unwrap();
+    assert!(rendered.contains("load(\"@bazel_skylib//rules:common_settings.bzl\", \"bool_flag\")"));
+    assert!(rendered.contains(
+      "bool_flag(
+    name = \"feature_nightly\",
+    build_setting_default = False,
+)
+
+config_setting(
+    name = \"feature_nightly_enabled\",
+    flag_values = {\":feature_nightly\": \"true\"},
+)"
+    ));
+    assert!(rendered.contains(
+      "    crate_features = [
+        \"default\",
+        \"std\",
+    ] + select({
+        \":feature_fast_enabled\": [
+            \"fast\",
+        ],
+        \"//conditions:default\": [],
+    }) + select({
+        \":feature_nightly_enabled\": [
+            \"fast\",
+            \"nightly\",
+        ],
+        \"//conditions:default\": [],
+    }),"
+    ));
+    assert!(!rendered.contains("feature_transitive"));
   }
 
   #[test]
@@ -12,8 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::planning::PlannedBuild;
+use crate::{
+  bazel::{format_file_outputs, BazelRenderer},
+  context::{CrateContext, WorkspaceContext},
+  lockfile::render_lockfile,
+  planning::PlannedBuild,
+  settings::{FetchMode, GenMode, RazeSettings},
+  starlark::StarlarkRenderer,
+  util::{sanitize_repository_name, RazeError},
+};
 use anyhow::Result;
+use std::{
+  collections::{BTreeMap, HashSet},
+  fs,
+  path::Path,
+};
 
 /**
  * An object that can convert a prepared build plan into a series of files for a Bazel-like build
@@ -30,9 +43,114 @@ pub trait BuildRenderer {
     render_details: &RenderDetails,
     planned_build: &PlannedBuild,
   ) -> Result<Vec<FileOutputs>>;
+
+  /**
+   * Renders like `render_planned_build`, passing the name of each crate to `on_crate_rendered`
+   * once its build file is rendered.
+   *
+   * By default, the crates are only reported once the whole build is rendered.
+   */
+  fn render_planned_build_with_progress(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
+  ) -> Result<Vec<FileOutputs>> {
+    let file_outputs = self.render_planned_build(render_details, planned_build)?;
+    report_crates_rendered(planned_build, on_crate_rendered);
+    Ok(file_outputs)
+  }
+
+  /** Renders like `render_remote_planned_build`, reporting progress as described above. */
+  fn render_remote_planned_build_with_progress(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
+  ) -> Result<Vec<FileOutputs>> {
+    let file_outputs = self.render_remote_planned_build(render_details, planned_build)?;
+    report_crates_rendered(planned_build, on_crate_rendered);
+    Ok(file_outputs)
+  }
 }
 
-#[derive(Debug, Clone)]
+/**
+ * Receives the name of each crate as its build file is rendered, such as to show progress.
+ *
+ * Crates may be rendered in parallel, so the hook may be called from several threads at once.
+ */
+pub type CrateRenderedHook<'a> = dyn Fn(&str) + Sync + 'a;
+
+/** A hook ignoring the rendered crates. */
+pub fn ignore_crates_rendered(_crate_name: &str) {}
+
+fn report_crates_rendered(planned_build: &PlannedBuild, on_crate_rendered: &CrateRenderedHook) {
+  for package in &planned_build.crate_contexts {
+    on_crate_rendered(&package.pkg_name);
+  }
+}
+
+/** Creates a new renderer, as registered in a `RendererRegistry`. */
+pub type RendererFactory = fn() -> Box<dyn BuildRenderer>;
+
+/**
+ * Maps the names of renderers to the factories creating them.
+ *
+ * The default registry holds the renderers bundled with cargo-raze, `"bazel"` for the Tera
+ * templates and `"starlark-ast"` for the Starlark statements. Other renderers can be registered
+ * under their own name, to be selected by it like the bundled ones.
+ */
+pub struct RendererRegistry {
+  factories: BTreeMap<String, RendererFactory>,
+}
+
+impl RendererRegistry {
+  /** Creates a registry without any renderer. */
+  pub fn new() -> Self {
+    Self {
+      factories: BTreeMap::new(),
+    }
+  }
+
+  /** Registers a renderer under the given name, replacing any renderer already registered. */
+  pub fn register(&mut self, name: &str, factory: RendererFactory) {
+    self.factories.insert(name.to_owned(), factory);
+  }
+
+  /** The names of the registered renderers, in order. */
+  pub fn names(&self) -> Vec<&str> {
+    self.factories.keys().map(String::as_str).collect()
+  }
+
+  /** Creates a new instance of the renderer registered under the given name. */
+  pub fn create(&self, name: &str) -> Result<Box<dyn BuildRenderer>> {
+    match self.factories.get(name) {
+      Some(factory) => Ok(factory()),
+      None => Err(
+        RazeError::Config {
+          field_path_opt: None,
+          message: format!(
+            "No renderer is registered as '{}', expected one of: {}",
+            name,
+            self.names().join(", ")
+          ),
+        }
+        .into(),
+      ),
+    }
+  }
+}
+
+impl Default for RendererRegistry {
+  fn default() -> Self {
+    let mut registry = Self::new();
+    registry.register("bazel", || Box::new(BazelRenderer::new()));
+    registry.register("starlark-ast", || Box::new(StarlarkRenderer::new()));
+    registry
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FileOutputs {
   pub path: String,
   pub contents: String,
@@ -42,4 +160,698 @@ pub struct FileOutputs {
 pub struct RenderDetails {
   pub path_prefix: String,
   pub buildfile_suffix: String,
+  // Whether to emit a stub `BUILD` file next to each build file named by `buildfile_suffix`
+  pub legacy_buildfile_stubs: bool,
+  // How the remote crates are fetched, in Remote genmode
+  pub fetch_mode: FetchMode,
+  // The package the aliases are written to, relative to `path_prefix`, instead of the root one
+  pub aliases_package: Option<String>,
+  // Whether to convert the CRLF line endings of every output to LF
+  pub normalize_line_endings: bool,
+}
+
+impl RenderDetails {
+  /**
+   * Checks that the rendered paths are well formed.
+   *
+   * The build file suffix must be a non-empty file name, and the paths relative to `path_prefix`
+   * may not hold a `..` component, so that every output lands under it. The prefix itself comes
+   * from the `--output` flag and may point anywhere, such as `../third_party`.
+   */
+  pub fn validate(&self) -> Result<()> {
+    if self.buildfile_suffix.is_empty() {
+      return Err(
+        RazeError::Config {
+          field_path_opt: Some("output_buildfile_suffix".to_owned()),
+          message: "The build file suffix must not be empty".to_owned(),
+        }
+        .into(),
+      );
+    }
+
+    if self.buildfile_suffix.contains('/') {
+      return Err(
+        RazeError::Config {
+          field_path_opt: Some("output_buildfile_suffix".to_owned()),
+          message: format!(
+            "The build file suffix must be a file name, but was '{}'",
+            self.buildfile_suffix
+          ),
+        }
+        .into(),
+      );
+    }
+
+    let paths = [
+      ("output_buildfile_suffix", Some(&self.buildfile_suffix)),
+      ("output_aliases_package", self.aliases_package.as_ref()),
+    ];
+    for (field_path, path) in paths.iter() {
+      let traverses_to_parent = |path: &String| path.split('/').any(|component| component == "..");
+      if let Some(path) = path.filter(|path| traverses_to_parent(path)) {
+        return Err(
+          RazeError::Config {
+            field_path_opt: Some(field_path.to_string()),
+            message: format!(
+              "The path '{}' must not traverse to a parent directory",
+              path
+            ),
+          }
+          .into(),
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  /**
+   * A validated copy of these details, with the trailing slashes of `path_prefix` removed.
+   *
+   * Paths are rendered by joining `path_prefix` to the package path with a slash, which would
+   * otherwise double up.
+   */
+  pub fn normalized(&self) -> Result<RenderDetails> {
+    self.validate()?;
+
+    Ok(RenderDetails {
+      path_prefix: self.path_prefix.trim_end_matches('/').to_owned(),
+      ..self.clone()
+    })
+  }
+
+  /**
+   * The path of the build file holding the aliases of the root dependencies.
+   *
+   * No build file is generated for the root package when the aliases are written elsewhere.
+   */
+  pub fn aliases_build_file_path(&self) -> String {
+    match &self.aliases_package {
+      Some(package) => format!(
+        "{}/{}/{}",
+        self.path_prefix,
+        package.trim_matches('/'),
+        self.buildfile_suffix
+      ),
+      None => format!("{}/{}", self.path_prefix, self.buildfile_suffix),
+    }
+  }
+}
+
+/**
+ * Sorts the crates by the name of the repository they are fetched into.
+ *
+ * Planning yields the crates in the order cargo resolved them, whereas the fetch files must not
+ * depend on it to be reproducible.
+ */
+pub fn sort_by_repository_name(
+  workspace_context: &WorkspaceContext,
+  crate_contexts: &[CrateContext],
+) -> Vec<CrateContext> {
+  let mut sorted_crates = crate_contexts.to_vec();
+  sorted_crates.sort_by_cached_key(|package| {
+    sanitize_repository_name(
+      &workspace_context.gen_workspace_prefix,
+      &package.pkg_name,
+      &package.pkg_version,
+    )
+  });
+  sorted_crates
+}
+
+/** The name of the build file looked up by tooling predating `BUILD.bazel`. */
+const LEGACY_BUILDFILE_NAME: &str = "BUILD";
+
+/**
+ * Converts the CRLF line endings of every output to LF, if requested by `render_details`.
+ *
+ * CRLF endings come from templates and additional build files checked out on Windows, and would
+ * otherwise be mixed with the LF endings of the rendered contents.
+ */
+pub fn normalize_line_endings(render_details: &RenderDetails, file_outputs: &mut [FileOutputs]) {
+  if !render_details.normalize_line_endings {
+    return;
+  }
+
+  for output in file_outputs.iter_mut() {
+    if output.contents.contains("\r\n") {
+      output.contents = output.contents.replace("\r\n", "\n");
+    }
+  }
+}
+
+/**
+ * Adds a stub `BUILD` file next to each package build file, if requested by `render_details`.
+ *
+ * Bazel reads `BUILD.bazel` in preference to `BUILD` when a package has both, so the stubs only
+ * need to exist. Nothing is added when the build files are already named `BUILD`.
+ */
+pub fn add_legacy_buildfile_stubs(
+  render_details: &RenderDetails,
+  file_outputs: &mut Vec<FileOutputs>,
+) {
+  if !render_details.legacy_buildfile_stubs
+    || render_details.buildfile_suffix == LEGACY_BUILDFILE_NAME
+  {
+    return;
+  }
+
+  let stubs = file_outputs
+    .iter()
+    .filter_map(|output| {
+      let path = Path::new(&output.path);
+      if path.file_name()? != render_details.buildfile_suffix.as_str() {
+        return None;
+      }
+
+      Some(FileOutputs {
+        path: path
+          .with_file_name(LEGACY_BUILDFILE_NAME)
+          .display()
+          .to_string(),
+        contents: format!(
+          "# @generated\n# DO NOT EDIT! Replaced on runs of cargo-raze\n#\n# The targets of this \
+           package are defined in {}, which Bazel reads instead of this file.\n",
+          render_details.buildfile_suffix
+        ),
+      })
+    })
+    .collect::<Vec<FileOutputs>>();
+
+  file_outputs.extend(stubs);
+}
+
+/**
+ * Renders the planned build into the files it would generate, without touching the filesystem.
+ *
+ * The render mode is picked from `settings.genmode`, and the outputs are formatted with buildifier
+ * when `settings.format_output` is set. A lockfile is added when `settings.gen_lockfile` is set.
+ * Use `write_file_outputs` to persist the result.
+ */
+pub fn render_file_outputs(
+  renderer: &mut dyn BuildRenderer,
+  settings: &RazeSettings,
+  render_details: &RenderDetails,
+  planned_build: &PlannedBuild,
+) -> Result<Vec<FileOutputs>> {
+  let render_details = &render_details.normalized()?;
+
+  let mut file_outputs = match settings.genmode {
+    GenMode::Vendored => renderer.render_planned_build(render_details, planned_build)?,
+    GenMode::Remote => renderer.render_remote_planned_build(render_details, planned_build)?,
+  };
+
+  if settings.format_output {
+    file_outputs = format_file_outputs(file_outputs, settings.buildifier_path.as_deref());
+  }
+
+  if settings.gen_lockfile {
+    file_outputs.push(render_lockfile(render_details, planned_build)?);
+  }
+
+  Ok(file_outputs)
+}
+
+/**
+ * Renders the planned build like `render_file_outputs`, but keeps only the build files of the
+ * crates named in `names`.
+ *
+ * The aggregate files, such as the aliases and the fetch functions, are still rendered in full, as
+ * they refer to every crate. The build files of the other crates are left out, so that writing the
+ * outputs keeps their existing files.
+ */
+pub fn render_subset(
+  renderer: &mut dyn BuildRenderer,
+  settings: &RazeSettings,
+  render_details: &RenderDetails,
+  planned_build: &PlannedBuild,
+  names: &[String],
+) -> Result<Vec<FileOutputs>> {
+  let render_details = &render_details.normalized()?;
+
+  if let Some(unknown_name) = names.iter().find(|name| {
+    !planned_build
+      .crate_contexts
+      .iter()
+      .any(|package| &package.pkg_name == *name)
+  }) {
+    return Err(
+      RazeError::Rendering {
+        crate_name_opt: Some(unknown_name.clone()),
+        message: "The crate is not part of the planned build".to_owned(),
+      }
+      .into(),
+    );
+  }
+
+  let mut excluded_outputs = planned_build
+    .crate_contexts
+    .iter()
+    .filter(|package| !names.contains(&package.pkg_name))
+    .map(|package| FileOutputs {
+      path: format!(
+        "{}/{}",
+        render_details.path_prefix, package.expected_build_path
+      ),
+      contents: String::new(),
+    })
+    .collect::<Vec<FileOutputs>>();
+  add_legacy_buildfile_stubs(render_details, &mut excluded_outputs);
+  let excluded_paths = excluded_outputs
+    .into_iter()
+    .map(|output| output.path)
+    .collect::<HashSet<String>>();
+
+  let mut file_outputs = render_file_outputs(renderer, settings, render_details, planned_build)?;
+  file_outputs.retain(|output| !excluded_paths.contains(&output.path));
+  Ok(file_outputs)
+}
+
+/**
+ * Serializes the given outputs into a single stream, such as for comparison against a snapshot.
+ *
+ * Each output is written as a header line holding its path and the length in bytes of its contents,
+ * followed by the contents and a newline. Outputs are sorted by path, so the stream does not depend
+ * on the order they were rendered in.
+ */
+pub fn serialize_file_outputs(file_outputs: &[FileOutputs]) -> String {
+  let mut sorted_outputs = file_outputs.iter().collect::<Vec<&FileOutputs>>();
+  sorted_outputs.sort_by(|a, b| (&a.path, &a.contents).cmp(&(&b.path, &b.contents)));
+
+  sorted_outputs
+    .into_iter()
+    .map(|output| {
+      format!(
+        "--- {} ({} bytes)\n{}\n",
+        output.path,
+        output.contents.len(),
+        output.contents
+      )
+    })
+    .collect()
+}
+
+/** A destination for rendered files, such as the disk, or an in-memory store in tests. */
+pub trait OutputSink {
+  /** Writes the given contents to the file at the given path, replacing any previous contents. */
+  fn write(&mut self, path: &str, contents: &str) -> Result<()>;
+}
+
+/** An `OutputSink` writing to disk, creating any missing parent directories. */
+#[derive(Debug, Default)]
+pub struct FileSystemSink;
+
+impl OutputSink for FileSystemSink {
+  fn write(&mut self, path: &str, contents: &str) -> Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+      fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    println!("Generated {} successfully", path);
+    Ok(())
+  }
+}
+
+/** Writes each of the given outputs to the given sink, in order. */
+pub fn write_file_outputs_to(
+  sink: &mut dyn OutputSink,
+  file_outputs: &[FileOutputs],
+) -> Result<()> {
+  for FileOutputs {
+    path,
+    contents,
+  } in file_outputs
+  {
+    sink.write(path, contents)?;
+  }
+
+  Ok(())
+}
+
+/** Writes each of the given outputs to disk, creating any missing parent directories. */
+pub fn write_file_outputs(file_outputs: &[FileOutputs]) -> Result<()> {
+  write_file_outputs_to(&mut FileSystemSink, file_outputs)
+}
+
+/**
+ * Checks that the build files of path dependencies can be written to disk.
+ *
+ * These build files live in the directories of the path dependencies, which are located relative to
+ * `manifest_dir`, the directory holding the root `Cargo.toml`. They are only written where intended
+ * when `path_prefix` is that same directory. As these directories belong to the user, an existing
+ * file there is only replaced if cargo-raze generated it.
+ */
+pub fn check_path_dependency_outputs(
+  render_details: &RenderDetails,
+  planned_build: &PlannedBuild,
+  manifest_dir: &Path,
+) -> Result<()> {
+  let path_dependencies = planned_build
+    .crate_contexts
+    .iter()
+    .filter(|package| package.source_details.path_data.is_some())
+    .collect::<Vec<&CrateContext>>();
+  if path_dependencies.is_empty() {
+    return Ok(());
+  }
+
+  let path_prefix = Path::new(&render_details.path_prefix);
+  let prefix_dir = fs::canonicalize(path_prefix).unwrap_or_else(|_| path_prefix.to_path_buf());
+  let manifest_dir = fs::canonicalize(manifest_dir).unwrap_or_else(|_| manifest_dir.to_path_buf());
+  if prefix_dir != manifest_dir {
+    return Err(
+      RazeError::Rendering {
+        crate_name_opt: Some(path_dependencies[0].pkg_name.clone()),
+        message: format!(
+          "The build files of path dependencies are placed relative to {}, but the output \
+           directory is {}",
+          manifest_dir.display(),
+          prefix_dir.display()
+        ),
+      }
+      .into(),
+    );
+  }
+
+  for package in path_dependencies {
+    let mut package_outputs = vec![FileOutputs {
+      path: format!(
+        "{}/{}",
+        render_details.path_prefix, package.expected_build_path
+      ),
+      contents: String::new(),
+    }];
+    add_legacy_buildfile_stubs(render_details, &mut package_outputs);
+
+    for FileOutputs {
+      path, ..
+    } in package_outputs
+    {
+      let is_user_file = match fs::read_to_string(&path) {
+        Ok(contents) => !contents.contains("@generated"),
+        Err(_) => Path::new(&path).exists(),
+      };
+      if is_user_file {
+        return Err(
+          RazeError::Rendering {
+            crate_name_opt: Some(package.pkg_name.clone()),
+            message: format!(
+              "Refusing to overwrite {}, which was not generated by cargo-raze",
+              path
+            ),
+          }
+          .into(),
+        );
+      }
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{
+    context::WorkspaceContext,
+    settings::{testing::*, AliasNameStyle, RendererKind},
+  };
+
+  use tempfile::TempDir;
+
+  fn render_details_in(dir: &TempDir) -> RenderDetails {
+    RenderDetails {
+      path_prefix: dir.path().join("cargo").display().to_string(),
+      buildfile_suffix: "BUILD.bazel".to_owned(),
+      legacy_buildfile_stubs: false,
+      fetch_mode: FetchMode::Workspace,
+      aliases_package: None,
+      normalize_line_endings: true,
+    }
+  }
+
+  fn empty_planned_build() -> PlannedBuild {
+    PlannedBuild {
+      workspace_context: WorkspaceContext {
+        workspace_path: "//cargo".to_owned(),
+        gen_workspace_prefix: "raze".to_owned(),
+        output_buildfile_suffix: "BUILD.bazel".to_owned(),
+        rules_rust_repository_name: "io_bazel_rules_rust".to_owned(),
+        default_tags: vec!["cargo-raze".to_owned(), "manual".to_owned()],
+        generated_file_header: "DO NOT EDIT! Replaced on runs of cargo-raze".to_owned(),
+        alias_name_style: AliasNameStyle::Sanitize,
+      },
+      crate_contexts: Vec::new(),
+      warnings: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn renderers_are_looked_up_by_name() {
+    let dir = TempDir::new().unwrap();
+    let registry = RendererRegistry::default();
+    assert_eq!(registry.names(), vec!["bazel", "starlark-ast"]);
+
+    let mut renderer = registry.create("bazel").unwrap();
+    let file_outputs = renderer
+      .render_remote_planned_build(&render_details_in(&dir), &empty_planned_build())
+      .unwrap();
+    assert!(file_outputs
+      .iter()
+      .any(|output| output.path.ends_with("/cargo/crates.bzl")));
+
+    for renderer_kind in [RendererKind::Tera, RendererKind::Starlark] {
+      assert!(registry.create(renderer_kind.registry_name()).is_ok());
+    }
+
+    let err = registry.create("ninja").err().unwrap();
+    assert!(err.to_string().contains("bazel, starlark-ast"));
+  }
+
+  #[test]
+  fn render_file_outputs_does_not_write_files() {
+    let dir = TempDir::new().unwrap();
+
+    for genmode in [GenMode::Vendored, GenMode::Remote] {
+      let settings = RazeSettings {
+        genmode,
+        ..dummy_raze_settings()
+      };
+
+      let file_outputs = render_file_outputs(
+        &mut BazelRenderer::new(),
+        &settings,
+        &render_details_in(&dir),
+        &empty_planned_build(),
+      )
+      .unwrap();
+
+      assert!(!file_outputs.is_empty());
+      assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+  }
+
+  #[test]
+  fn render_file_outputs_adds_a_lockfile_when_enabled() {
+    let dir = TempDir::new().unwrap();
+    let render_details = render_details_in(&dir);
+    let lockfile_path = format!("{}/cargo-raze.lock", render_details.path_prefix);
+
+    for gen_lockfile in [false, true] {
+      let settings = RazeSettings {
+        gen_lockfile,
+        ..dummy_raze_settings()
+      };
+
+      let file_outputs = render_file_outputs(
+        &mut BazelRenderer::new(),
+        &settings,
+        &render_details,
+        &empty_planned_build(),
+      )
+      .unwrap();
+
+      assert_eq!(
+        file_outputs
+          .iter()
+          .any(|output| output.path == lockfile_path),
+        gen_lockfile
+      );
+    }
+  }
+
+  #[test]
+  fn write_file_outputs_creates_missing_directories() {
+    let dir = TempDir::new().unwrap();
+    let settings = RazeSettings {
+      genmode: GenMode::Remote,
+      ..dummy_raze_settings()
+    };
+
+    let file_outputs = render_file_outputs(
+      &mut BazelRenderer::new(),
+      &settings,
+      &render_details_in(&dir),
+      &empty_planned_build(),
+    )
+    .unwrap();
+    write_file_outputs(&file_outputs).unwrap();
+
+    for output in file_outputs {
+      assert_eq!(fs::read_to_string(&output.path).unwrap(), output.contents);
+    }
+  }
+
+  // An OutputSink keeping the written files in memory
+  #[derive(Default)]
+  struct InMemorySink {
+    files: BTreeMap<String, String>,
+  }
+
+  impl OutputSink for InMemorySink {
+    fn write(&mut self, path: &str, contents: &str) -> Result<()> {
+      self.files.insert(path.to_owned(), contents.to_owned());
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn file_outputs_can_be_written_to_any_sink() {
+    let dir = TempDir::new().unwrap();
+    let settings = RazeSettings {
+      genmode: GenMode::Remote,
+      ..dummy_raze_settings()
+    };
+
+    let file_outputs = render_file_outputs(
+      &mut BazelRenderer::new(),
+      &settings,
+      &render_details_in(&dir),
+      &empty_planned_build(),
+    )
+    .unwrap();
+    let mut sink = InMemorySink::default();
+    write_file_outputs_to(&mut sink, &file_outputs).unwrap();
+
+    assert!(!file_outputs.is_empty());
+    assert_eq!(sink.files.len(), file_outputs.len());
+    for output in &file_outputs {
+      assert_eq!(sink.files[&output.path], output.contents);
+      assert!(!Path::new(&output.path).exists());
+    }
+  }
+
+  #[test]
+  fn serialized_file_outputs_do_not_depend_on_their_order() {
+    let file_outputs = vec![
+      FileOutputs {
+        path: "cargo/BUILD.bazel".to_owned(),
+        contents: "# aliases\n".to_owned(),
+      },
+      FileOutputs {
+        path: "cargo/crates.bzl".to_owned(),
+        contents: String::new(),
+      },
+      FileOutputs {
+        path: "cargo/remote/BUILD.bazel".to_owned(),
+        contents: "# remote".to_owned(),
+      },
+    ];
+    let mut reordered_outputs = file_outputs.clone();
+    reordered_outputs.reverse();
+
+    let serialized = serialize_file_outputs(&file_outputs);
+    assert_eq!(serialized, serialize_file_outputs(&reordered_outputs));
+    assert_eq!(
+      serialized,
+      "--- cargo/BUILD.bazel (10 bytes)\n# aliases\n\n--- cargo/crates.bzl (0 bytes)\n\n--- \
+       cargo/remote/BUILD.bazel (8 bytes)\n# remote\n"
+    );
+  }
+
+  #[test]
+  fn render_details_reject_malformed_paths() {
+    let dir = TempDir::new().unwrap();
+
+    for render_details in [
+      RenderDetails {
+        buildfile_suffix: String::new(),
+        ..render_details_in(&dir)
+      },
+      RenderDetails {
+        buildfile_suffix: "cargo/BUILD".to_owned(),
+        ..render_details_in(&dir)
+      },
+      RenderDetails {
+        buildfile_suffix: "..".to_owned(),
+        ..render_details_in(&dir)
+      },
+      RenderDetails {
+        aliases_package: Some("../aliases".to_owned()),
+        ..render_details_in(&dir)
+      },
+    ] {
+      assert!(render_details.validate().is_err());
+      assert!(render_file_outputs(
+        &mut BazelRenderer::new(),
+        &dummy_raze_settings(),
+        &render_details,
+        &empty_planned_build(),
+      )
+      .is_err());
+    }
+
+    let err = RenderDetails {
+      buildfile_suffix: String::new(),
+      ..render_details_in(&dir)
+    }
+    .validate()
+    .unwrap_err();
+    assert!(err.to_string().contains("output_buildfile_suffix"));
+  }
+
+  #[test]
+  fn render_details_accept_a_prefix_outside_the_working_directory() {
+    let dir = TempDir::new().unwrap();
+    let render_details = RenderDetails {
+      path_prefix: "../third_party".to_owned(),
+      ..render_details_in(&dir)
+    };
+    assert!(render_details.validate().is_ok());
+  }
+
+  #[test]
+  fn render_details_are_normalized_without_trailing_slashes() {
+    let dir = TempDir::new().unwrap();
+    let render_details = render_details_in(&dir);
+
+    let normalized = RenderDetails {
+      path_prefix: format!("{}//", render_details.path_prefix),
+      ..render_details.clone()
+    }
+    .normalized()
+    .unwrap();
+    assert_eq!(normalized.path_prefix, render_details.path_prefix);
+
+    let settings = RazeSettings {
+      gen_lockfile: true,
+      ..dummy_raze_settings()
+    };
+    let file_outputs = render_file_outputs(
+      &mut BazelRenderer::new(),
+      &settings,
+      &RenderDetails {
+        path_prefix: format!("{}/", render_details.path_prefix),
+        ..render_details.clone()
+      },
+      &empty_planned_build(),
+    )
+    .unwrap();
+    assert!(!file_outputs.is_empty());
+    for output in file_outputs {
+      assert!(!output.path.contains("//"), "{}", output.path);
+      assert!(output.path.starts_with(&render_details.path_prefix));
+    }
+  }
 }
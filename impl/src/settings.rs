@@ -13,9 +13,17 @@
 // limitations under the License.
 
 use super::util::RazeError;
+use cfg_expr::targets::get_builtin_target_by_triple;
 use semver::Version;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use serde_derive::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt,
+  fs::File,
+  io::Read,
+  path::Path,
+};
 
 pub type CrateSettingsPerVersion = HashMap<Version, CrateSettings>;
 
@@ -58,11 +66,90 @@ pub struct RazeSettings {
   /**
    * A list of targets to generate BUILD rules for.
    *
-   * Each item comes in the form of a "triple", such as "x86_64-unknown-linux-gnu"
+   * Each item comes in the form of a "triple", such as "x86_64-unknown-linux-gnu". Crate features
+   * are resolved separately for each of these, and features that are only enabled on some of them
+   * are selected per platform.
    */
   #[serde(default)]
   pub targets: Option<Vec<String>>,
 
+  /**
+   * The platform triples that are supported by the Bazel rules_rust rules.
+   *
+   * Dependencies gated on a platform are only generated if they match one of these triples. When
+   * empty, a built-in set of triples known to rules_rust is used. Each item must be a triple known
+   * to rustc, such as "aarch64-pc-windows-msvc".
+   */
+  #[serde(default)]
+  pub supported_platform_triples: Vec<String>,
+
+  /**
+   * The name of the Bazel repository in which rules_rust is declared, without the leading "@".
+   *
+   * This is used for the platform conditions of `select` statements, which take the form
+   * "@{rules_rust_repository_name}//rust/platform:{triple}", and for the `load` statements of
+   * the rust rules in generated BUILD files. It may also be set as `rust_rules_workspace_name`.
+   * Default: io_bazel_rules_rust
+   */
+  #[serde(
+    default = "default_raze_settings_field_rules_rust_repository_name",
+    alias = "rust_rules_workspace_name"
+  )]
+  pub rules_rust_repository_name: String,
+
+  /**
+   * Whether planning should fail, rather than warn, when a targeted dependency is dropped because
+   * its target matches none of the `supported_platform_triples`.
+   */
+  #[serde(default)]
+  pub strict_platform_support: bool,
+
+  /**
+   * The tags of every target generated by cargo-raze, which makes them easy to query.
+   *
+   * Default: ["cargo-raze", "manual"]
+   */
+  #[serde(default = "default_raze_settings_field_default_tags")]
+  pub default_tags: Vec<String>,
+
+  /**
+   * The notice at the top of every generated file, warning against editing it by hand.
+   *
+   * Default: "DO NOT EDIT! Replaced on runs of cargo-raze"
+   */
+  #[serde(default = "default_raze_settings_field_generated_file_header")]
+  pub generated_file_header: String,
+
+  /**
+   * Crates that no BUILD file or alias is generated for, such as those provided by a system library.
+   *
+   * Entries take the form "{name}" for every version of a crate, or "{name}=={version}" for a
+   * single one.
+   */
+  #[serde(default)]
+  pub excluded_crates: Vec<String>,
+
+  /**
+   * The directory of each vendored crate under Vendored GenMode, relative to the `workspace_path`.
+   *
+   * The patterns `{crate}` and `{version}` will be used to fill in the package's name (eg: rand)
+   * and version (eg: 0.7.1). `{version}` is required, so that each version of a crate gets its
+   * own directory.
+   * Default: vendor/{crate}-{version}
+   */
+  #[serde(default = "default_raze_settings_field_vendor_dir_template")]
+  pub vendor_dir_template: String,
+
+  /**
+   * Whether the dev-dependencies of crates are planned and rendered.
+   *
+   * When disabled, crates only reached through the dev-dependencies of the workspace are neither
+   * planned nor expected to be vendored. The test and example targets of crates declaring
+   * dev-dependencies are skipped as well, as these would not build without them.
+   */
+  #[serde(default)]
+  pub render_dev_dependencies: bool,
+
   /** Any crate-specific configuration. See CrateSettings for details. */
   #[serde(default)]
   pub crates: HashMap<String, CrateSettingsPerVersion>,
@@ -83,6 +170,14 @@ pub struct RazeSettings {
   #[serde(default = "default_raze_settings_field_genmode")]
   pub genmode: GenMode,
 
+  /**
+   * How the remote crates are fetched. See FetchMode for details.
+   *
+   * This has no effect unless the genmode is Remote.
+   */
+  #[serde(default = "default_raze_settings_field_fetch_mode")]
+  pub fetch_mode: FetchMode,
+
   /**
    * Suffix for generated build files, uses whole value for Vendored mode
    * Default: BUILD
@@ -90,6 +185,41 @@ pub struct RazeSettings {
   #[serde(default = "default_raze_settings_field_output_buildfile_suffix")]
   pub output_buildfile_suffix: String,
 
+  /**
+   * Whether to emit a stub `BUILD` file next to each generated build file.
+   *
+   * This only has an effect when `output_buildfile_suffix` is not `BUILD` (eg: `BUILD.bazel`), and
+   * keeps tooling looking for `BUILD` files working. Bazel reads `BUILD.bazel` in preference.
+   */
+  #[serde(default)]
+  pub output_legacy_buildfile_stubs: bool,
+
+  /**
+   * The package to write the aliases of the root dependencies to, relative to the workspace path
+   * (eg: "aliases"), instead of the root build file.
+   *
+   * The root build file is then left alone, so it can hold hand-written rules. In Remote genmode,
+   * it must exist for the generated `crates.bzl` to be loaded.
+   */
+  #[serde(default)]
+  pub output_aliases_package: Option<String>,
+
+  /**
+   * How the aliases of the root dependencies are named. See AliasNameStyle for details.
+   *
+   * Default: Sanitize
+   */
+  #[serde(default = "default_raze_settings_field_alias_name_style")]
+  pub alias_name_style: AliasNameStyle,
+
+  /**
+   * Whether to convert CRLF line endings to LF in the generated files, which Bazel prefers.
+   *
+   * Default: true
+   */
+  #[serde(default = "default_raze_settings_field_output_normalize_line_endings")]
+  pub output_normalize_line_endings: bool,
+
   /**
    * Default value for per-crate gen_buildrs setting if it's not explicitly for a crate.
    *
@@ -98,15 +228,65 @@ pub struct RazeSettings {
   #[serde(default = "default_raze_settings_field_gen_buildrs")]
   pub default_gen_buildrs: bool,
 
+  /**
+   * The edition given to crates, and their targets, whose metadata does not specify one.
+   *
+   * Default: "2015", as for Cargo
+   */
+  #[serde(default = "default_raze_settings_field_default_edition")]
+  pub default_edition: String,
+
   /**
    * The default crates registry.
    * 
    * The patterns `{crate}` and `{version}` will be used to fill
    * in the package's name (eg: rand) and version (eg: 0.7.1).
    * See https://doc.rust-lang.org/cargo/reference/registries.html#index-format
+   *
+   * Mirrors and proxies laid out differently from crates.io are supported by overriding this
+   * pattern, eg: "https://mirror.example.com/crates/{crate}-{version}.crate".
    */
   #[serde(default = "default_raze_settings_registry")]
   pub registry: String,
+
+  /**
+   * Crate registries other than the default one, keyed by the URL of their index.
+   *
+   * The index URL is the one Cargo records for the crate's source (eg: the `index` of a registry
+   * declared in `.cargo/config`). The values use the same `{crate}` and `{version}` patterns as
+   * the `registry` setting.
+   */
+  #[serde(default)]
+  pub registries: HashMap<String, String>,
+
+  /**
+   * Whether or not to format the generated files with buildifier.
+   *
+   * If buildifier cannot be run or fails to format a file, the file is written unformatted.
+   */
+  #[serde(default)]
+  pub format_output: bool,
+
+  /**
+   * The path to the buildifier binary used when `format_output` is set.
+   *
+   * Defaults to looking up "buildifier" on the PATH.
+   */
+  #[serde(default)]
+  pub buildifier_path: Option<String>,
+
+  /**
+   * Whether to write a `cargo-raze.lock` file next to the generated files.
+   *
+   * It records the name, version, source and sha256 of every crate, so that later runs with
+   * `--locked` fail if any of these drifted.
+   */
+  #[serde(default)]
+  pub gen_lockfile: bool,
+
+  /** How to render the generated files. See RendererKind for details. */
+  #[serde(default = "default_raze_settings_field_renderer")]
+  pub renderer: RendererKind,
 }
 
 /** Override settings for individual crates (as part of `RazeSettings`). */
@@ -127,51 +307,184 @@ pub struct CrateSettings {
   pub additional_deps: Vec<String>,
 
   /**
-   * Dependencies to be removed from a crate, in the form "{dep-name}-{dep-version}"
+   * Proc macro dependencies to be added to a crate, referenced like the `additional_deps`.
+   *
+   * Both are rendered verbatim, sorted along with the dependencies resolved from Cargo.
+   */
+  #[serde(default)]
+  pub additional_proc_macro_deps: Vec<String>,
+
+  /**
+   * Dependencies to be added to a single target of the crate, keyed by the kind and name of the
+   * target, such as "bin:{name}" or "lib:{name}", and referenced like the `additional_deps`. The
+   * kind tells apart the binary and library of a package, which share its name.
+   *
+   * Cargo resolves the dependencies of a package as a whole, so this is how a binary can depend on
+   * crates its library goes without, such as those of a command line interface. They are sorted
+   * along with the dependencies shared by every target of the crate.
+   */
+  #[serde(default)]
+  pub additional_target_deps: BTreeMap<String, Vec<String>>,
+
+  /**
+   * Dependencies to be removed from a crate, in the form "{dep-name}" for every version of the
+   * dependency, or "{dep-name}-{dep-version}" for a single one.
    *
-   * This is applied during Cargo analysis, so it uses Cargo-style labeling
+   * This is applied during Cargo analysis, so it uses Cargo-style labeling. A removed dependency
+   * that the crate does not declare as optional is reported as a planning warning, since it will
+   * usually have to be provided through `additional_deps` instead.
    */
   #[serde(default)]
   pub skipped_deps: Vec<String>,
 
   /**
-   * Library targets that should be aliased in the root BUILD file.
+   * Targets of the crate that should be aliased in the root BUILD file, besides its library.
    *
    * This is useful to facilitate using binary utility crates, such as bindgen, as part of genrules.
+   * Binary targets are named after the binary, prefixed with `cargo_bin_`.
    */
   #[serde(default)]
   pub extra_aliased_targets: Vec<String>,
 
+  /**
+   * The default visibility of the targets generated for this crate.
+   *
+   * Defaults to "//visibility:public". When restricting visibility, remember to include the
+   * `workspace_path` package (eg: "//cargo:__pkg__") so that its aliases remain usable.
+   */
+  #[serde(default)]
+  pub visibility: Vec<String>,
+
+  /** Tags to be added to the targets generated for this crate, besides the `default_tags`. */
+  #[serde(default)]
+  pub tags: Vec<String>,
+
+  /**
+   * Alternate crate types the library of this crate is also built as, such as "cdylib" or
+   * "staticlib" for FFI consumers.
+   *
+   * Each crate type gets a rust_library named "{library}_{crate_type}", next to the library used
+   * by the crate's Rust dependents.
+   */
+  #[serde(default)]
+  pub crate_types: Vec<String>,
+
   /** Flags to be added to the crate compilation process, in the form "--flag". */
   #[serde(default)]
   pub additional_flags: Vec<String>,
 
-  /** Environment variables to be added to the crate compilation process. */
+  /**
+   * Environment variables to be added to the crate compilation process.
+   *
+   * Values are rendered into the `rustc_env` attribute of every target, sorted by key. The build
+   * script has its own environment, see `buildrs_additional_environment_variables`.
+   */
   #[serde(default)]
-  pub additional_env: HashMap<String, String>,
+  pub additional_env: BTreeMap<String, String>,
+
+  /**
+   * The name of the build file generated for this crate, in place of `output_buildfile_suffix`.
+   *
+   * This is useful when the default name would clash with a file of the crate, such as a vendored
+   * crate already shipping a `BUILD` file.
+   */
+  #[serde(default)]
+  pub buildfile_suffix_override: Option<String>,
 
   /**
    * Whether or not to generate the build script that goes with this crate.
    *
    * Many build scripts will not function, as they will still be built hermetically. However, build
    * scripts that merely generate files into OUT_DIR may be fully functional.
+   *
+   * When unset, this falls back to `default_gen_buildrs`.
    */
   #[serde(default = "default_crate_settings_field_gen_buildrs")]
   pub gen_buildrs: Option<bool>,
 
+  /**
+   * Whether or not to leave out the build script of this crate, regardless of `gen_buildrs`.
+   *
+   * This is meant for build scripts that cannot run hermetically, such as those accessing the
+   * network. What they would have output for the crate compilation is then supplied statically by
+   * `build_script_rustc_flags` and `build_script_rustc_env`.
+   */
+  #[serde(default)]
+  pub skip_build_script: bool,
+
+  /**
+   * Flags a skipped build script would have passed to the crate compilation, such as the
+   * `--cfg=...` flags of its `cargo:rustc-cfg` outputs.
+   *
+   * This has no effect unless `skip_build_script` is set.
+   */
+  #[serde(default)]
+  pub build_script_rustc_flags: Vec<String>,
+
+  /**
+   * Environment variables a skipped build script would have set for the crate compilation, through
+   * its `cargo:rustc-env` outputs.
+   *
+   * This has no effect unless `skip_build_script` is set.
+   */
+  #[serde(default)]
+  pub build_script_rustc_env: BTreeMap<String, String>,
+
+  /**
+   * Whether or not to generate `rust_doc` and `rust_doc_test` targets for the crate's library.
+   *
+   * This is off by default, as most dependencies don't need their documentation built.
+   */
+  #[serde(default)]
+  pub gen_docs: bool,
+
+  /**
+   * Whether or not to generate `rust_binary` targets for the crate's examples.
+   *
+   * Examples build against the crate's library and its dev dependencies. This is off by default,
+   * but is useful for crates whose examples are the tools users want, such as code generators.
+   */
+  #[serde(default)]
+  pub gen_examples: bool,
+
+  /**
+   * Whether the optional features of the crate can be toggled at build time.
+   *
+   * Each feature that is left out by Cargo's resolution gets a `bool_flag` named
+   * `feature_{feature}` in the crate's package, and is added to the `crate_features` of its
+   * targets when the flag is set, as with `--//path/to/crate:feature_{feature}`. Features which
+   * enable dependencies cannot be toggled, since those dependencies are not part of the build.
+   */
+  #[serde(default)]
+  pub selectable_features: bool,
+
   /**
    * The verbatim `data` clause to be included for the generated build targets.
    *
+   * Each item is a Starlark list expression, such as `glob(["*.proto"])` or `["//some:label"]`.
+   * Multiple items are concatenated. A single string is also accepted.
+   *
    * N.B. Build scripts are always provided all crate files for their `data` attr.
    */
-  #[serde(default = "default_crate_settings_field_data_attr")]
-  pub data_attr: Option<String>,
+  #[serde(default, deserialize_with = "deserialize_string_or_vec")]
+  pub data_attr: Vec<String>,
+
+  /**
+   * The verbatim `compile_data` clause to be included for the generated build targets.
+   *
+   * This takes the same form as `data_attr`. These files are made available at compile time, for
+   * example to `include_str!`.
+   */
+  #[serde(default, deserialize_with = "deserialize_string_or_vec")]
+  pub compile_data_attr: Vec<String>,
 
   /**
    * Additional environment variables to add when running the build script.
+   *
+   * Values are rendered into the `build_script_env` attribute, sorted by key.
    */
   #[serde(default)]
-  pub buildrs_additional_environment_variables: HashMap<String, String>,
+  pub buildrs_additional_environment_variables: BTreeMap<String, String>,
 
   /**
    * The arguments given to the patch tool.
@@ -222,15 +535,53 @@ pub struct CrateSettings {
   #[serde(default)]
   pub patches: Vec<String>,
 
+  /**
+   * The expected sha256 checksum of the crate's archive.
+   *
+   * Planning fails if this does not match the checksum of the archive downloaded by Cargo.
+   */
+  #[serde(default)]
+  pub sha256: Option<String>,
+
+  /**
+   * Whether the submodules of a crate fetched from git should be initialized.
+   *
+   * This sets `init_submodules` on the `new_git_repository` of the crate, which fails to build
+   * crates using submodules when unset. It has no effect unless the crate is a git dependency
+   * under Remote GenMode.
+   * Default: true
+   */
+  #[serde(default = "default_crate_settings_field_init_submodules")]
+  pub init_submodules: bool,
+
   /**
    * Path to a file to be included as part of the generated BUILD file.
    *
    * For example, some crates include non-Rust code typically built through a build.rs script. They
    * can be made compatible by manually writing appropriate Bazel targets, and including them into
    * the crate through a combination of additional_build_file and additional_deps.
+   *
+   * Relative paths are resolved from the root of the Bazel workspace.
    */
   #[serde(default)]
   pub additional_build_file: Option<String>,
+
+  /**
+   * Paths to files to be included as part of the generated BUILD file, in order.
+   *
+   * These are included after any additional_build_file, such as a prelude shared by several crates
+   * followed by a crate-specific snippet.
+   */
+  #[serde(default)]
+  pub additional_build_files: Vec<String>,
+
+  /**
+   * Content to be included as part of the generated BUILD file, after any additional build files.
+   *
+   * This avoids creating a file on disk for a few lines of Bazel targets.
+   */
+  #[serde(default)]
+  pub additional_build_content: Option<String>,
 }
 
 /**
@@ -242,7 +593,8 @@ pub struct CrateSettings {
  *
  * Vendored:
  * This mode assumes that files are vendored (into vendor/), and generates BUILD files
- * accordingly
+ * accordingly. Crates reference each other by workspace labels, such as
+ * "//cargo/vendor/foo-1.0.0:foo", so no external repository is involved.
  */
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub enum GenMode {
@@ -250,23 +602,100 @@ pub enum GenMode {
   Remote,
 }
 
+/**
+ * Describes how the aliases of the root dependencies are named. Options are {Sanitize, Preserve}.
+ *
+ * Sanitize:
+ * This mode names aliases after the crate name with hyphens replaced by underscores (eg:
+ * "serde_json"), as it is spelled in Rust code.
+ *
+ * Preserve:
+ * This mode names aliases after the package name exactly as published (eg: "serde-json"), which
+ * Bazel target names allow.
+ */
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AliasNameStyle {
+  Sanitize,
+  Preserve,
+}
+
+/**
+ * Describes how remote crates are fetched. Options are {Workspace, Bzlmod}.
+ *
+ * Workspace:
+ * This mode generates a `crates.bzl` file defining a function to be called in a WORKSPACE file.
+ *
+ * Bzlmod:
+ * This mode generates an `extensions.bzl` file defining a module extension, along with a
+ * `MODULE.bazel.snippet` file holding the statements that bring its repositories into a
+ * MODULE.bazel file.
+ */
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum FetchMode {
+  Workspace,
+  Bzlmod,
+}
+
+/**
+ * Describes how the generated files are rendered. Options are {Tera, Starlark}.
+ *
+ * Tera:
+ * This mode fills in the text templates bundled with cargo-raze.
+ *
+ * Starlark:
+ * This mode builds the Starlark statements of each file before serializing them, which escapes
+ * all attribute values by construction.
+ */
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub enum RendererKind {
+  Tera,
+  Starlark,
+}
+
+impl RendererKind {
+  /** The name the renderer is registered under in the default `RendererRegistry`. */
+  pub fn registry_name(&self) -> &'static str {
+    match self {
+      Self::Tera => "bazel",
+      Self::Starlark => "starlark-ast",
+    }
+  }
+}
+
 impl Default for CrateSettings {
   fn default() -> Self {
     Self {
       additional_deps: Vec::new(),
+      additional_proc_macro_deps: Vec::new(),
+      additional_target_deps: BTreeMap::new(),
       skipped_deps: Vec::new(),
       extra_aliased_targets: Vec::new(),
+      visibility: Vec::new(),
+      tags: Vec::new(),
+      crate_types: Vec::new(),
       additional_flags: Vec::new(),
-      additional_env: HashMap::new(),
+      additional_env: BTreeMap::new(),
+      buildfile_suffix_override: None,
       gen_buildrs: default_crate_settings_field_gen_buildrs(),
-      data_attr: default_crate_settings_field_data_attr(),
-      buildrs_additional_environment_variables: HashMap::new(),
+      skip_build_script: false,
+      build_script_rustc_flags: Vec::new(),
+      build_script_rustc_env: BTreeMap::new(),
+      gen_docs: false,
+      gen_examples: false,
+      selectable_features: false,
+      data_attr: Vec::new(),
+      compile_data_attr: Vec::new(),
+      buildrs_additional_environment_variables: BTreeMap::new(),
       patch_args: Vec::new(),
       patch_cmds: Vec::new(),
       patch_cmds_win: Vec::new(),
       patch_tool: None,
       patches: Vec::new(),
+      sha256: None,
+      init_submodules: default_crate_settings_field_init_submodules(),
       additional_build_file: None,
+      additional_build_files: Vec::new(),
+      additional_build_content: None,
     }
   }
 }
@@ -275,18 +704,54 @@ fn default_raze_settings_field_gen_workspace_prefix() -> String {
   "raze".to_owned()
 }
 
+fn default_raze_settings_field_rules_rust_repository_name() -> String {
+  "io_bazel_rules_rust".to_owned()
+}
+
+fn default_raze_settings_field_default_tags() -> Vec<String> {
+  vec!["cargo-raze".to_owned(), "manual".to_owned()]
+}
+
+fn default_raze_settings_field_generated_file_header() -> String {
+  "DO NOT EDIT! Replaced on runs of cargo-raze".to_owned()
+}
+
+fn default_raze_settings_field_vendor_dir_template() -> String {
+  "vendor/{crate}-{version}".to_owned()
+}
+
 fn default_raze_settings_field_genmode() -> GenMode {
   GenMode::Vendored
 }
 
+fn default_raze_settings_field_fetch_mode() -> FetchMode {
+  FetchMode::Workspace
+}
+
+fn default_raze_settings_field_alias_name_style() -> AliasNameStyle {
+  AliasNameStyle::Sanitize
+}
+
+fn default_raze_settings_field_renderer() -> RendererKind {
+  RendererKind::Tera
+}
+
 fn default_raze_settings_field_output_buildfile_suffix() -> String {
   "BUILD.bazel".to_owned()
 }
 
+fn default_raze_settings_field_output_normalize_line_endings() -> bool {
+  true
+}
+
 fn default_raze_settings_field_gen_buildrs() -> bool {
   false
 }
 
+fn default_raze_settings_field_default_edition() -> String {
+  "2015".to_owned()
+}
+
 fn default_raze_settings_registry() -> String {
   "https://crates-io.s3-us-west-1.amazonaws.com/crates/{crate}/{crate}-{version}.crate".to_string()
 }
@@ -295,14 +760,44 @@ fn default_crate_settings_field_gen_buildrs() -> Option<bool> {
   None
 }
 
-fn default_crate_settings_field_data_attr() -> Option<String> {
-  None
+fn default_crate_settings_field_init_submodules() -> bool {
+  true
 }
 
 fn incompatible_relative_workspace_path() -> bool {
   false
 }
 
+/** A serde visitor which accepts either a single string or a list of strings. */
+struct StringOrVecVisitor;
+
+impl<'de> Visitor<'de> for StringOrVecVisitor {
+  type Value = Vec<String>;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("a string or a list of strings")
+  }
+
+  fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+    Ok(vec![value.to_owned()])
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    let mut values = Vec::new();
+    while let Some(value) = seq.next_element()? {
+      values.push(value);
+    }
+    Ok(values)
+  }
+}
+
+/** Deserializes a field that may be either a single string or a list of strings. */
+fn deserialize_string_or_vec<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+  deserializer.deserialize_any(StringOrVecVisitor)
+}
+
 /** Formats a registry url to include the name and version fo the target package */
 pub fn format_registry_url(registry_url: &String, name: &String, version: &String) -> String {
   registry_url
@@ -310,6 +805,25 @@ pub fn format_registry_url(registry_url: &String, name: &String, version: &Strin
     .replace("{version}", version)
 }
 
+/** Whether a version of a crate is among the `excluded_crates`. */
+pub fn is_excluded_crate(excluded_crates: &[String], name: &str, version: &str) -> bool {
+  excluded_crates
+    .iter()
+    .any(|excluded_crate| match excluded_crate.split_once("==") {
+      Some((excluded_name, excluded_version)) => {
+        excluded_name == name && excluded_version == version
+      },
+      None => excluded_crate == name,
+    })
+}
+
+/** Whether a version of a dependency is among the `skipped_deps` of a crate. */
+pub fn is_skipped_dep(skipped_deps: &[String], name: &str, version: &str) -> bool {
+  skipped_deps
+    .iter()
+    .any(|skipped_dep| skipped_dep == name || *skipped_dep == format!("{}-{}", name, version))
+}
+
 /** Verifies that the provided settings make sense. */
 fn validate_settings(settings: &mut RazeSettings) -> Result<(), RazeError> {
   if !settings.workspace_path.starts_with("//") {
@@ -330,6 +844,53 @@ fn validate_settings(settings: &mut RazeSettings) -> Result<(), RazeError> {
     settings.workspace_path.pop();
   }
 
+  if !settings.vendor_dir_template.contains("{version}") {
+    return Err(RazeError::Config {
+      field_path_opt: Some("raze.vendor_dir_template".to_owned()),
+      message: format!(
+        "\"{}\" must contain \"{{version}}\", otherwise every version of a crate is vendored into \
+         the same directory.",
+        settings.vendor_dir_template
+      ),
+    });
+  }
+
+  for triple in settings.supported_platform_triples.iter() {
+    if get_builtin_target_by_triple(triple).is_none() {
+      return Err(RazeError::Config {
+        field_path_opt: Some("raze.supported_platform_triples".to_owned()),
+        message: format!(
+          "\"{}\" is not a platform triple known to rustc. Run `rustc --print target-list` for \
+           the list of known triples.",
+          triple
+        ),
+      });
+    }
+  }
+
+  for (crate_name, crate_settings_per_version) in settings.crates.iter() {
+    for (version, crate_settings) in crate_settings_per_version.iter() {
+      for target_key in crate_settings.additional_target_deps.keys() {
+        let is_kind_and_name = match target_key.split_once(':') {
+          Some((kind, name)) => !kind.is_empty() && !name.is_empty(),
+          None => false,
+        };
+        if !is_kind_and_name {
+          return Err(RazeError::Config {
+            field_path_opt: Some(format!(
+              "raze.crates.{}.{}.additional_target_deps",
+              crate_name, version
+            )),
+            message: format!(
+              "\"{}\" must be the kind and name of a target, such as \"bin:{}\".",
+              target_key, target_key
+            ),
+          });
+        }
+      }
+    }
+  }
+
   Ok(())
 }
 
@@ -370,13 +931,32 @@ pub mod testing {
       workspace_path: "//cargo".to_owned(),
       target: Some("x86_64-unknown-linux-gnu".to_owned()),
       targets: None,
+      supported_platform_triples: Vec::new(),
+      rules_rust_repository_name: default_raze_settings_field_rules_rust_repository_name(),
+      strict_platform_support: false,
+      default_tags: default_raze_settings_field_default_tags(),
+      generated_file_header: default_raze_settings_field_generated_file_header(),
+      excluded_crates: Vec::new(),
+      vendor_dir_template: default_raze_settings_field_vendor_dir_template(),
+      render_dev_dependencies: false,
       crates: HashMap::new(),
       gen_workspace_prefix: "raze_test".to_owned(),
       genmode: GenMode::Remote,
+      fetch_mode: default_raze_settings_field_fetch_mode(),
       output_buildfile_suffix: "BUILD".to_owned(),
+      output_legacy_buildfile_stubs: false,
+      output_aliases_package: None,
+      alias_name_style: default_raze_settings_field_alias_name_style(),
+      output_normalize_line_endings: default_raze_settings_field_output_normalize_line_endings(),
       default_gen_buildrs: default_raze_settings_field_gen_buildrs(),
+      default_edition: default_raze_settings_field_default_edition(),
       incompatible_relative_workspace_path: incompatible_relative_workspace_path(),
       registry: default_raze_settings_registry(),
+      registries: HashMap::new(),
+      format_output: false,
+      buildifier_path: None,
+      gen_lockfile: false,
+      renderer: default_raze_settings_field_renderer(),
     }
   }
 
@@ -413,6 +993,132 @@ pub mod testing {
     load_settings(cargo_toml_path).unwrap();
   }
 
+  #[test]
+  fn test_loading_data_attrs() {
+    let toml_contents = r#"
+    [package]
+    name = "load_settings_test"
+    version = "0.1.0"
+
+    [lib]
+    path = "not_a_file.rs"
+
+    [raze]
+    workspace_path = "//workspace_path/raze"
+
+    [raze.crates.some-crate.'0.1.0']
+    data_attr = "glob([\"data/**\"])"
+    compile_data_attr = ["glob([\"**/*.proto\"])", "[\"//some:label\"]"]
+    "#;
+    let temp_workspace_dir = TempDir::new().expect("Failed to set up temporary directory");
+    let cargo_toml_path = temp_workspace_dir.path().join("Cargo.toml");
+    let mut toml = File::create(&cargo_toml_path).unwrap();
+    toml.write_all(toml_contents.as_bytes()).unwrap();
+
+    let settings = load_settings(cargo_toml_path).unwrap();
+    let crate_settings = &settings.crates["some-crate"][&Version::new(0, 1, 0)];
+    assert_eq!(crate_settings.data_attr, vec!["glob([\"data/**\"])"]);
+    assert_eq!(
+      crate_settings.compile_data_attr,
+      vec!["glob([\"**/*.proto\"])", "[\"//some:label\"]"]
+    );
+  }
+
+  #[test]
+  fn test_loading_rust_rules_workspace_name() {
+    let toml_contents = r#"
+    [package]
+    name = "load_settings_test"
+    version = "0.1.0"
+
+    [lib]
+    path = "not_a_file.rs"
+
+    [raze]
+    workspace_path = "//workspace_path/raze"
+    rust_rules_workspace_name = "rules_rust"
+    "#;
+    let temp_workspace_dir = TempDir::new().expect("Failed to set up temporary directory");
+    let cargo_toml_path = temp_workspace_dir.path().join("Cargo.toml");
+    let mut toml = File::create(&cargo_toml_path).unwrap();
+    toml.write_all(toml_contents.as_bytes()).unwrap();
+
+    let settings = load_settings(cargo_toml_path).unwrap();
+    assert_eq!(settings.rules_rust_repository_name, "rules_rust");
+  }
+
+  #[test]
+  fn test_validating_supported_platform_triples() {
+    let mut settings = dummy_raze_settings();
+    settings.supported_platform_triples = vec!["aarch64-pc-windows-msvc".to_owned()];
+    assert!(validate_settings(&mut settings).is_ok());
+
+    settings.supported_platform_triples = vec!["unknown-unknown-unknown".to_owned()];
+    assert!(validate_settings(&mut settings).is_err());
+  }
+
+  #[test]
+  fn test_validating_vendor_dir_template() {
+    let mut settings = dummy_raze_settings();
+    settings.vendor_dir_template = "third_party/rust/{crate}/{version}".to_owned();
+    assert!(validate_settings(&mut settings).is_ok());
+
+    settings.vendor_dir_template = "third_party/rust/{crate}".to_owned();
+    let err = validate_settings(&mut settings).unwrap_err();
+    assert!(err.to_string().contains("{version}"));
+  }
+
+  #[test]
+  fn test_validating_additional_target_deps() {
+    let mut settings = dummy_raze_settings();
+    let mut crate_settings_per_version = HashMap::new();
+    crate_settings_per_version.insert(
+      Version::parse("0.1.0").unwrap(),
+      CrateSettings {
+        additional_target_deps: vec![("bin:foo".to_owned(), vec!["//cli:clap".to_owned()])]
+          .into_iter()
+          .collect(),
+        ..CrateSettings::default()
+      },
+    );
+    settings
+      .crates
+      .insert("foo".to_owned(), crate_settings_per_version.clone());
+    assert!(validate_settings(&mut settings).is_ok());
+
+    crate_settings_per_version
+      .values_mut()
+      .for_each(|crate_settings| {
+        crate_settings.additional_target_deps =
+          vec![("foo".to_owned(), Vec::new())].into_iter().collect()
+      });
+    settings
+      .crates
+      .insert("foo".to_owned(), crate_settings_per_version);
+    let err = validate_settings(&mut settings).unwrap_err();
+    assert!(err.to_string().contains("\"bin:foo\""));
+  }
+
+  #[test]
+  fn test_matching_excluded_crates() {
+    let excluded_crates = vec!["openssl-sys".to_owned(), "libz-sys==1.1.2".to_owned()];
+
+    assert!(is_excluded_crate(&excluded_crates, "openssl-sys", "0.9.58"));
+    assert!(is_excluded_crate(&excluded_crates, "libz-sys", "1.1.2"));
+    assert!(!is_excluded_crate(&excluded_crates, "libz-sys", "1.1.3"));
+    assert!(!is_excluded_crate(&excluded_crates, "openssl", "0.10.30"));
+  }
+
+  #[test]
+  fn test_matching_skipped_deps() {
+    let skipped_deps = vec!["openssl-sys".to_owned(), "libz-sys-1.1.2".to_owned()];
+
+    assert!(is_skipped_dep(&skipped_deps, "openssl-sys", "0.9.58"));
+    assert!(is_skipped_dep(&skipped_deps, "libz-sys", "1.1.2"));
+    assert!(!is_skipped_dep(&skipped_deps, "libz-sys", "1.1.3"));
+    assert!(!is_skipped_dep(&skipped_deps, "openssl", "0.10.30"));
+  }
+
   #[test]
   fn test_formatting_registry_url() {
     assert_eq!(
@@ -0,0 +1,2351 @@
+// Copyright 2020 Google Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+use crate::{
+  bazel::{include_additional_build_file, root_alias_key, root_dependency_aliases},
+  context::{
+    BuildableDependency, BuildableTarget, CrateContext, CrateDependencyContext,
+    CrateTargetedDepContext, WorkspaceContext,
+  },
+  planning::PlannedBuild,
+  rendering::{
+    add_legacy_buildfile_stubs, ignore_crates_rendered, normalize_line_endings,
+    sort_by_repository_name, BuildRenderer, CrateRenderedHook, FileOutputs, RenderDetails,
+  },
+  settings::FetchMode,
+  util,
+};
+
+use rayon::prelude::*;
+
+const INDENT: &str = "    ";
+
+/** A Starlark expression. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+  /** A string literal, escaped when serialized. */
+  Str(String),
+  Bool(bool),
+  /** A bare identifier, such as a rule or function name. */
+  Ident(String),
+  /**
+   * An expression that is emitted verbatim.
+   *
+   * This is only meant for user-provided Starlark, such as the `data_attr` crate setting.
+   */
+  Raw(String),
+  List(Vec<Expr>),
+  Tuple(Vec<Expr>),
+  Dict(Vec<(Expr, Expr)>),
+  Call(Call),
+  /** The concatenation of several expressions, eg: `[...] + select(...)`. */
+  Add(Vec<Expr>),
+  /** An expression followed by a line comment when it is part of a multi-line collection. */
+  Commented(Box<Expr>, String),
+}
+
+/** An argument to a Starlark function call, optionally named. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arg {
+  pub name: Option<String>,
+  pub value: Expr,
+}
+
+/** A Starlark function call, such as a rule instantiation. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+  pub function: String,
+  pub args: Vec<Arg>,
+}
+
+/** A Starlark statement. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+  /** A triple-quoted docstring. */
+  Docstring(String),
+  /** A line comment, without the leading "# ". */
+  Comment(String),
+  Load {
+    module: String,
+    symbols: Vec<String>,
+  },
+  Expr(Expr),
+  /** An assignment of an expression to a name. */
+  Assign {
+    name: String,
+    value: Expr,
+  },
+  Def {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+  },
+  Pass,
+  /** An empty line. */
+  Blank,
+}
+
+impl Call {
+  pub fn new(function: &str) -> Self {
+    Call {
+      function: function.to_owned(),
+      args: Vec::new(),
+    }
+  }
+
+  /** Adds a positional argument to the call. */
+  pub fn arg(mut self, value: Expr) -> Self {
+    self.args.push(Arg {
+      name: None,
+      value,
+    });
+    self
+  }
+
+  /** Adds a named argument to the call. */
+  pub fn named(mut self, name: &str, value: Expr) -> Self {
+    self.args.push(Arg {
+      name: Some(name.to_owned()),
+      value,
+    });
+    self
+  }
+}
+
+impl From<Call> for Expr {
+  fn from(call: Call) -> Self {
+    Expr::Call(call)
+  }
+}
+
+impl Expr {
+  pub fn str(value: &str) -> Self {
+    Expr::Str(value.to_owned())
+  }
+
+  /** A list of string literals. */
+  pub fn str_list<S: AsRef<str>>(values: &[S]) -> Self {
+    Expr::List(
+      values
+        .iter()
+        .map(|value| Expr::str(value.as_ref()))
+        .collect(),
+    )
+  }
+
+  /** Whether the expression is serialized over several lines. */
+  fn is_multiline(&self) -> bool {
+    match self {
+      Expr::Str(_) | Expr::Bool(_) | Expr::Ident(_) | Expr::Raw(_) => false,
+      Expr::Commented(..) => true,
+      Expr::List(items) | Expr::Tuple(items) => {
+        items.len() > 1 || items.iter().any(Expr::is_multiline)
+      },
+      Expr::Dict(entries) => !entries.is_empty(),
+      Expr::Call(call) => call
+        .args
+        .iter()
+        .any(|arg| arg.name.is_some() || arg.value.is_multiline()),
+      Expr::Add(parts) => parts.iter().any(Expr::is_multiline),
+    }
+  }
+
+  fn write(&self, out: &mut String, depth: usize) {
+    match self {
+      Expr::Str(value) => out.push_str(&quote(value)),
+      Expr::Bool(true) => out.push_str("True"),
+      Expr::Bool(false) => out.push_str("False"),
+      Expr::Ident(name) | Expr::Raw(name) => out.push_str(name),
+      Expr::List(items) => write_items(out, depth, "[", "]", items),
+      Expr::Tuple(items) if items.len() == 1 && !self.is_multiline() => {
+        // A single element tuple needs a trailing comma to not be a parenthesized expression
+        out.push('(');
+        items[0].write(out, depth);
+        out.push_str(",)");
+      },
+      Expr::Tuple(items) => write_items(out, depth, "(", ")", items),
+      Expr::Dict(entries) => {
+        if entries.is_empty() {
+          out.push_str("{}");
+          return;
+        }
+        out.push_str("{\n");
+        for (key, value) in entries {
+          indent(out, depth + 1);
+          key.write(out, depth + 1);
+          out.push_str(": ");
+          value.write(out, depth + 1);
+          out.push_str(",\n");
+        }
+        indent(out, depth);
+        out.push('}');
+      },
+      Expr::Call(call) => {
+        out.push_str(&call.function);
+        if !self.is_multiline() {
+          out.push('(');
+          for (idx, arg) in call.args.iter().enumerate() {
+            if idx > 0 {
+              out.push_str(", ");
+            }
+            arg.value.write(out, depth);
+          }
+          out.push(')');
+          return;
+        }
+        out.push_str("(\n");
+        for arg in &call.args {
+          indent(out, depth + 1);
+          if let Some(name) = &arg.name {
+            out.push_str(name);
+            out.push_str(" = ");
+          }
+          arg.value.write(out, depth + 1);
+          out.push_str(",\n");
+        }
+        indent(out, depth);
+        out.push(')');
+      },
+      Expr::Add(parts) => {
+        for (idx, part) in parts.iter().enumerate() {
+          if idx > 0 {
+            out.push_str(" + ");
+          }
+          part.write(out, depth);
+        }
+      },
+      Expr::Commented(expr, _) => expr.write(out, depth),
+    }
+  }
+}
+
+fn indent(out: &mut String, depth: usize) {
+  for _ in 0..depth {
+    out.push_str(INDENT);
+  }
+}
+
+fn write_items(out: &mut String, depth: usize, open: &str, close: &str, items: &[Expr]) {
+  out.push_str(open);
+  if items.len() == 1 && !items[0].is_multiline() {
+    items[0].write(out, depth);
+    out.push_str(close);
+    return;
+  }
+  if !items.is_empty() {
+    out.push('\n');
+    for item in items {
+      indent(out, depth + 1);
+      item.write(out, depth + 1);
+      out.push(',');
+      if let Expr::Commented(_, comment) = item {
+        out.push_str("  # ");
+        out.push_str(&single_line(comment));
+      }
+      out.push('\n');
+    }
+    indent(out, depth);
+  }
+  out.push_str(close);
+}
+
+/** Comments cannot span several lines, so line breaks are flattened. */
+fn single_line(text: &str) -> String {
+  text.replace(&['\n', '\r'][..], " ")
+}
+
+/**
+ * Escapes the given value to be embedded in a double-quoted Starlark string literal.
+ *
+ * This is the `starlark_escape` filter of the templates of `BazelRenderer`.
+ */
+pub fn escape_starlark(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      '\t' => escaped.push_str("\\t"),
+      // Starlark supports octal escapes for any other control character
+      c if (c as u32) < 0x20 || c == '\u{7f}' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/** Escapes a value for use within a triple-quoted Starlark docstring. */
+pub fn escape_starlark_docstring(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace("\"\"\"", "\\\"\\\"\\\"")
+}
+
+/** Produces a double-quoted Starlark string literal for the given value. */
+pub fn quote(value: &str) -> String {
+  format!("\"{}\"", escape_starlark(value))
+}
+
+impl Stmt {
+  fn write(&self, out: &mut String, depth: usize) {
+    match self {
+      Stmt::Blank => {},
+      Stmt::Docstring(text) => {
+        indent(out, depth);
+        out.push_str("\"\"\"");
+        out.push_str(&escape_starlark_docstring(text));
+        out.push_str("\"\"\"");
+      },
+      Stmt::Comment(text) => {
+        indent(out, depth);
+        out.push('#');
+        if !text.is_empty() {
+          out.push(' ');
+          out.push_str(&single_line(text));
+        }
+      },
+      Stmt::Load {
+        module,
+        symbols,
+      } => {
+        let mut args = vec![Expr::str(module)];
+        args.extend(symbols.iter().map(|symbol| Expr::str(symbol)));
+        indent(out, depth);
+        // Loads are multi-line as soon as there is more than one symbol to import
+        if symbols.len() > 1 {
+          write_items(out, depth, "load(", ")", &args);
+        } else {
+          let call = args.into_iter().fold(Call::new("load"), Call::arg);
+          Expr::Call(call).write(out, depth);
+        }
+      },
+      Stmt::Expr(expr) => {
+        indent(out, depth);
+        expr.write(out, depth);
+      },
+      Stmt::Assign {
+        name,
+        value,
+      } => {
+        indent(out, depth);
+        out.push_str(name);
+        out.push_str(" = ");
+        value.write(out, depth);
+      },
+      Stmt::Def {
+        name,
+        params,
+        body,
+      } => {
+        indent(out, depth);
+        out.push_str("def ");
+        out.push_str(name);
+        out.push('(');
+        out.push_str(&params.join(", "));
+        out.push_str("):");
+        for stmt in body {
+          out.push('\n');
+          stmt.write(out, depth + 1);
+        }
+      },
+      Stmt::Pass => {
+        indent(out, depth);
+        out.push_str("pass");
+      },
+    }
+  }
+}
+
+/** Serializes a list of top level statements into the contents of a Starlark file. */
+pub fn serialize(stmts: &[Stmt]) -> String {
+  let mut out = String::new();
+  for stmt in stmts {
+    stmt.write(&mut out, 0);
+    out.push('\n');
+  }
+  out
+}
+
+fn generated_docstring(workspace_context: &WorkspaceContext, description: &str) -> Stmt {
+  Stmt::Docstring(format!(
+    "\n@generated\n{}\n\n{}\n",
+    description, workspace_context.generated_file_header
+  ))
+}
+
+fn cargo_raze_tags(workspace_context: &WorkspaceContext) -> Expr {
+  Expr::str_list(&workspace_context.default_tags)
+}
+
+/** The default tags, followed by any other tags from the crate settings. */
+fn crate_tags(workspace_context: &WorkspaceContext, package: &CrateContext) -> Expr {
+  let mut tags = workspace_context.default_tags.clone();
+  for tag in &package.raze_settings.tags {
+    if !tags.contains(tag) {
+      tags.push(tag.clone());
+    }
+  }
+  Expr::str_list(&tags)
+}
+
+fn sanitize(name: &str) -> String {
+  name.replace("-", "_")
+}
+
+fn alias(workspace_context: &WorkspaceContext, name: &str, actual: &str) -> Stmt {
+  Stmt::Expr(
+    Call::new("alias")
+      .named("name", Expr::str(name))
+      .named("actual", Expr::str(actual))
+      .named("tags", cargo_raze_tags(workspace_context))
+      .into(),
+  )
+}
+
+/**
+ * A renderer that builds an AST of Starlark statements for each file, rather than filling in
+ * text templates.
+ *
+ * Every attribute value goes through `Expr`, so string values are escaped by construction. The
+ * output is equivalent to that of `BazelRenderer`, though not necessarily formatted identically.
+ */
+#[derive(Default)]
+pub struct StarlarkRenderer;
+
+impl StarlarkRenderer {
+  pub fn new() -> Self {
+    StarlarkRenderer
+  }
+
+  pub fn render_crate(
+    &self,
+    workspace_context: &WorkspaceContext,
+    package: &CrateContext,
+  ) -> String {
+    let mut package = package.clone();
+    package.sort_dependencies_by_label();
+
+    serialize(&crate_build_file(workspace_context, &package, None))
+  }
+
+  /** Renders the build file of a crate fetched into its own external repository. */
+  pub fn render_remote_crate(
+    &self,
+    workspace_context: &WorkspaceContext,
+    package: &CrateContext,
+  ) -> String {
+    let mut package = package.clone();
+    package.sort_dependencies_by_label();
+
+    // Path dependencies are built from their own directory, rather than an external repository
+    let repository_name = match package.source_details.path_data {
+      Some(_) => None,
+      None => Some(remote_crate_repository_name(workspace_context, &package)),
+    };
+    serialize(&crate_build_file(
+      workspace_context,
+      &package,
+      repository_name.as_deref(),
+    ))
+  }
+
+  pub fn render_aliases(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> String {
+    serialize(&workspace_build_file(workspace_context, all_packages))
+  }
+
+  pub fn render_bzl_fetch(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> String {
+    serialize(&remote_crates_bzl_file(workspace_context, all_packages))
+  }
+
+  /** Renders the module extension fetching the remote crates with bzlmod. */
+  pub fn render_bzl_module_extension(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> String {
+    serialize(&remote_crates_extension_bzl_file(
+      workspace_context,
+      all_packages,
+    ))
+  }
+
+  /** Renders the MODULE.bazel statements using the module extension of the remote crates. */
+  pub fn render_module_snippet(
+    &self,
+    workspace_context: &WorkspaceContext,
+    all_packages: &[CrateContext],
+  ) -> String {
+    serialize(&remote_crates_module_snippet(
+      workspace_context,
+      all_packages,
+    ))
+  }
+
+  /**
+   * Renders the build files of the crates in parallel, yielding the same files as rendering them
+   * one at a time.
+   *
+   * The files are collected in the order of the crates. Every crate is rendered even if another
+   * one fails, so that the error returned is always that of the first crate failing to render.
+   */
+  fn render_crate_files(
+    &self,
+    render_crate: fn(&Self, &WorkspaceContext, &CrateContext) -> String,
+    path_prefix: &str,
+    workspace_context: &WorkspaceContext,
+    crate_contexts: &[CrateContext],
+    on_crate_rendered: &CrateRenderedHook,
+  ) -> Result<Vec<FileOutputs>> {
+    crate_contexts
+      .par_iter()
+      .map(|package| {
+        let contents =
+          include_additional_build_file(package, render_crate(self, workspace_context, package))?;
+        on_crate_rendered(&package.pkg_name);
+
+        Ok(FileOutputs {
+          path: format!("{}/{}", path_prefix, package.expected_build_path),
+          contents,
+        })
+      })
+      .collect::<Vec<Result<FileOutputs>>>()
+      .into_iter()
+      .collect()
+  }
+}
+
+/**
+ * The statements of a crate's build file.
+ *
+ * Crates fetched into an external repository, named by `repository_name`, gather their sources in
+ * a filegroup of that repository rather than globbing them in each target.
+ */
+fn crate_build_file(
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+  repository_name: Option<&str>,
+) -> Vec<Stmt> {
+  let crate_name_sanitized = sanitize(&package.pkg_name);
+  let srcs = match repository_name {
+    Some(repository_name) => Expr::str_list(&[format!("@{}//:srcs", repository_name)]),
+    None => glob("**/*.rs"),
+  };
+
+  let mut rules = vec!["rust_binary"];
+  if package.raze_settings.gen_docs {
+    rules.extend(&["rust_doc", "rust_doc_test"]);
+  }
+  rules.push("rust_library");
+  if package
+    .targets
+    .iter()
+    .any(|target| target.kind == "proc-macro")
+  {
+    rules.push("rust_proc_macro");
+  }
+  rules.push("rust_test");
+
+  let default_visibility = if package.raze_settings.visibility.is_empty() {
+    Expr::List(vec![Expr::Commented(
+      Box::new(Expr::str("//visibility:public")),
+      format!(
+        "Prefer access through \"{}\"",
+        workspace_context.workspace_path
+      ),
+    )])
+  } else {
+    Expr::str_list(&package.raze_settings.visibility)
+  };
+
+  let mut stmts = vec![
+    generated_docstring(
+      workspace_context,
+      &format!(
+        "cargo-raze crate build file for {} {}.",
+        package.pkg_name, package.pkg_version
+      ),
+    ),
+    Stmt::Blank,
+    Stmt::Comment("buildifier: disable=load".to_owned()),
+    Stmt::Load {
+      module: format!(
+        "@{}//rust:rust.bzl",
+        workspace_context.rules_rust_repository_name
+      ),
+      symbols: rules.into_iter().map(str::to_owned).collect(),
+    },
+    Stmt::Blank,
+    Stmt::Comment("buildifier: disable=load".to_owned()),
+    Stmt::Load {
+      module: "@bazel_skylib//lib:selects.bzl".to_owned(),
+      symbols: vec!["selects".to_owned()],
+    },
+    Stmt::Blank,
+  ];
+  if !package.selectable_features.is_empty() {
+    stmts.extend(vec![
+      Stmt::Comment("buildifier: disable=load".to_owned()),
+      Stmt::Load {
+        module: "@bazel_skylib//rules:common_settings.bzl".to_owned(),
+        symbols: vec!["bool_flag".to_owned()],
+      },
+      Stmt::Blank,
+    ]);
+  }
+  stmts.extend(vec![
+    Stmt::Expr(
+      Call::new("package")
+        .named("default_visibility", default_visibility)
+        .into(),
+    ),
+    Stmt::Blank,
+    Stmt::Expr(
+      Call::new("licenses")
+        .arg(Expr::List(vec![Expr::Commented(
+          Box::new(Expr::str(&package.license.rating)),
+          package.license.name.clone(),
+        )]))
+        .into(),
+    ),
+    Stmt::Blank,
+  ]);
+
+  if repository_name.is_some() {
+    stmts.extend(vec![
+      Stmt::Comment("The sources of this crate, shared by its targets".to_owned()),
+      Stmt::Expr(
+        Call::new("filegroup")
+          .named("name", Expr::str("srcs"))
+          .named("srcs", glob("**/*.rs"))
+          .into(),
+      ),
+      Stmt::Blank,
+    ]);
+  }
+
+  if !package.selectable_features.is_empty() {
+    stmts.extend(feature_flag_stmts(package));
+  }
+
+  stmts.push(Stmt::Comment("Generated targets".to_owned()));
+
+  if package.build_script_target.is_some() {
+    stmts.extend(build_script_stmts(
+      workspace_context,
+      package,
+      &crate_name_sanitized,
+      &srcs,
+    ));
+  }
+
+  let mut main_lib_seen = false;
+  for target in &package.targets {
+    let is_lib = matches!(
+      target.kind.as_str(),
+      "lib" | "proc-macro" | "dylib" | "rlib"
+    );
+    // Only the first library is the one named after the crate
+    let is_main_lib = is_lib && !main_lib_seen;
+    main_lib_seen |= is_lib;
+
+    stmts.push(Stmt::Blank);
+    stmts.extend(target_stmts(
+      workspace_context,
+      package,
+      target,
+      is_main_lib,
+      &crate_name_sanitized,
+      &srcs,
+    ));
+  }
+
+  stmts
+}
+
+fn build_script_stmts(
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+  crate_name_sanitized: &str,
+  srcs: &Expr,
+) -> Vec<Stmt> {
+  let build_script_target = package.build_script_target.as_ref();
+  let crate_root = build_script_target
+    .map(|target| target.path.as_str())
+    .filter(|path| !path.is_empty())
+    .unwrap_or("build.rs");
+  let labels = package.default_deps.rendered_labels();
+
+  let mut call = Call::new("cargo_build_script")
+    .named(
+      "name",
+      Expr::Str(format!("{}_build_script", crate_name_sanitized)),
+    )
+    .named("srcs", srcs.clone())
+    .named("crate_root", Expr::str(crate_root))
+    .named("edition", Expr::str(&package.edition))
+    .named(
+      "deps",
+      with_targeted_deps_of_kind(
+        package,
+        Expr::str_list(&labels.build_dependencies),
+        |deps| &deps.build_dependencies,
+      ),
+    );
+  if !labels.build_proc_macro_dependencies.is_empty() {
+    call = call.named(
+      "proc_macro_deps",
+      Expr::str_list(&labels.build_proc_macro_dependencies),
+    );
+  }
+
+  call = call
+    .named("rustc_flags", Expr::str_list(&["--cap-lints=allow"]))
+    .named("crate_features", crate_features(package))
+    .named(
+      "build_script_env",
+      Expr::Dict(
+        package
+          .raze_settings
+          .buildrs_additional_environment_variables
+          .iter()
+          .map(|(key, value)| (Expr::str(key), Expr::str(value)))
+          .collect(),
+      ),
+    )
+    .named("data", glob("**"))
+    .named("tags", cargo_raze_tags(workspace_context))
+    .named("version", Expr::str(&package.pkg_version))
+    .named("visibility", Expr::str_list(&["//visibility:private"]));
+
+  vec![
+    Stmt::Blank,
+    Stmt::Comment("buildifier: disable=load-on-top".to_owned()),
+    Stmt::Load {
+      module: format!(
+        "@{}//cargo:cargo_build_script.bzl",
+        workspace_context.rules_rust_repository_name
+      ),
+      symbols: vec!["cargo_build_script".to_owned()],
+    },
+    Stmt::Blank,
+    Stmt::Comment("buildifier: leave-alone".to_owned()),
+    Stmt::Expr(call.into()),
+  ]
+}
+
+fn glob(pattern: &str) -> Expr {
+  Call::new("glob").arg(Expr::str_list(&[pattern])).into()
+}
+
+fn target_stmts(
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+  target: &BuildableTarget,
+  is_main_lib: bool,
+  crate_name_sanitized: &str,
+  srcs: &Expr,
+) -> Vec<Stmt> {
+  let target_name_sanitized = sanitize(&target.name);
+  let mut stmts = Vec::new();
+
+  let (rule, name, implicit_lib_dep) = match target.kind.as_str() {
+    "lib" | "proc-macro" | "dylib" | "rlib" => {
+      if is_main_lib && target_name_sanitized != crate_name_sanitized {
+        stmts.push(alias(
+          workspace_context,
+          crate_name_sanitized,
+          &format!(":{}", target_name_sanitized),
+        ));
+        stmts.push(Stmt::Blank);
+      }
+      let rule = if target.kind == "proc-macro" {
+        "rust_proc_macro"
+      } else {
+        "rust_library"
+      };
+      (rule, target_name_sanitized.clone(), false)
+    },
+    "bin" => (
+      "rust_binary",
+      format!("cargo_bin_{}", target_name_sanitized),
+      true,
+    ),
+    "test" => (
+      "rust_test",
+      format!("cargo_test_{}", target_name_sanitized),
+      true,
+    ),
+    "example" if package.raze_settings.gen_examples => (
+      "rust_binary",
+      format!("cargo_example_{}", target_name_sanitized),
+      true,
+    ),
+    _ => {
+      stmts.push(Stmt::Comment(format!(
+        "Unsupported target \"{}\" with type \"{}\" omitted",
+        target.name, target.kind
+      )));
+      return stmts;
+    },
+  };
+
+  let rule_call = |name: &str, crate_type: &str| {
+    let mut call = Call::new(rule).named("name", Expr::str(name));
+    if rule == "rust_library" {
+      call = call.named("crate_type", Expr::str(crate_type));
+    }
+    if is_main_lib {
+      // The library rule is named after its target, which may differ from the crate name
+      let lib_crate_name = sanitize(package.lib_target_name.as_ref().unwrap_or(&target.name));
+      if lib_crate_name != name {
+        call = call.named("crate_name", Expr::Str(lib_crate_name));
+      }
+    }
+
+    let mut deps = Vec::new();
+    if implicit_lib_dep {
+      if let Some(lib_target_name) = &package.lib_target_name {
+        deps.push(Expr::Str(format!(":{}", sanitize(lib_target_name))));
+      }
+    }
+    if package.build_script_target.is_some() {
+      deps.push(Expr::Str(format!(":{}_build_script", crate_name_sanitized)));
+    }
+    let dependencies = package
+      .target_dependencies
+      .get(&format!("{}:{}", target.kind, target.name))
+      .unwrap_or(&package.default_deps.dependencies);
+    deps.extend(
+      dependencies
+        .iter()
+        .map(|dep| Expr::str(&dep.buildable_target)),
+    );
+    if target.kind == "test" || target.kind == "example" {
+      let labels = package.default_deps.rendered_labels();
+      deps.extend(labels.dev_dependencies.iter().map(|label| Expr::str(label)));
+    }
+    call = call.named("deps", with_targeted_deps(package, Expr::List(deps)));
+    common_attrs(call, workspace_context, package, target, srcs)
+  };
+
+  stmts.push(Stmt::Comment("buildifier: leave-alone".to_owned()));
+  stmts.push(Stmt::Expr(rule_call(&name, &target.kind).into()));
+
+  // Library targets are the ones without an implicit dependency on the crate's library
+  if !implicit_lib_dep && package.raze_settings.gen_docs {
+    stmts.extend(doc_stmts(&target_name_sanitized));
+  }
+
+  // Alternate crate types of the library are built by rules of their own
+  if is_main_lib && rule == "rust_library" {
+    for crate_type in &package.raze_settings.crate_types {
+      stmts.push(Stmt::Blank);
+      stmts.push(Stmt::Comment("buildifier: leave-alone".to_owned()));
+      stmts.push(Stmt::Expr(
+        rule_call(&format!("{}_{}", name, crate_type), crate_type).into(),
+      ));
+    }
+  }
+  stmts
+}
+
+/** Documentation rules for a library target, both of which build against the library itself. */
+fn doc_stmts(target_name_sanitized: &str) -> Vec<Stmt> {
+  let doc_rule = |rule: &str, name: String| {
+    Stmt::Expr(
+      Call::new(rule)
+        .named("name", Expr::Str(name))
+        .named("dep", Expr::Str(format!(":{}", target_name_sanitized)))
+        .into(),
+    )
+  };
+
+  vec![
+    Stmt::Blank,
+    Stmt::Comment("buildifier: leave-alone".to_owned()),
+    doc_rule("rust_doc", format!("{}_doc", target_name_sanitized)),
+    Stmt::Blank,
+    Stmt::Comment("buildifier: leave-alone".to_owned()),
+    doc_rule(
+      "rust_doc_test",
+      format!("{}_doc_test", target_name_sanitized),
+    ),
+  ]
+}
+
+/** Appends the platform specific dependencies of the crate to its default dependencies. */
+fn with_targeted_deps(package: &CrateContext, deps: Expr) -> Expr {
+  if package.targeted_deps.is_empty() {
+    return deps;
+  }
+
+  let mut parts = vec![deps];
+  for targeted_dep in &package.targeted_deps {
+    parts.push(targeted_select(
+      targeted_dep,
+      &targeted_dep.deps.dependencies,
+    ));
+  }
+  Expr::Add(parts)
+}
+
+/**
+ * Appends the platform specific dependencies of one kind, such as the proc-macro dependencies, to
+ * the default ones of that kind.
+ *
+ * Only the platforms having dependencies of that kind get a select.
+ */
+fn with_targeted_deps_of_kind(
+  package: &CrateContext,
+  deps: Expr,
+  deps_of_kind: fn(&CrateDependencyContext) -> &Vec<BuildableDependency>,
+) -> Expr {
+  let mut parts = vec![deps];
+  for targeted_dep in &package.targeted_deps {
+    if !deps_of_kind(&targeted_dep.deps).is_empty() {
+      parts.push(targeted_select(
+        targeted_dep,
+        deps_of_kind(&targeted_dep.deps),
+      ));
+    }
+  }
+
+  match parts.len() {
+    1 => parts.remove(0),
+    _ => Expr::Add(parts),
+  }
+}
+
+/** A `bool_flag`, and the `config_setting` matching it, for each selectable feature. */
+fn feature_flag_stmts(package: &CrateContext) -> Vec<Stmt> {
+  let mut stmts = vec![Stmt::Comment(
+    "Flags toggling the optional features of the crate".to_owned(),
+  )];
+  for (index, selectable_feature) in package.selectable_features.iter().enumerate() {
+    if index > 0 {
+      stmts.push(Stmt::Blank);
+    }
+    let flag_name = format!("feature_{}", selectable_feature.name);
+    stmts.extend(vec![
+      Stmt::Expr(
+        Call::new("bool_flag")
+          .named("name", Expr::str(&flag_name))
+          .named("build_setting_default", Expr::Bool(false))
+          .into(),
+      ),
+      Stmt::Blank,
+      Stmt::Expr(
+        Call::new("config_setting")
+          .named("name", Expr::Str(format!("{}_enabled", flag_name)))
+          .named(
+            "flag_values",
+            Expr::Dict(vec![(
+              Expr::Str(format!(":{}", flag_name)),
+              Expr::str("true"),
+            )]),
+          )
+          .into(),
+      ),
+    ]);
+  }
+  stmts.push(Stmt::Blank);
+  stmts
+}
+
+/**
+ * The common features of the crate, followed by a select of its platform specific features and
+ * one select for each of its selectable features.
+ */
+fn crate_features(package: &CrateContext) -> Expr {
+  let mut parts = vec![Expr::str_list(&package.features)];
+  if !package.targeted_features.is_empty() {
+    let mut arms: Vec<(Expr, Expr)> = package
+      .targeted_features
+      .iter()
+      .map(|targeted_feature| {
+        (
+          Expr::Tuple(
+            targeted_feature
+              .conditions
+              .iter()
+              .map(|condition| Expr::str(condition))
+              .collect(),
+          ),
+          Expr::str_list(&targeted_feature.features),
+        )
+      })
+      .collect();
+    arms.push((Expr::str("//conditions:default"), Expr::List(Vec::new())));
+    parts.push(Call::new("selects.with_or").arg(Expr::Dict(arms)).into());
+  }
+  for selectable_feature in &package.selectable_features {
+    parts.push(
+      Call::new("select")
+        .arg(Expr::Dict(vec![
+          (
+            Expr::Str(format!(":feature_{}_enabled", selectable_feature.name)),
+            Expr::str_list(&selectable_feature.features),
+          ),
+          (Expr::str("//conditions:default"), Expr::List(Vec::new())),
+        ]))
+        .into(),
+    );
+  }
+
+  match parts.len() {
+    1 => parts.remove(0),
+    _ => Expr::Add(parts),
+  }
+}
+
+/** A select of the given dependencies under the conditions of a targeted dependency set. */
+fn targeted_select(targeted_dep: &CrateTargetedDepContext, deps: &[BuildableDependency]) -> Expr {
+  Call::new("selects.with_or")
+    .arg(Expr::Dict(vec![
+      (
+        Expr::Tuple(
+          targeted_dep
+            .conditions
+            .iter()
+            .map(|condition| Expr::str(condition))
+            .collect(),
+        ),
+        Expr::List(
+          deps
+            .iter()
+            .map(|dep| Expr::str(&dep.buildable_target))
+            .collect(),
+        ),
+      ),
+      (Expr::str("//conditions:default"), Expr::List(Vec::new())),
+    ]))
+    .into()
+}
+
+fn common_attrs(
+  mut call: Call,
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+  target: &BuildableTarget,
+  srcs: &Expr,
+) -> Call {
+  let settings = &package.raze_settings;
+  let labels = package.default_deps.rendered_labels();
+
+  call = call
+    .named("srcs", srcs.clone())
+    .named("crate_root", Expr::str(&target.path))
+    .named("edition", Expr::str(&target.edition));
+  let has_targeted_proc_macro_deps = package
+    .targeted_deps
+    .iter()
+    .any(|targeted_dep| !targeted_dep.deps.proc_macro_dependencies.is_empty());
+  if !labels.proc_macro_dependencies.is_empty() || has_targeted_proc_macro_deps {
+    call = call.named(
+      "proc_macro_deps",
+      with_targeted_deps_of_kind(
+        package,
+        Expr::str_list(&labels.proc_macro_dependencies),
+        |deps| &deps.proc_macro_dependencies,
+      ),
+    );
+  }
+
+  let mut rustc_flags = vec![Expr::str("--cap-lints=allow")];
+  rustc_flags.extend(settings.additional_flags.iter().map(|flag| Expr::str(flag)));
+  call = call.named("rustc_flags", Expr::List(rustc_flags));
+
+  if !settings.additional_env.is_empty() {
+    call = call.named(
+      "rustc_env",
+      Expr::Dict(
+        settings
+          .additional_env
+          .iter()
+          .map(|(key, value)| (Expr::str(key), Expr::str(value)))
+          .collect(),
+      ),
+    );
+  }
+  if !settings.data_attr.is_empty() {
+    call = call.named("data", raw_sum(&settings.data_attr));
+  }
+  if !settings.compile_data_attr.is_empty() {
+    call = call.named("compile_data", raw_sum(&settings.compile_data_attr));
+  }
+
+  call = call
+    .named("version", Expr::str(&package.pkg_version))
+    .named("tags", crate_tags(workspace_context, package))
+    .named("crate_features", crate_features(package));
+
+  let aliased_dependencies = &package.default_deps.aliased_dependencies;
+  if !aliased_dependencies.is_empty() {
+    call = call.named(
+      "aliases",
+      Expr::Dict(
+        aliased_dependencies
+          .iter()
+          .map(|alias| (Expr::str(&alias.target), Expr::str(&alias.alias)))
+          .collect(),
+      ),
+    );
+  }
+
+  call
+}
+
+/** The sum of user-provided Starlark expressions, such as the `data_attr` crate setting. */
+fn raw_sum(exprs: &[String]) -> Expr {
+  Expr::Add(exprs.iter().map(|expr| Expr::Raw(expr.clone())).collect())
+}
+
+fn workspace_build_file(
+  workspace_context: &WorkspaceContext,
+  all_packages: &[CrateContext],
+) -> Vec<Stmt> {
+  let mut stmts = vec![
+    generated_docstring(workspace_context, "cargo-raze workspace build file."),
+    Stmt::Blank,
+    Stmt::Expr(
+      Call::new("package")
+        .named(
+          "default_visibility",
+          Expr::str_list(&["//visibility:public"]),
+        )
+        .into(),
+    ),
+    Stmt::Blank,
+    Stmt::Expr(
+      Call::new("licenses")
+        .arg(Expr::List(vec![Expr::Commented(
+          Box::new(Expr::str("notice")),
+          "See individual crates for specific licenses".to_owned(),
+        )]))
+        .into(),
+    ),
+    Stmt::Blank,
+  ];
+
+  if all_packages.is_empty() {
+    stmts.push(Stmt::Comment("No targets defined".to_owned()));
+    return stmts;
+  }
+
+  let root_aliases = root_dependency_aliases(workspace_context.alias_name_style, all_packages);
+  stmts.push(Stmt::Comment("Aliased targets".to_owned()));
+  for package in all_packages {
+    if package.is_root_dependency && package.lib_target_name.is_some() {
+      let crate_name_sanitized = sanitize(&package.pkg_name);
+      let alias_key = root_alias_key(&package.pkg_name, &package.pkg_version);
+      for alias_name in root_aliases.get(&alias_key).into_iter().flatten() {
+        stmts.push(Stmt::Blank);
+        stmts.push(alias(
+          workspace_context,
+          alias_name,
+          &format!(
+            "{}:{}",
+            package.workspace_path_to_crate, crate_name_sanitized
+          ),
+        ));
+      }
+    }
+    for aliased_target in &package.raze_settings.extra_aliased_targets {
+      stmts.push(Stmt::Blank);
+      stmts.push(Stmt::Comment(
+        "Extra aliased target, from raze configuration".to_owned(),
+      ));
+      stmts.push(alias(
+        workspace_context,
+        aliased_target,
+        &format!("{}:{}", package.workspace_path_to_crate, aliased_target),
+      ));
+    }
+  }
+
+  stmts
+}
+
+/** The crates to fetch, leaving out path dependencies built from their own directory. */
+fn remote_packages(all_packages: &[CrateContext]) -> Vec<&CrateContext> {
+  all_packages
+    .iter()
+    .filter(|package| package.source_details.path_data.is_none())
+    .collect()
+}
+
+/**
+ * The body of a function fetching every remote crate, documented by `docstring`.
+ *
+ * The repository rules are wrapped in `maybe` when `wrap_in_maybe` is set.
+ */
+fn fetch_remote_crates_body(
+  workspace_context: &WorkspaceContext,
+  all_packages: &[CrateContext],
+  docstring: &str,
+  wrap_in_maybe: bool,
+) -> Vec<Stmt> {
+  let remote_packages = remote_packages(all_packages);
+
+  let mut body = Vec::new();
+  if remote_packages.is_empty() {
+    body.push(Stmt::Docstring(
+      "No crates were detected in the source Cargo.toml. This is a no-op".to_owned(),
+    ));
+    body.push(Stmt::Pass);
+  } else {
+    body.push(Stmt::Docstring(docstring.to_owned()));
+    for package in remote_packages {
+      body.push(Stmt::Expr(
+        remote_crate_repository(workspace_context, package, wrap_in_maybe).into(),
+      ));
+    }
+  }
+  body
+}
+
+// Not every repository rule is used by every workspace, so each load opts out of the lint
+fn repository_rule_load(module: &str, symbol: &str) -> Vec<Stmt> {
+  vec![
+    Stmt::Comment("buildifier: disable=load".to_owned()),
+    Stmt::Load {
+      module: module.to_owned(),
+      symbols: vec![symbol.to_owned()],
+    },
+  ]
+}
+
+fn remote_crates_bzl_file(
+  workspace_context: &WorkspaceContext,
+  all_packages: &[CrateContext],
+) -> Vec<Stmt> {
+  let mut stmts = vec![
+    generated_docstring(workspace_context, "cargo-raze crate workspace functions"),
+    Stmt::Blank,
+  ];
+  stmts.extend(repository_rule_load(
+    "@bazel_tools//tools/build_defs/repo:git.bzl",
+    "new_git_repository",
+  ));
+  stmts.extend(repository_rule_load(
+    "@bazel_tools//tools/build_defs/repo:http.bzl",
+    "http_archive",
+  ));
+  stmts.extend(repository_rule_load(
+    "@bazel_tools//tools/build_defs/repo:utils.bzl",
+    "maybe",
+  ));
+  stmts.push(Stmt::Blank);
+  stmts.push(Stmt::Def {
+    name: format!(
+      "{}_fetch_remote_crates",
+      workspace_context.gen_workspace_prefix
+    ),
+    params: Vec::new(),
+    body: fetch_remote_crates_body(
+      workspace_context,
+      all_packages,
+      "This function defines a collection of repos and should be called in a WORKSPACE file",
+      true, /* wrap_in_maybe */
+    ),
+  });
+  stmts
+}
+
+/** The name of the module extension fetching the remote crates. */
+fn module_extension_name(workspace_context: &WorkspaceContext) -> String {
+  format!("{}_crates", workspace_context.gen_workspace_prefix)
+}
+
+fn remote_crates_extension_bzl_file(
+  workspace_context: &WorkspaceContext,
+  all_packages: &[CrateContext],
+) -> Vec<Stmt> {
+  let extension_name = module_extension_name(workspace_context);
+  let implementation_name = format!("_{}_impl", extension_name);
+
+  let mut stmts = vec![
+    generated_docstring(workspace_context, "cargo-raze crate module extension"),
+    Stmt::Blank,
+  ];
+  stmts.extend(repository_rule_load(
+    "@bazel_tools//tools/build_defs/repo:git.bzl",
+    "new_git_repository",
+  ));
+  stmts.extend(repository_rule_load(
+    "@bazel_tools//tools/build_defs/repo:http.bzl",
+    "http_archive",
+  ));
+  stmts.push(Stmt::Blank);
+  stmts.push(Stmt::Def {
+    name: implementation_name.clone(),
+    params: vec!["module_ctx".to_owned()],
+    body: fetch_remote_crates_body(
+      workspace_context,
+      all_packages,
+      "This function defines a collection of repos and should be used as a module extension",
+      false, /* wrap_in_maybe */
+    ),
+  });
+  stmts.push(Stmt::Blank);
+  stmts.push(Stmt::Assign {
+    name: extension_name,
+    value: Call::new("module_extension")
+      .named("implementation", Expr::Ident(implementation_name))
+      .into(),
+  });
+  stmts
+}
+
+fn remote_crates_module_snippet(
+  workspace_context: &WorkspaceContext,
+  all_packages: &[CrateContext],
+) -> Vec<Stmt> {
+  let extension_name = module_extension_name(workspace_context);
+
+  let mut use_repo = Call::new("use_repo").arg(Expr::Ident(extension_name.clone()));
+  for package in remote_packages(all_packages) {
+    use_repo = use_repo.arg(Expr::Str(remote_crate_repository_name(
+      workspace_context,
+      package,
+    )));
+  }
+
+  let mut stmts = vec![
+    Stmt::Comment("@generated".to_owned()),
+    Stmt::Comment(
+      "cargo-raze MODULE.bazel statements, to be copied into the MODULE.bazel file".to_owned(),
+    ),
+    Stmt::Comment(String::new()),
+  ];
+  stmts.extend(
+    workspace_context
+      .generated_file_header
+      .lines()
+      .map(|line| Stmt::Comment(line.to_owned())),
+  );
+  stmts.extend(vec![
+    Stmt::Blank,
+    Stmt::Assign {
+      name: extension_name.clone(),
+      value: Call::new("use_extension")
+        .arg(Expr::Str(format!(
+          "{}:extensions.bzl",
+          workspace_context.workspace_path
+        )))
+        .arg(Expr::Str(extension_name))
+        .into(),
+    },
+    Stmt::Expr(use_repo.into()),
+  ]);
+  stmts
+}
+
+fn remote_crate_repository_name(
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+) -> String {
+  util::sanitize_repository_name(
+    &workspace_context.gen_workspace_prefix,
+    &package.pkg_name,
+    &package.pkg_version,
+  )
+}
+
+fn remote_crate_repository(
+  workspace_context: &WorkspaceContext,
+  package: &CrateContext,
+  wrap_in_maybe: bool,
+) -> Call {
+  let name = remote_crate_repository_name(workspace_context, package);
+  let build_file = Call::new("Label").arg(Expr::Str(format!(
+    "{}/remote:{}-{}.{}",
+    workspace_context.workspace_path,
+    package.pkg_name,
+    package.pkg_version,
+    package
+      .raze_settings
+      .buildfile_suffix_override
+      .as_ref()
+      .unwrap_or(&workspace_context.output_buildfile_suffix)
+  )));
+
+  let call = match &package.source_details.git_data {
+    Some(git_data) => repository_rule_call("new_git_repository", wrap_in_maybe)
+      .named("name", Expr::Str(name))
+      .named("remote", Expr::str(&git_data.remote))
+      .named("commit", Expr::str(&git_data.commit))
+      .named("build_file", build_file.into())
+      .named(
+        "init_submodules",
+        Expr::Bool(package.raze_settings.init_submodules),
+      ),
+    None => {
+      let mut call = repository_rule_call("http_archive", wrap_in_maybe)
+        .named("name", Expr::Str(name))
+        .named("url", Expr::str(&package.registry_url))
+        .named("type", Expr::str("tar.gz"));
+      if let Some(sha256) = &package.sha256 {
+        call = call.named("sha256", Expr::str(sha256));
+      }
+      call
+        .named(
+          "strip_prefix",
+          Expr::Str(format!("{}-{}", package.pkg_name, package.pkg_version)),
+        )
+        .named("build_file", build_file.into())
+    },
+  };
+
+  patch_attrs(call, package)
+}
+
+fn repository_rule_call(rule: &str, wrap_in_maybe: bool) -> Call {
+  if wrap_in_maybe {
+    Call::new("maybe").arg(Expr::Ident(rule.to_owned()))
+  } else {
+    Call::new(rule)
+  }
+}
+
+fn patch_attrs(mut call: Call, package: &CrateContext) -> Call {
+  let settings = &package.raze_settings;
+  if !settings.patches.is_empty() {
+    call = call.named("patches", Expr::str_list(&settings.patches));
+  }
+  if !settings.patch_args.is_empty() {
+    call = call.named("patch_args", Expr::str_list(&settings.patch_args));
+  }
+  if !settings.patch_cmds.is_empty() {
+    call = call.named("patch_cmds", Expr::str_list(&settings.patch_cmds));
+  }
+  if !settings.patch_cmds_win.is_empty() {
+    call = call.named("patch_cmds_win", Expr::str_list(&settings.patch_cmds_win));
+  }
+  if let Some(patch_tool) = &settings.patch_tool {
+    call = call.named("patch_tool", Expr::str(patch_tool));
+  }
+  call
+}
+
+impl BuildRenderer for StarlarkRenderer {
+  fn render_planned_build(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+  ) -> Result<Vec<FileOutputs>> {
+    self.render_planned_build_with_progress(render_details, planned_build, &ignore_crates_rendered)
+  }
+
+  fn render_remote_planned_build(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+  ) -> Result<Vec<FileOutputs>> {
+    self.render_remote_planned_build_with_progress(
+      render_details,
+      planned_build,
+      &ignore_crates_rendered,
+    )
+  }
+
+  fn render_planned_build_with_progress(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
+  ) -> Result<Vec<FileOutputs>> {
+    let render_details = &render_details.normalized()?;
+    let RenderDetails {
+      path_prefix, ..
+    } = render_details;
+    let PlannedBuild {
+      workspace_context,
+      crate_contexts,
+      ..
+    } = planned_build;
+
+    let mut file_outputs = self.render_crate_files(
+      Self::render_crate,
+      path_prefix,
+      workspace_context,
+      crate_contexts,
+      on_crate_rendered,
+    )?;
+
+    file_outputs.push(FileOutputs {
+      path: render_details.aliases_build_file_path(),
+      contents: self.render_aliases(workspace_context, crate_contexts),
+    });
+
+    normalize_line_endings(render_details, &mut file_outputs);
+    add_legacy_buildfile_stubs(render_details, &mut file_outputs);
+    Ok(file_outputs)
+  }
+
+  fn render_remote_planned_build_with_progress(
+    &mut self,
+    render_details: &RenderDetails,
+    planned_build: &PlannedBuild,
+    on_crate_rendered: &CrateRenderedHook,
+  ) -> Result<Vec<FileOutputs>> {
+    let render_details = &render_details.normalized()?;
+    let RenderDetails {
+      path_prefix,
+      buildfile_suffix,
+      ..
+    } = render_details;
+    let PlannedBuild {
+      workspace_context,
+      crate_contexts,
+      ..
+    } = planned_build;
+
+    // N.B. File needs to exist so that contained xyz-1.2.3.BUILD can be referenced
+    let mut file_outputs = vec![FileOutputs {
+      path: format!("{}/remote/{}", path_prefix, buildfile_suffix),
+      contents: String::new(),
+    }];
+
+    file_outputs.extend(self.render_crate_files(
+      Self::render_remote_crate,
+      path_prefix,
+      workspace_context,
+      crate_contexts,
+      on_crate_rendered,
+    )?);
+
+    file_outputs.push(FileOutputs {
+      path: render_details.aliases_build_file_path(),
+      contents: self.render_aliases(workspace_context, crate_contexts),
+    });
+
+    let fetched_crates = sort_by_repository_name(workspace_context, crate_contexts);
+    match render_details.fetch_mode {
+      FetchMode::Workspace => file_outputs.push(FileOutputs {
+        path: format!("{}/crates.bzl", path_prefix),
+        contents: self.render_bzl_fetch(workspace_context, &fetched_crates),
+      }),
+      FetchMode::Bzlmod => file_outputs.extend([
+        FileOutputs {
+          path: format!("{}/extensions.bzl", path_prefix),
+          contents: self.render_bzl_module_extension(workspace_context, &fetched_crates),
+        },
+        FileOutputs {
+          path: format!("{}/MODULE.bazel.snippet", path_prefix),
+          contents: self.render_module_snippet(workspace_context, &fetched_crates),
+        },
+      ]),
+    }
+
+    normalize_line_endings(render_details, &mut file_outputs);
+    add_legacy_buildfile_stubs(render_details, &mut file_outputs);
+    Ok(file_outputs)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::BTreeMap;
+
+  use crate::{
+    bazel::BazelRenderer,
+    context::{
+      CrateDependencyContext, CrateSelectableFeatureContext, CrateTargetedFeatureContext, GitRepo,
+      LicenseData, PathSource, SourceDetails,
+    },
+    settings::{AliasNameStyle, CrateSettings, FetchMode},
+  };
+
+  fn dummy_workspace_context() -> WorkspaceContext {
+    WorkspaceContext {
+      workspace_path: "//workspace/prefix".to_owned(),
+      gen_workspace_prefix: "raze".to_owned(),
+      output_buildfile_suffix: "BUILD".to_owned(),
+      rules_rust_repository_name: "io_bazel_rules_rust".to_owned(),
+      default_tags: vec!["cargo-raze".to_owned(), "manual".to_owned()],
+      generated_file_header: "DO NOT EDIT! Replaced on runs of cargo-raze".to_owned(),
+      alias_name_style: AliasNameStyle::Sanitize,
+    }
+  }
+
+  fn dummy_crate(name: &str, kind: &str) -> CrateContext {
+    CrateContext {
+      pkg_name: name.to_owned(),
+      pkg_version: "1.1.1".to_owned(),
+      edition: "2015".to_owned(),
+      raze_settings: CrateSettings::default(),
+      default_deps: CrateDependencyContext {
+        dependencies: Vec::new(),
+        proc_macro_dependencies: Vec::new(),
+        build_dependencies: Vec::new(),
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: Vec::new(),
+        aliased_dependencies: Vec::new(),
+      },
+      targeted_deps: Vec::new(),
+      target_dependencies: BTreeMap::new(),
+      license: LicenseData::default(),
+      features: vec!["feature1".to_owned()],
+      targeted_features: Vec::new(),
+      selectable_features: Vec::new(),
+      workspace_path_to_crate: format!("@raze__{}__1_1_1//", sanitize(name)),
+      is_root_dependency: true,
+      targets: vec![BuildableTarget {
+        name: name.to_owned(),
+        kind: kind.to_owned(),
+        path: "src/lib.rs".to_owned(),
+        edition: "2015".to_owned(),
+      }],
+      build_script_target: None,
+      source_details: SourceDetails {
+        git_data: None,
+        path_data: None,
+      },
+      sha256: Some("0".repeat(64)),
+      registry_url: format!("https://crates.io/api/v1/crates/{}/1.1.1/download", name),
+      expected_build_path: format!("remote/{}-1.1.1.BUILD", name),
+      lib_target_name: if kind == "lib" {
+        Some(name.to_owned())
+      } else {
+        None
+      },
+    }
+  }
+
+  fn dummy_planned_build(crate_contexts: Vec<CrateContext>) -> PlannedBuild {
+    PlannedBuild {
+      workspace_context: dummy_workspace_context(),
+      crate_contexts,
+      warnings: Vec::new(),
+    }
+  }
+
+  fn dummy_render_details() -> RenderDetails {
+    RenderDetails {
+      path_prefix: "./some_render_prefix".to_owned(),
+      buildfile_suffix: "BUILD".to_owned(),
+      legacy_buildfile_stubs: false,
+      fetch_mode: FetchMode::Workspace,
+      aliases_package: None,
+      normalize_line_endings: true,
+    }
+  }
+
+  /** Parses back a string literal produced by `quote`, following Starlark's escaping rules. */
+  fn unquote(literal: &str) -> String {
+    assert!(literal.len() >= 2 && literal.starts_with('"') && literal.ends_with('"'));
+    let mut chars = literal[1..literal.len() - 1].chars();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+      assert_ne!(c, '"', "unescaped quote in {}", literal);
+      assert!(
+        !c.is_control(),
+        "unescaped control character in {}",
+        literal
+      );
+      if c != '\\' {
+        value.push(c);
+        continue;
+      }
+      match chars.next() {
+        Some('n') => value.push('\n'),
+        Some('r') => value.push('\r'),
+        Some('t') => value.push('\t'),
+        Some(c @ '"') | Some(c @ '\\') => value.push(c),
+        Some(c) if c.is_digit(8) => {
+          let octal = std::iter::once(c)
+            .chain(chars.by_ref().take(2))
+            .collect::<String>();
+          value.push(std::char::from_u32(u32::from_str_radix(&octal, 8).unwrap()).unwrap());
+        },
+        other => panic!("invalid escape {:?} in {}", other, literal),
+      }
+    }
+    value
+  }
+
+  #[test]
+  fn quoting_escapes_special_characters() {
+    assert_eq!(quote("plain"), r#""plain""#);
+    assert_eq!(quote(r#"a"b"#), r#""a\"b""#);
+    assert_eq!(quote(r"a\b"), r#""a\\b""#);
+    assert_eq!(quote("a\nb\tc"), r#""a\nb\tc""#);
+    assert_eq!(quote("\u{1}"), r#""\001""#);
+  }
+
+  #[test]
+  fn quoted_strings_round_trip() {
+    // Exhaustively checks every short string over an alphabet of troublesome characters
+    let alphabet = [
+      'a', '"', '\\', '\'', '\n', '\r', '\t', '\u{0}', '\u{7f}', '#', '$', 'é',
+    ];
+    let mut values = vec![String::new()];
+    for _ in 0..3 {
+      let longer = values
+        .iter()
+        .flat_map(|value| alphabet.iter().map(move |c| format!("{}{}", value, c)))
+        .collect::<Vec<String>>();
+      values.extend(longer);
+    }
+
+    for value in values {
+      assert_eq!(unquote(&quote(&value)), value);
+    }
+  }
+
+  #[test]
+  fn serializes_nested_expressions() {
+    let stmts = vec![
+      Stmt::Load {
+        module: "//some:file.bzl".to_owned(),
+        symbols: vec!["a".to_owned(), "b".to_owned()],
+      },
+      Stmt::Expr(
+        Call::new("rule")
+          .named("name", Expr::str("some_name"))
+          .named("srcs", glob("**/*.rs"))
+          .named("flag", Expr::Bool(true))
+          .named(
+            "deps",
+            Expr::Add(vec![
+              Expr::str_list(&["//a", "//b"]),
+              Call::new("select")
+                .arg(Expr::Dict(vec![(
+                  Expr::Tuple(vec![Expr::str("//cond")]),
+                  Expr::List(Vec::new()),
+                )]))
+                .into(),
+            ]),
+          )
+          .into(),
+      ),
+    ];
+
+    assert_eq!(
+      serialize(&stmts),
+      r#"load(
+    "//some:file.bzl",
+    "a",
+    "b",
+)
+rule(
+    name = "some_name",
+    srcs = glob(["**/*.rs"]),
+    flag = True,
+    deps = [
+        "//a",
+        "//b",
+    ] + select(
+        {
+            ("//cond",): [],
+        },
+    ),
+)
+"#
+    );
+  }
+
+  #[test]
+  fn every_file_starts_with_the_generated_file_header() {
+    let mut workspace_context = dummy_workspace_context();
+    workspace_context.generated_file_header =
+      "Generated by cargo-raze.\nEdit Cargo.toml".to_owned();
+    let packages = vec![dummy_crate("test-library", "lib")];
+    let renderer = StarlarkRenderer::new();
+
+    assert!(renderer
+      .render_crate(&workspace_context, &packages[0])
+      .starts_with(
+        "\"\"\"\n@generated\ncargo-raze crate build file for test-library 1.1.1.\n\n\
+         Generated by cargo-raze.\nEdit Cargo.toml\n\"\"\"\n"
+      ));
+    for contents in &[
+      renderer.render_aliases(&workspace_context, &packages),
+      renderer.render_bzl_fetch(&workspace_context, &packages),
+      renderer.render_bzl_module_extension(&workspace_context, &packages),
+    ] {
+      assert!(contents.starts_with("\"\"\"\n@generated\n"), "{}", contents);
+      assert!(contents.contains("\n\nGenerated by cargo-raze.\nEdit Cargo.toml\n\"\"\"\n"));
+    }
+    assert!(renderer
+      .render_module_snippet(&workspace_context, &packages)
+      .contains("#\n# Generated by cargo-raze.\n# Edit Cargo.toml\n\n"));
+  }
+
+  #[test]
+  fn remote_crates_are_fetched_in_repository_name_order() {
+    let file_outputs = StarlarkRenderer::new()
+      .render_remote_planned_build(
+        &dummy_render_details(),
+        &dummy_planned_build(vec![
+          dummy_crate("zlib", "lib"),
+          dummy_crate("anyhow", "lib"),
+        ]),
+      )
+      .unwrap();
+
+    let crates_bzl = file_outputs
+      .iter()
+      .find(|output| output.path.ends_with("/crates.bzl"))
+      .unwrap();
+    let position_of = |name: &str| crates_bzl.contents.find(name).unwrap();
+    assert!(
+      position_of("name = \"raze__anyhow__1_1_1\"") < position_of("name = \"raze__zlib__1_1_1\"")
+    );
+  }
+
+  #[test]
+  fn crate_names_are_escaped_in_all_files() {
+    let mut package = dummy_crate(r#"we"ird\name"#, "bin");
+    package.features = vec![r#"feat"ure"#.to_owned(), "other".to_owned()];
+    package.license.name = "line\nbreak".to_owned();
+
+    let file_outputs = StarlarkRenderer::new()
+      .render_remote_planned_build(&dummy_render_details(), &dummy_planned_build(vec![package]))
+      .unwrap();
+
+    let contents = file_outputs
+      .iter()
+      .map(|output| output.contents.as_str())
+      .collect::<String>();
+    assert!(contents.contains(r#"name = "cargo_bin_we\"ird\\name","#));
+    assert!(contents.contains(r#""feat\"ure","#));
+    assert!(contents.contains(r#"strip_prefix = "we\"ird\\name-1.1.1","#));
+    assert!(contents.contains("# line break"));
+
+    // Every string literal, other than docstrings, must be closed on the line where it opens
+    let mut in_docstring = false;
+    for line in contents.lines() {
+      if line.matches(r#"""""#).count() % 2 == 1 {
+        in_docstring = !in_docstring;
+      }
+      if in_docstring {
+        continue;
+      }
+      let line = line.replace(r#"""""#, "");
+      let unescaped_quotes = line
+        .replace(r"\\", "")
+        .replace(r#"\""#, "")
+        .matches('"')
+        .count();
+      assert_eq!(unescaped_quotes % 2, 0, "unbalanced quotes in: {}", line);
+    }
+  }
+
+  #[test]
+  fn crates_get_rules_for_their_targets() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.targets.push(BuildableTarget {
+      name: "some_test".to_owned(),
+      kind: "test".to_owned(),
+      path: "tests/some_test.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    let proc_macro = dummy_crate("test-proc-macro", "proc-macro");
+
+    let renderer = StarlarkRenderer::new();
+    let workspace_context = dummy_workspace_context();
+    let library_contents = renderer.render_crate(&workspace_context, &library);
+    let proc_macro_contents = renderer.render_crate(&workspace_context, &proc_macro);
+
+    assert!(library_contents.contains("rust_library(\n    name = \"test_library\",\n"));
+    assert!(library_contents.contains("    crate_type = \"lib\",\n"));
+    assert!(library_contents.contains("rust_test(\n    name = \"cargo_test_some_test\",\n"));
+    assert!(library_contents.contains("    deps = [\":test_library\"],\n"));
+    assert!(!library_contents.contains("\"rust_proc_macro\""));
+    assert!(proc_macro_contents.contains("rust_proc_macro(\n    name = \"test_proc_macro\",\n"));
+    assert!(proc_macro_contents.contains("    \"rust_proc_macro\",\n"));
+  }
+
+  #[test]
+  fn rust_rules_are_loaded_from_the_rules_rust_repository() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.build_script_target = Some(BuildableTarget {
+      name: "build_script_build".to_owned(),
+      kind: "custom-build".to_owned(),
+      path: "build.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    let mut workspace_context = dummy_workspace_context();
+    workspace_context.rules_rust_repository_name = "rules_rust".to_owned();
+
+    let contents = StarlarkRenderer::new().render_crate(&workspace_context, &library);
+    assert!(contents.contains("    \"@rules_rust//rust:rust.bzl\",\n"));
+    assert!(contents.contains("load(\"@rules_rust//cargo:cargo_build_script.bzl\""));
+    assert!(!contents.contains("io_bazel_rules_rust"));
+  }
+
+  #[test]
+  fn targets_declare_their_crate_root() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.targets[0].path = "path/lib.rs".to_owned();
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains("    crate_root = \"path/lib.rs\",\n"));
+  }
+
+  #[test]
+  fn remote_crates_reference_the_sources_of_their_repository() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.build_script_target = Some(BuildableTarget {
+      kind: "custom-build".to_owned(),
+      name: "build-script-build".to_owned(),
+      path: "build.rs".to_owned(),
+      edition: "2018".to_owned(),
+    });
+    let renderer = StarlarkRenderer::new();
+
+    let remote_contents = renderer.render_remote_crate(&dummy_workspace_context(), &library);
+    assert!(remote_contents
+      .contains("filegroup(\n    name = \"srcs\",\n    srcs = glob([\"**/*.rs\"]),\n)"));
+    assert_eq!(
+      remote_contents
+        .matches("    srcs = [\"@raze__test_library__1_1_1//:srcs\"],\n")
+        .count(),
+      2
+    );
+
+    let vendored_contents = renderer.render_crate(&dummy_workspace_context(), &library);
+    assert!(!vendored_contents.contains("filegroup("));
+    assert_eq!(
+      vendored_contents
+        .matches("    srcs = glob([\"**/*.rs\"]),\n")
+        .count(),
+      2
+    );
+  }
+
+  #[test]
+  fn targets_are_tagged_with_default_and_crate_tags() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.raze_settings.tags = vec!["no-clippy".to_owned(), "raze-generated".to_owned()];
+    let mut workspace_context = dummy_workspace_context();
+    workspace_context.default_tags = vec!["raze-generated".to_owned()];
+
+    let renderer = StarlarkRenderer::new();
+    let crate_contents = renderer.render_crate(&workspace_context, &library);
+    let workspace_contents = renderer.render_aliases(&workspace_context, &[library]);
+
+    assert!(crate_contents
+      .contains("    tags = [\n        \"raze-generated\",\n        \"no-clippy\",\n    ],\n"));
+    assert!(workspace_contents.contains("    tags = [\"raze-generated\"],\n"));
+  }
+
+  #[test]
+  fn libraries_declare_a_crate_name_distinct_from_their_target_name() {
+    let mut library = dummy_crate("foo-bar", "lib");
+    let renderer = StarlarkRenderer::new();
+    let workspace_context = dummy_workspace_context();
+    assert!(!renderer
+      .render_crate(&workspace_context, &library)
+      .contains("crate_name"));
+
+    library.targets[0].name = "readable-name".to_owned();
+    let contents = renderer.render_crate(&workspace_context, &library);
+    assert!(contents.contains(
+      "rust_library(\n    name = \"readable_name\",\n    crate_type = \"lib\",\n    crate_name = \"foo_bar\",\n"
+    ));
+  }
+
+  #[test]
+  fn libraries_are_also_built_as_their_alternate_crate_types() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.raze_settings.crate_types = vec!["cdylib".to_owned(), "staticlib".to_owned()];
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains(
+      "rust_library(\n    name = \"test_library\",\n    crate_type = \"lib\",\n    deps"
+    ));
+    for crate_type in &["cdylib", "staticlib"] {
+      assert!(contents.contains(&format!(
+        "rust_library(\n    name = \"test_library_{0}\",\n    crate_type = \"{0}\",\n    \
+         crate_name = \"test_library\",\n    deps",
+        crate_type
+      )));
+    }
+  }
+
+  #[test]
+  fn crates_with_a_library_and_a_proc_macro_render_both() {
+    let mut library = dummy_crate("foo-bar", "lib");
+    library.lib_target_name = Some("foo_bar".to_owned());
+    library.targets.push(BuildableTarget {
+      name: "foo-bar-macros".to_owned(),
+      kind: "proc-macro".to_owned(),
+      path: "src/macros.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains("    \"rust_proc_macro\",\n"));
+    assert!(
+      contents.contains("rust_library(\n    name = \"foo_bar\",\n    crate_type = \"lib\",\n")
+    );
+    assert!(contents.contains("rust_proc_macro(\n    name = \"foo_bar_macros\",\n    deps"));
+    assert!(!contents.contains("alias("));
+    assert!(!contents.contains("crate_name"));
+  }
+
+  #[test]
+  fn libraries_get_doc_rules_when_enabled() {
+    let mut library = dummy_crate("test-library", "lib");
+    let renderer = StarlarkRenderer::new();
+    let workspace_context = dummy_workspace_context();
+
+    assert!(!renderer
+      .render_crate(&workspace_context, &library)
+      .contains("rust_doc"));
+
+    library.raze_settings.gen_docs = true;
+    let contents = renderer.render_crate(&workspace_context, &library);
+    assert!(contents.contains("    \"rust_doc\",\n    \"rust_doc_test\",\n"));
+    assert!(contents
+      .contains("rust_doc(\n    name = \"test_library_doc\",\n    dep = \":test_library\",\n)"));
+    assert!(contents.contains(
+      "rust_doc_test(\n    name = \"test_library_doc_test\",\n    dep = \":test_library\",\n)"
+    ));
+  }
+
+  #[test]
+  fn examples_get_binary_rules_when_enabled() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.targets.push(BuildableTarget {
+      name: "some_example".to_owned(),
+      kind: "example".to_owned(),
+      path: "examples/some_example.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    let renderer = StarlarkRenderer::new();
+    let workspace_context = dummy_workspace_context();
+
+    assert!(!renderer
+      .render_crate(&workspace_context, &library)
+      .contains("cargo_example_some_example"));
+
+    library.raze_settings.gen_examples = true;
+    let contents = renderer.render_crate(&workspace_context, &library);
+    assert!(contents.contains("rust_binary(\n    name = \"cargo_example_some_example\",\n"));
+    assert!(contents.contains("    deps = [\":test_library\"],\n"));
+  }
+
+  #[test]
+  fn multiple_versions_of_a_root_crate_get_versioned_aliases() {
+    let library = dummy_crate("test-library", "lib");
+    let older_library = CrateContext {
+      pkg_version: "0.7.3".to_owned(),
+      workspace_path_to_crate: "@raze__test_library__0_7_3//".to_owned(),
+      ..library.clone()
+    };
+
+    let contents =
+      StarlarkRenderer::new().render_aliases(&dummy_workspace_context(), &[library, older_library]);
+    assert!(!contents.contains("name = \"test_library\","));
+    assert!(contents.contains(
+      "alias(\n    name = \"test_library-1\",\n    actual = \"@raze__test_library__1_1_1//:test_library\","
+    ));
+    assert!(contents.contains(
+      "alias(\n    name = \"test_library-0_7_3\",\n    actual = \"@raze__test_library__0_7_3//:test_library\","
+    ));
+  }
+
+  #[test]
+  fn targeted_proc_macro_deps_are_selected_in_proc_macro_deps() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.targeted_deps = vec![CrateTargetedDepContext {
+      target: "cfg(unix)".to_owned(),
+      deps: CrateDependencyContext {
+        dependencies: Vec::new(),
+        proc_macro_dependencies: vec![BuildableDependency {
+          name: "unix-macro".to_owned(),
+          version: "1.0.0".to_owned(),
+          buildable_target: "@raze__unix_macro__1_0_0//:unix_macro".to_owned(),
+          is_proc_macro: true,
+        }],
+        build_dependencies: Vec::new(),
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: Vec::new(),
+        aliased_dependencies: Vec::new(),
+      },
+      conditions: vec!["//:unix".to_owned()],
+    }];
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains(
+      "    proc_macro_deps = [] + selects.with_or(\n        {\n            (\"//:unix\",): \
+       [\"@raze__unix_macro__1_0_0//:unix_macro\"],\n"
+    ));
+    assert!(contents
+      .contains("    deps = [] + selects.with_or(\n        {\n            (\"//:unix\",): [],\n"));
+  }
+
+  #[test]
+  fn targeted_build_deps_are_selected_in_the_build_script_deps() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.build_script_target = Some(BuildableTarget {
+      name: "build_script_build".to_owned(),
+      kind: "custom-build".to_owned(),
+      path: "build.rs".to_owned(),
+      edition: "2015".to_owned(),
+    });
+    library.targeted_deps = vec![CrateTargetedDepContext {
+      target: "cfg(windows)".to_owned(),
+      deps: CrateDependencyContext {
+        dependencies: Vec::new(),
+        proc_macro_dependencies: Vec::new(),
+        build_dependencies: vec![BuildableDependency {
+          name: "winres".to_owned(),
+          version: "1.0.0".to_owned(),
+          buildable_target: "@raze__winres__1_0_0//:winres".to_owned(),
+          is_proc_macro: false,
+        }],
+        build_proc_macro_dependencies: Vec::new(),
+        dev_dependencies: Vec::new(),
+        aliased_dependencies: Vec::new(),
+      },
+      conditions: vec!["//:windows".to_owned()],
+    }];
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains(
+      "    deps = [] + selects.with_or(\n        {\n            (\"//:windows\",): \
+       [\"@raze__winres__1_0_0//:winres\"],\n"
+    ));
+    assert_eq!(contents.matches("@raze__winres__1_0_0//:winres").count(), 1);
+  }
+
+  #[test]
+  fn targeted_features_are_selected_in_crate_features() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.targeted_features = vec![CrateTargetedFeatureContext {
+      features: vec!["simd".to_owned()],
+      conditions: vec!["//:x86_64".to_owned()],
+    }];
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(contents.contains(
+      "    crate_features = [\"feature1\"] + selects.with_or(\n        {\n            \
+       (\"//:x86_64\",): [\"simd\"],\n            \"//conditions:default\": [],\n        },\n    ),\n"
+    ));
+  }
+
+  #[test]
+  fn additional_target_deps_are_added_to_their_target() {
+    let dependency = |label: &str| BuildableDependency {
+      name: label.to_owned(),
+      version: String::new(),
+      buildable_target: label.to_owned(),
+      is_proc_macro: false,
+    };
+    let mut binary = dummy_crate("test-binary", "bin");
+    binary.default_deps.dependencies = vec![dependency("@raze__log__1_0_0//:log")];
+    binary.target_dependencies.insert(
+      "bin:test-binary".to_owned(),
+      vec![
+        dependency("//cli:clap"),
+        dependency("@raze__log__1_0_0//:log"),
+      ],
+    );
+    binary.target_dependencies.insert(
+      "bin:other-binary".to_owned(),
+      vec![dependency("//other:dep")],
+    );
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &binary);
+    assert!(contents.contains(
+      "    deps = [\n        \"//cli:clap\",\n        \"@raze__log__1_0_0//:log\",\n    ],\n"
+    ));
+    assert!(!contents.contains("//other:dep"));
+  }
+
+  #[test]
+  fn selectable_features_get_flags_and_selects() {
+    let mut library = dummy_crate("test-library", "lib");
+    library.selectable_features = vec![CrateSelectableFeatureContext {
+      name: "nightly".to_owned(),
+      features: vec!["fast".to_owned(), "nightly".to_owned()],
+    }];
+
+    let contents = StarlarkRenderer::new().render_crate(&dummy_workspace_context(), &library);
+    assert!(
+      contents.contains("load(\"@bazel_skylib//rules:common_settings.bzl\", \"bool_flag\")\n")
+    );
+    assert!(contents.contains(
+      "bool_flag(\n    name = \"feature_nightly\",\n    build_setting_default = False,\n)\n"
+    ));
+    assert!(contents.contains(
+      "config_setting(\n    name = \"feature_nightly_enabled\",\n    flag_values = {\n        \
+       \":feature_nightly\": \"true\",\n    },\n)\n"
+    ));
+    assert!(contents.contains(
+      "    crate_features = [\"feature1\"] + select(\n        {\n            \
+       \":feature_nightly_enabled\": [\n                \"fast\",\n                \"nightly\",\n            \
+       ],\n            \"//conditions:default\": [],\n        },\n    ),\n"
+    ));
+  }
+
+  #[test]
+  fn remote_crates_get_repository_rules() {
+    let registry_crate = dummy_crate("test-library", "lib");
+    let mut git_crate = dummy_crate("git-library", "lib");
+    git_crate.source_details.git_data = Some(GitRepo {
+      remote: "https://github.com/some/repo".to_owned(),
+      commit: "abcdef".to_owned(),
+    });
+
+    let contents = StarlarkRenderer::new()
+      .render_bzl_fetch(&dummy_workspace_context(), &[registry_crate, git_crate]);
+
+    assert!(contents.contains("def raze_fetch_remote_crates():\n"));
+    assert!(contents.contains("    maybe(\n        http_archive,\n"));
+    assert!(contents.contains("        name = \"raze__test_library__1_1_1\",\n"));
+    assert!(contents.contains(&format!("        sha256 = \"{}\",\n", "0".repeat(64))));
+    assert!(contents.contains("    maybe(\n        new_git_repository,\n"));
+    assert!(contents.contains("        commit = \"abcdef\",\n"));
+    assert!(contents.contains(
+      "        build_file = Label(\"//workspace/prefix/remote:git-library-1.1.1.BUILD\"),\n"
+    ));
+  }
+
+  #[test]
+  fn git_crates_initialize_submodules_unless_disabled() {
+    let mut git_crate = dummy_crate("git-library", "lib");
+    git_crate.source_details.git_data = Some(GitRepo {
+      remote: "https://github.com/some/repo".to_owned(),
+      commit: "abcdef".to_owned(),
+    });
+    let renderer = StarlarkRenderer::new();
+
+    let contents = renderer.render_bzl_fetch(&dummy_workspace_context(), &[git_crate.clone()]);
+    assert!(contents.contains("        init_submodules = True,\n"));
+
+    git_crate.raze_settings.init_submodules = false;
+    let contents = renderer.render_bzl_fetch(&dummy_workspace_context(), &[git_crate]);
+    assert!(contents.contains("        init_submodules = False,\n"));
+  }
+
+  #[test]
+  fn remote_crates_get_a_module_extension() {
+    let workspace_context = dummy_workspace_context();
+    let crates = [dummy_crate("test-library", "lib")];
+    let renderer = StarlarkRenderer::new();
+
+    let extension = renderer.render_bzl_module_extension(&workspace_context, &crates);
+    assert!(extension.contains("def _raze_crates_impl(module_ctx):\n"));
+    assert!(
+      extension.contains("    http_archive(\n        name = \"raze__test_library__1_1_1\",\n")
+    );
+    assert!(extension
+      .contains("raze_crates = module_extension(\n    implementation = _raze_crates_impl,\n)\n"));
+    assert!(!extension.contains("maybe"));
+
+    let module_snippet = renderer.render_module_snippet(&workspace_context, &crates);
+    assert!(module_snippet.contains(
+      "raze_crates = use_extension(\"//workspace/prefix:extensions.bzl\", \"raze_crates\")\n\
+       use_repo(raze_crates, \"raze__test_library__1_1_1\")\n"
+    ));
+  }
+
+  #[test]
+  fn path_dependencies_are_not_fetched() {
+    let registry_crate = dummy_crate("test-library", "lib");
+    let mut local_crate = dummy_crate("local-library", "lib");
+    local_crate.source_details.path_data = Some(PathSource {
+      path: "../local-library".to_owned(),
+    });
+
+    let contents = StarlarkRenderer::new().render_bzl_fetch(
+      &dummy_workspace_context(),
+      &[registry_crate, local_crate.clone()],
+    );
+
+    assert!(contents.contains("name = \"raze__test_library__1_1_1\""));
+    assert!(!contents.contains("local_library"));
+
+    let contents =
+      StarlarkRenderer::new().render_remote_crate(&dummy_workspace_context(), &local_crate);
+    assert!(contents.contains("    srcs = glob([\"**/*.rs\"]),\n"));
+    assert!(!contents.contains("//:srcs"));
+  }
+
+  #[test]
+  fn renders_the_same_files_as_the_template_renderer() {
+    let planned_build = dummy_planned_build(vec![
+      dummy_crate("test-library", "lib"),
+      dummy_crate("test-binary", "bin"),
+    ]);
+
+    let paths = |file_outputs: Vec<FileOutputs>| {
+      file_outputs
+        .into_iter()
+        .map(|output| output.path)
+        .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+      paths(
+        StarlarkRenderer::new()
+          .render_remote_planned_build(&dummy_render_details(), &planned_build)
+          .unwrap()
+      ),
+      paths(
+        BazelRenderer::new()
+          .render_remote_planned_build(&dummy_render_details(), &planned_build)
+          .unwrap()
+      )
+    );
+    let bzlmod_render_details = RenderDetails {
+      fetch_mode: FetchMode::Bzlmod,
+      ..dummy_render_details()
+    };
+    assert_eq!(
+      paths(
+        StarlarkRenderer::new()
+          .render_remote_planned_build(&bzlmod_render_details, &planned_build)
+          .unwrap()
+      ),
+      paths(
+        BazelRenderer::new()
+          .render_remote_planned_build(&bzlmod_render_details, &planned_build)
+          .unwrap()
+      )
+    );
+    assert_eq!(
+      paths(
+        StarlarkRenderer::new()
+          .render_planned_build(&dummy_render_details(), &planned_build)
+          .unwrap()
+      ),
+      paths(
+        BazelRenderer::new()
+          .render_planned_build(&dummy_render_details(), &planned_build)
+          .unwrap()
+      )
+    );
+  }
+}
@@ -13,8 +13,9 @@
 // limitations under the License.
 
 use std::{
-  fmt,
+  fmt, fs,
   iter::Iterator,
+  path::{Component, Path, PathBuf},
   process::Command,
   str::{self, FromStr},
 };
@@ -23,6 +24,8 @@ use anyhow::Result;
 
 use cargo_platform::Cfg;
 
+use sha2::{Digest, Sha256};
+
 pub const PLEASE_FILE_A_BUG: &str =
   "Please file an issue at github.com/google/cargo-raze with details.";
 
@@ -163,6 +166,59 @@ pub fn sanitize_ident(ident: &str) -> String {
   slug::slugify(&ident).replace("-", "_")
 }
 
+/**
+ * Computes the name of the external repository holding a remote crate, in the form
+ * {prefix}__{name}__{version}.
+ *
+ * Characters Bazel does not allow in repository names, such as the "-" of crate names or the "."
+ * and "+" of versions, become "_". The prefix leads the name, so crate names starting with a digit
+ * still make valid repository names.
+ *
+ * Every renderer names repositories through this, as the generated aliases and dependency labels
+ * point into them.
+ */
+pub fn sanitize_repository_name(prefix: &str, name: &str, version: &str) -> String {
+  let name = name
+    .chars()
+    .map(|c| match c.is_ascii_alphanumeric() {
+      true => c,
+      false => '_',
+    })
+    .collect::<String>();
+  format!("{}__{}__{}", prefix, name, sanitize_ident(version))
+}
+
+/**
+ * Computes the path of `path` relative to the `base` directory.
+ *
+ * Both paths are expected to be absolute. Components of `base` which are not shared with `path`
+ * are walked back up with "..".
+ */
+pub fn relative_path(base: &Path, path: &Path) -> PathBuf {
+  let base_components = base.components().collect::<Vec<Component>>();
+  let path_components = path.components().collect::<Vec<Component>>();
+  let common_len = base_components
+    .iter()
+    .zip(path_components.iter())
+    .take_while(|(a, b)| a == b)
+    .count();
+
+  let mut relative = PathBuf::new();
+  for _ in common_len..base_components.len() {
+    relative.push("..");
+  }
+  for component in &path_components[common_len..] {
+    relative.push(component.as_os_str());
+  }
+  relative
+}
+
+/** Computes the hex encoded sha256 digest of the contents of a file. */
+pub fn sha256_of_file(path: &Path) -> Result<String> {
+  let contents = fs::read(path)?;
+  Ok(format!("{:x}", Sha256::digest(&contents)))
+}
+
 /** Gets the proper system attributes for the provided platform triple using rustc. */
 fn fetch_attrs(target: &str) -> Result<Vec<Cfg>> {
   let args = vec![format!("--target={}", target), "--print=cfg".to_owned()];
@@ -195,6 +251,51 @@ fn fetch_attrs(target: &str) -> Result<Vec<Cfg>> {
 mod tests {
   use super::*;
 
+  #[test]
+  fn test_sha256_of_file() {
+    assert_eq!(
+      sha256_of_file(Path::new("test_fixtures/test-crate-0.1.0.crate")).unwrap(),
+      "c5b0fcb3cef4a47add42b02f834b55e5a3df346749ece821f6dee1de11f8b4d6"
+    );
+    assert!(sha256_of_file(Path::new("test_fixtures/not-a-file.crate")).is_err());
+  }
+
+  #[test]
+  fn test_sanitize_repository_name() {
+    assert_eq!(
+      sanitize_repository_name("raze", "test-binary", "1.1.1"),
+      "raze__test_binary__1_1_1"
+    );
+    assert_eq!(
+      sanitize_repository_name("raze", "some.crate", "0.2.0"),
+      "raze__some_crate__0_2_0"
+    );
+    assert_eq!(
+      sanitize_repository_name("raze", "semver_crate", "0.2.0-beta.1+build.5"),
+      "raze__semver_crate__0_2_0_beta_1_build_5"
+    );
+    assert_eq!(
+      sanitize_repository_name("raze", "2d-geometry", "1.0.0"),
+      "raze__2d_geometry__1_0_0"
+    );
+  }
+
+  #[test]
+  fn test_relative_path() {
+    assert_eq!(
+      relative_path(Path::new("/repo/cargo"), Path::new("/repo/cargo/local/foo")),
+      PathBuf::from("local/foo")
+    );
+    assert_eq!(
+      relative_path(Path::new("/repo/cargo"), Path::new("/repo/foo")),
+      PathBuf::from("../foo")
+    );
+    assert_eq!(
+      relative_path(Path::new("/repo/cargo"), Path::new("/repo/cargo")),
+      PathBuf::new()
+    );
+  }
+
   #[test]
   fn test_collect_up_to_works_for_zero() {
     let test_items: Vec<u32> = Vec::new();